@@ -14,7 +14,10 @@ use mapsdk::{
         image_tiled_layer::{ImageTiledLayer, ImageTiledLayerOptions},
         vector_tiled_layer::{VectorTiledLayer, VectorTiledLayerOptions},
     },
-    map::{Map, MapOptions, MapViewChange},
+    map::{
+        interaction::{MapInteraction, MapInteractionOptions},
+        Map, MapOptions, MapViewChange,
+    },
     utils::{color::Color, proj::lonlat_to_wm},
     Canvas,
 };
@@ -30,24 +33,10 @@ use winit::{
 
 struct App {
     map: Option<Map>,
-    motion: Motion,
+    interaction: MapInteraction,
     rng: rand::rngs::ThreadRng,
 }
 
-#[derive(Default)]
-struct Motion {
-    cursor_position: Option<Coord>,
-
-    dragging: bool,
-    drag_start_cursor_position: Option<Coord>,
-    drag_start_map_center: Option<Coord>,
-
-    rotating: bool,
-    rotate_start_cursor_position: Option<Coord>,
-    pitch_start_value: f64,
-    yaw_start_value: f64,
-}
-
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.map.is_none() {
@@ -206,6 +195,10 @@ impl ApplicationHandler for App {
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         if let Some(map) = &mut self.map {
+            if self.interaction.handle_window_event(map, &event) {
+                return;
+            }
+
             match event {
                 WindowEvent::CloseRequested => {
                     event_loop.exit();
@@ -216,102 +209,6 @@ impl ApplicationHandler for App {
                 WindowEvent::Resized(size) => {
                     map.resize(size.width, size.height);
                 }
-                WindowEvent::MouseInput { state, button, .. } => match state {
-                    ElementState::Pressed => {
-                        if button == MouseButton::Left {
-                            self.motion.dragging = true;
-                            self.motion.drag_start_cursor_position = None;
-                        } else if button == MouseButton::Right {
-                            self.motion.rotating = true;
-                            self.motion.rotate_start_cursor_position = None;
-                        }
-                    }
-                    ElementState::Released => {
-                        if button == MouseButton::Left {
-                            self.motion.dragging = false;
-                        } else if button == MouseButton::Right {
-                            self.motion.rotating = false;
-                        }
-                    }
-                },
-                WindowEvent::CursorMoved { position, .. } => {
-                    self.motion.cursor_position = Some(Coord {
-                        x: position.x,
-                        y: position.y,
-                    });
-
-                    if self.motion.dragging {
-                        match self.motion.drag_start_cursor_position {
-                            Some(drag_start_cursor_position) => {
-                                if let Some(drag_start_map_center) =
-                                    self.motion.drag_start_map_center
-                                {
-                                    let map_res = map.resolution().unwrap();
-                                    let map_yaw = map.yaw();
-                                    let dx = (position.x - drag_start_cursor_position.x) * map_res;
-                                    let dy = (drag_start_cursor_position.y - position.y) * map_res;
-                                    let cos_map_yaw = map_yaw.to_radians().cos();
-                                    let sin_map_yaw = map_yaw.to_radians().sin();
-                                    let new_center = drag_start_map_center
-                                        - Coord {
-                                            x: dx * cos_map_yaw - dy * sin_map_yaw,
-                                            y: dy * cos_map_yaw + dx * sin_map_yaw,
-                                        };
-                                    map.set_center(new_center);
-                                }
-                            }
-                            None => {
-                                self.motion.drag_start_cursor_position = Some(Coord {
-                                    x: position.x,
-                                    y: position.y,
-                                });
-                                self.motion.drag_start_map_center = map.center();
-                            }
-                        }
-                    } else if self.motion.rotating {
-                        match self.motion.rotate_start_cursor_position {
-                            Some(rotate_start_cursor_position) => {
-                                let w = map.width().unwrap() as f64;
-                                let h = map.height().unwrap() as f64;
-                                let dx = position.x - rotate_start_cursor_position.x;
-                                let dy = position.y - rotate_start_cursor_position.y;
-                                map.set_pitch_yaw(
-                                    self.motion.pitch_start_value
-                                        + (dy / h) * map.options().pitch_max,
-                                    self.motion.yaw_start_value - (dx / w) * 90.0,
-                                )
-                            }
-                            None => {
-                                self.motion.rotate_start_cursor_position = Some(Coord {
-                                    x: position.x,
-                                    y: position.y,
-                                });
-                                self.motion.pitch_start_value = map.pitch();
-                                self.motion.yaw_start_value = map.yaw();
-                            }
-                        }
-                    }
-                }
-                WindowEvent::MouseWheel { delta, .. } => {
-                    let coord = if let Some(cursor_position) = self.motion.cursor_position {
-                        map.to_map(&cursor_position)
-                    } else {
-                        map.center()
-                    };
-
-                    if let Some(coord) = coord {
-                        match delta {
-                            MouseScrollDelta::LineDelta(_, y) => {
-                                let scalar = 2.0_f64.powf((y / 20.0).into());
-                                map.zoom_around(&coord, scalar);
-                            }
-                            MouseScrollDelta::PixelDelta(p) => {
-                                let scalar = 2.0_f64.powf(p.y / 20.0);
-                                map.zoom_around(&coord, scalar);
-                            }
-                        }
-                    }
-                }
                 WindowEvent::KeyboardInput {
                     event:
                         KeyEvent {
@@ -368,7 +265,7 @@ pub fn main() {
 
     let mut app = App {
         map: None,
-        motion: Motion::default(),
+        interaction: MapInteraction::new(MapInteractionOptions::default()),
         rng: rand::thread_rng(),
     };
 
@@ -1,4 +1,44 @@
+use std::time::Duration;
+
+use geo::Coord;
+
+use crate::tiling::TileId;
+
 #[derive(Debug)]
-pub enum Event {
+pub(crate) enum Event {
     MapRequestRedraw,
+    Publish(MapEvent),
+}
+
+/// Events published by a map, see [`crate::map::Map::subscribe`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapEvent {
+    /// An `ease_to`, `fly_to` or `jump_to` transition reached its target view.
+    AnimationFinished,
+    FrameRendered {
+        elapsed: Duration,
+    },
+    LayerAdded {
+        name: String,
+    },
+    LayerRemoved {
+        name: String,
+    },
+    MoveEnd,
+    MoveStart,
+    TileFailed {
+        layer: String,
+        tile_id: TileId,
+        error: String,
+    },
+    TileLoaded {
+        layer: String,
+        tile_id: TileId,
+    },
+    ViewChanged {
+        center: Coord,
+        zoom_res: f64,
+        pitch: f64,
+        yaw: f64,
+    },
 }
@@ -118,6 +118,24 @@ impl<T: CoordType> Shape<T> {
         }
     }
 
+    /// Geometry type as used in style filters, i.e. `Point`, `LineString` or `Polygon`.
+    pub fn geometry_type(&self) -> &'static str {
+        match self {
+            Shape::Geometry(geom) => match geom {
+                Geometry::Point(_) | Geometry::MultiPoint(_) => "Point",
+                Geometry::Line(_) | Geometry::LineString(_) | Geometry::MultiLineString(_) => {
+                    "LineString"
+                }
+                Geometry::Polygon(_)
+                | Geometry::MultiPolygon(_)
+                | Geometry::Rect(_)
+                | Geometry::Triangle(_) => "Polygon",
+                Geometry::GeometryCollection(_) => "Unknown",
+            },
+            Shape::Circle { .. } => "Polygon",
+        }
+    }
+
     pub fn is_points(&self) -> bool {
         match self {
             Shape::Geometry(geom) => match geom {
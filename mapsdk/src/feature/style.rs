@@ -1,14 +1,41 @@
-use crate::utils::color::Color;
+use std::{borrow::Cow, error::Error, str::FromStr};
 
-#[derive(Clone, Debug)]
+use crate::{
+    feature::style::expression::{EvaluationContext, Expression, Value},
+    utils::color::Color,
+};
+
+pub mod expression;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ShapeStyles {
     pub fill_enabled: bool,
     pub fill_color: Color,
+    /// Name of an icon added by `Map::add_icon`, repeated over fills instead of the fill color.
+    pub fill_pattern: Option<String>,
+    /// Gradient drawn instead of the fill color, the fill pattern takes precedence.
+    pub fill_gradient: Option<FillGradient>,
     pub stroke_enabled: bool,
     pub stroke_color: Color,
     pub stroke_width: f32,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    /// Max ratio of the miter length to the stroke width, sharper miter joins are beveled.
+    pub miter_limit: f32,
+    /// Lengths of alternating dashes and gaps in pixels, strokes are solid if not set.
+    pub dash_array: Option<Vec<f32>>,
+    /// Name of an icon added by `Map::add_icon`, repeated along strokes instead of the stroke
+    /// color.
+    pub stroke_pattern: Option<String>,
     pub symbol_size: f32,
+    /// Marker shape of points, filled and stroked like polygons.
+    pub symbol_shape: SymbolShape,
     pub outline_align: OutlineAlign,
+    pub visible: bool,
+    pub expressions: ShapeStyleExpressions,
+    pub label: Option<LabelStyle>,
+    /// Icon drawn instead of the marker shape of points.
+    pub icon: Option<IconStyle>,
 }
 
 impl Default for ShapeStyles {
@@ -16,11 +43,505 @@ impl Default for ShapeStyles {
         Self {
             fill_enabled: true,
             fill_color: Color::from_rgba(255, 0, 0, 0.7),
+            fill_pattern: None,
+            fill_gradient: None,
             stroke_enabled: true,
             stroke_color: Color::from_rgb(0, 0, 0),
             stroke_width: 1.8,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 2.0,
+            dash_array: None,
+            stroke_pattern: None,
             symbol_size: 4.0,
+            symbol_shape: SymbolShape::Circle,
             outline_align: OutlineAlign::Center,
+            visible: true,
+            expressions: ShapeStyleExpressions::default(),
+            label: None,
+            icon: None,
+        }
+    }
+}
+
+impl ShapeStyles {
+    /// Upper bound of the symbol size and stroke width features resolve to at a zoom, `None`
+    /// when an expression of them is unbounded.
+    pub fn max_size(&self, zoom: f64) -> Option<f32> {
+        let max = |size: f32, expression: &Option<Expression>| match expression {
+            Some(expression) => expression.max_number(zoom).map(|max| size.max(max as f32)),
+            None => Some(size),
+        };
+
+        Some(
+            max(self.symbol_size, &self.expressions.symbol_size)?
+                .max(max(self.stroke_width, &self.expressions.stroke_width)?),
+        )
+    }
+
+    pub fn is_data_driven(&self) -> bool {
+        self.expressions != ShapeStyleExpressions::default()
+    }
+
+    /// Whether a feature is visible and passes the filter, without resolving the other styles.
+    pub fn is_visible(&self, context: &EvaluationContext) -> bool {
+        let visible = self
+            .expressions
+            .visible
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_bool())
+            .unwrap_or(self.visible);
+
+        visible && self.matches_filter(context)
+    }
+
+    /// Label style of a visible feature, without resolving the other styles. The label style is
+    /// only cloned if it has expressions.
+    pub fn resolve_label(&self, context: &EvaluationContext) -> Option<Cow<'_, LabelStyle>> {
+        let label = self.label.as_ref()?;
+        if !self.is_visible(context) {
+            return None;
+        }
+
+        let ShapeStyleExpressions {
+            label_size,
+            label_color,
+            label_halo_color,
+            label_halo_width,
+            ..
+        } = &self.expressions;
+        if [label_size, label_color, label_halo_color, label_halo_width]
+            .iter()
+            .all(|e| e.is_none())
+        {
+            return Some(Cow::Borrowed(label));
+        }
+
+        let mut label = label.clone();
+        self.expressions.resolve_label(&mut label, context);
+        Some(Cow::Owned(label))
+    }
+
+    /// Whether a feature passes the filter, features without a filter always do.
+    pub fn matches_filter(&self, context: &EvaluationContext) -> bool {
+        match &self.expressions.filter {
+            Some(filter) => filter.evaluate(context) == Value::Bool(true),
+            None => true,
+        }
+    }
+
+    pub fn is_zoom_dependent(&self) -> bool {
+        let ShapeStyleExpressions {
+            fill_color,
+            stroke_color,
+            stroke_width,
+            miter_limit,
+            symbol_size,
+            symbol_shape,
+            visible,
+            filter,
+            label_size,
+            label_color,
+            label_halo_color,
+            label_halo_width,
+            icon_name,
+            icon_size,
+            icon_rotation,
+            icon_tint,
+        } = &self.expressions;
+
+        [
+            fill_color,
+            stroke_color,
+            stroke_width,
+            miter_limit,
+            symbol_size,
+            symbol_shape,
+            visible,
+            filter,
+            label_size,
+            label_color,
+            label_halo_color,
+            label_halo_width,
+            icon_name,
+            icon_size,
+            icon_rotation,
+            icon_tint,
+        ]
+        .iter()
+        .any(|e| e.as_ref().is_some_and(|e| e.is_zoom_dependent()))
+    }
+
+    /// Evaluate the expressions for a feature, values fall back to the static ones when an
+    /// expression does not evaluate to the expected type.
+    pub fn resolve(&self, context: &EvaluationContext) -> ShapeStyles {
+        let mut shape_styles = self.clone();
+        shape_styles.expressions = ShapeStyleExpressions::default();
+
+        let ShapeStyleExpressions {
+            fill_color,
+            stroke_color,
+            stroke_width,
+            miter_limit,
+            symbol_size,
+            symbol_shape,
+            visible: _,
+            filter: _,
+            label_size: _,
+            label_color: _,
+            label_halo_color: _,
+            label_halo_width: _,
+            icon_name,
+            icon_size,
+            icon_rotation,
+            icon_tint,
+        } = &self.expressions;
+
+        if let Some(v) = fill_color
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_color())
+        {
+            shape_styles.fill_color = v;
+        }
+        if let Some(v) = stroke_color
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_color())
+        {
+            shape_styles.stroke_color = v;
+        }
+        if let Some(v) = stroke_width
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_number())
+        {
+            shape_styles.stroke_width = v as f32;
+        }
+        if let Some(v) = miter_limit
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_number())
+        {
+            shape_styles.miter_limit = v as f32;
+        }
+        if let Some(v) = symbol_size
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_number())
+        {
+            shape_styles.symbol_size = v as f32;
+        }
+        if let Some(Value::String(v)) = symbol_shape.as_ref().map(|e| e.evaluate(context)) {
+            if let Ok(v) = v.parse() {
+                shape_styles.symbol_shape = v;
+            }
+        }
+        shape_styles.visible = self.is_visible(context);
+        if let Some(label) = &mut shape_styles.label {
+            self.expressions.resolve_label(label, context);
+        }
+        if let Some(icon) = &mut shape_styles.icon {
+            if let Some(Value::String(v)) = icon_name.as_ref().map(|e| e.evaluate(context)) {
+                icon.name = v;
+            }
+            if let Some(v) = icon_size
+                .as_ref()
+                .and_then(|e| e.evaluate(context).as_number())
+            {
+                icon.size = v as f32;
+            }
+            if let Some(v) = icon_rotation
+                .as_ref()
+                .and_then(|e| e.evaluate(context).as_number())
+            {
+                icon.rotation = v as f32;
+            }
+            if let Some(v) = icon_tint
+                .as_ref()
+                .and_then(|e| e.evaluate(context).as_color())
+            {
+                icon.tint = Some(v);
+            }
+        }
+
+        shape_styles
+    }
+}
+
+/// Expressions computing style values per feature, overriding the static values if set.
+///
+/// Expressions evaluate to single values, so array styles, i.e. dash arrays and offsets, are
+/// static. Label and icon expressions apply if the label or icon style is set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapeStyleExpressions {
+    pub fill_color: Option<Expression>,
+    pub stroke_color: Option<Expression>,
+    pub stroke_width: Option<Expression>,
+    pub miter_limit: Option<Expression>,
+    pub symbol_size: Option<Expression>,
+    /// Name of a marker shape, e.g. `["match", ["get", "kind"], "shop", "square", "circle"]`.
+    pub symbol_shape: Option<Expression>,
+    pub visible: Option<Expression>,
+    /// Features are only drawn if the filter evaluates to true, e.g. to style a source layer with
+    /// multiple rules.
+    pub filter: Option<Expression>,
+    pub label_size: Option<Expression>,
+    pub label_color: Option<Expression>,
+    pub label_halo_color: Option<Expression>,
+    pub label_halo_width: Option<Expression>,
+    pub icon_name: Option<Expression>,
+    pub icon_size: Option<Expression>,
+    /// Icon rotation in degrees, e.g. `["get", "heading"]` for vehicles.
+    pub icon_rotation: Option<Expression>,
+    pub icon_tint: Option<Expression>,
+}
+
+impl ShapeStyleExpressions {
+    fn resolve_label(&self, label: &mut LabelStyle, context: &EvaluationContext) {
+        if let Some(v) = self
+            .label_size
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_number())
+        {
+            label.size = v as f32;
+        }
+        if let Some(v) = self
+            .label_color
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_color())
+        {
+            label.color = v;
+        }
+        if let Some(v) = self
+            .label_halo_color
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_color())
+        {
+            label.halo_color = v;
+        }
+        if let Some(v) = self
+            .label_halo_width
+            .as_ref()
+            .and_then(|e| e.evaluate(context).as_number())
+        {
+            label.halo_width = v as f32;
+        }
+    }
+}
+
+/// Vector marker shapes of points, sized to fit a square of the symbol size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolShape {
+    Circle,
+    Square,
+    /// Triangle pointing north.
+    Triangle,
+    Diamond,
+    /// Five pointed star.
+    Star,
+    Cross,
+    /// Arrow pointing north.
+    Arrow,
+}
+
+impl FromStr for SymbolShape {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "circle" => Ok(SymbolShape::Circle),
+            "square" => Ok(SymbolShape::Square),
+            "triangle" => Ok(SymbolShape::Triangle),
+            "diamond" => Ok(SymbolShape::Diamond),
+            "star" => Ok(SymbolShape::Star),
+            "cross" => Ok(SymbolShape::Cross),
+            "arrow" => Ok(SymbolShape::Arrow),
+            _ => Err(format!("Unknown symbol shape {}", s).into()),
+        }
+    }
+}
+
+/// Gradient filling each feature, positioned relative to its bounding box from `[0, 0]` at the
+/// top left to `[1, 1]` at the bottom right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FillGradient {
+    pub kind: GradientKind,
+    /// Colors at offsets from 0 to 1 in ascending order, up to 8 stops are used.
+    pub stops: Vec<(f32, Color)>,
+}
+
+impl FillGradient {
+    pub fn linear(start: [f32; 2], end: [f32; 2], stops: Vec<(f32, Color)>) -> Self {
+        Self {
+            kind: GradientKind::Linear { start, end },
+            stops,
+        }
+    }
+
+    pub fn radial(center: [f32; 2], radius: f32, stops: Vec<(f32, Color)>) -> Self {
+        Self {
+            kind: GradientKind::Radial { center, radius },
+            stops,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Colors change along the line from start to end.
+    Linear { start: [f32; 2], end: [f32; 2] },
+    /// Colors change from the center to the radius, elliptical for non-square bounding boxes.
+    Radial { center: [f32; 2], radius: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabelStyle {
+    /// Text of the label, usually an attribute, e.g. `Expression::Get("name".to_string())`.
+    pub text: Expression,
+    /// Name of a font added by `Map::add_font`.
+    pub font: String,
+    pub size: f32,
+    pub color: Color,
+    pub halo_color: Color,
+    pub halo_width: f32,
+    /// Offset in pixels, x is along the line for line placement.
+    pub offset: [f32; 2],
+    pub anchor: Anchor,
+    pub placement: LabelPlacement,
+    /// Space in pixels kept around labels when detecting collisions.
+    pub padding: f32,
+}
+
+impl Default for LabelStyle {
+    fn default() -> Self {
+        Self {
+            text: Expression::Get("name".to_string()),
+            font: "sans".to_string(),
+            size: 14.0,
+            color: Color::from_rgb(0, 0, 0),
+            halo_color: Color::from_rgb(255, 255, 255),
+            halo_width: 1.0,
+            offset: [0.0, 0.0],
+            anchor: Anchor::Center,
+            placement: LabelPlacement::Point,
+            padding: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IconStyle {
+    /// Name of an icon added by `Map::add_icon`.
+    pub name: String,
+    /// Scale of the icon size.
+    pub size: f32,
+    /// Rotation in degrees clockwise.
+    pub rotation: f32,
+    pub rotation_alignment: RotationAlignment,
+    pub anchor: Anchor,
+    /// Offset in pixels, rotated with the icon.
+    pub offset: [f32; 2],
+    /// Color multiplied with the icon colors, e.g. to color white icons.
+    pub tint: Option<Color>,
+}
+
+impl Default for IconStyle {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            size: 1.0,
+            rotation: 0.0,
+            rotation_alignment: RotationAlignment::Screen,
+            anchor: Anchor::Center,
+            offset: [0.0, 0.0],
+            tint: None,
+        }
+    }
+}
+
+/// What the rotation of icons is relative to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RotationAlignment {
+    /// Relative to north, icons rotate with the map, e.g. vehicles by their heading.
+    Map,
+    /// Relative to the top of the screen, icons stay upright when the map rotates.
+    Screen,
+}
+
+/// Part of a label or icon placed at its anchor point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    Center,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor as fractions of the width and height, from the top left.
+    pub fn position(&self) -> (f32, f32) {
+        match self {
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelPlacement {
+    /// At points, the middle of lines and inside polygons.
+    Point,
+    /// Along lines, following their curvature.
+    Line,
+}
+
+/// Shape of the ends of open lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    /// Extended by half the stroke width.
+    Square,
+}
+
+impl FromStr for LineCap {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "butt" => Ok(LineCap::Butt),
+            "round" => Ok(LineCap::Round),
+            "square" => Ok(LineCap::Square),
+            _ => Err(format!("Unknown line cap {}", s).into()),
+        }
+    }
+}
+
+/// Shape of the outer side of turns in lines and outlines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Bevel,
+    Round,
+}
+
+impl FromStr for LineJoin {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "miter" => Ok(LineJoin::Miter),
+            "bevel" => Ok(LineJoin::Bevel),
+            "round" => Ok(LineJoin::Round),
+            _ => Err(format!("Unknown line join {}", s).into()),
         }
     }
 }
@@ -30,3 +551,155 @@ pub enum OutlineAlign {
     Center,
     Side,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{feature::style::expression::Interpolation, JsonValue};
+
+    #[test]
+    fn test_resolve() {
+        let shape_styles = ShapeStyles {
+            expressions: ShapeStyleExpressions {
+                fill_color: Some(
+                    r##"["match", ["get", "class"], "park", "#00ff00", "#0000ff"]"##
+                        .parse()
+                        .unwrap(),
+                ),
+                stroke_width: Some(r#"["*", ["get", "lanes"], 2]"#.parse().unwrap()),
+                visible: Some(r#"["!=", ["get", "class"], "hidden"]"#.parse().unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(shape_styles.is_data_driven());
+
+        let attrs = Some(HashMap::from([
+            ("class".to_string(), JsonValue::from("park")),
+            ("lanes".to_string(), JsonValue::from(3)),
+        ]));
+        let resolved = shape_styles.resolve(&EvaluationContext::new(&attrs));
+        assert_eq!(resolved.fill_color, Color::from_rgb(0, 255, 0));
+        assert_eq!(resolved.stroke_width, 6.0);
+        assert!(resolved.visible);
+        assert!(!resolved.is_data_driven());
+
+        let attrs = Some(HashMap::from([(
+            "class".to_string(),
+            JsonValue::from("hidden"),
+        )]));
+        let resolved = shape_styles.resolve(&EvaluationContext::new(&attrs));
+        assert_eq!(resolved.fill_color, Color::from_rgb(0, 0, 255));
+        assert_eq!(resolved.stroke_width, 1.8);
+        assert!(!resolved.visible);
+    }
+
+    #[test]
+    fn test_resolve_icon() {
+        let shape_styles = ShapeStyles {
+            icon: Some(IconStyle {
+                name: "car".to_string(),
+                ..Default::default()
+            }),
+            expressions: ShapeStyleExpressions {
+                icon_name: Some(r#"["get", "kind"]"#.parse().unwrap()),
+                icon_rotation: Some(r#"["get", "heading"]"#.parse().unwrap()),
+                icon_tint: Some(
+                    r##"["match", ["get", "kind"], "bus", "#ff0000", "none"]"##
+                        .parse()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let attrs = Some(HashMap::from([
+            ("kind".to_string(), JsonValue::from("bus")),
+            ("heading".to_string(), JsonValue::from(90)),
+        ]));
+        let icon = shape_styles
+            .resolve(&EvaluationContext::new(&attrs))
+            .icon
+            .unwrap();
+        assert_eq!(icon.name, "bus");
+        assert_eq!(icon.rotation, 90.0);
+        assert_eq!(icon.tint, Some(Color::from_rgb(255, 0, 0)));
+
+        let icon = shape_styles
+            .resolve(&EvaluationContext::new(&None))
+            .icon
+            .unwrap();
+        assert_eq!(icon.name, "car");
+        assert_eq!(icon.rotation, 0.0);
+        assert_eq!(icon.tint, None);
+    }
+
+    #[test]
+    fn test_resolve_zoom_stops() {
+        let shape_styles = ShapeStyles {
+            label: Some(LabelStyle::default()),
+            expressions: ShapeStyleExpressions {
+                miter_limit: Some(Expression::zoom_step(2.0, vec![(10.0, 4.0)])),
+                label_size: Some(Expression::zoom_interpolate(
+                    Interpolation::Linear,
+                    vec![(10.0, 10.0), (14.0, 18.0)],
+                )),
+                label_halo_color: Some(Expression::zoom_step(
+                    Color::from_rgb(255, 255, 255),
+                    vec![(12.0, Color::from_rgb(0, 0, 0))],
+                )),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(shape_styles.is_zoom_dependent());
+
+        let resolve =
+            |zoom: f64| shape_styles.resolve(&EvaluationContext::new(&None).with_zoom(zoom));
+
+        let resolved = resolve(8.0);
+        let label = resolved.label.unwrap();
+        assert_eq!(resolved.miter_limit, 2.0);
+        assert_eq!(label.size, 10.0);
+        assert_eq!(label.halo_color, Color::from_rgb(255, 255, 255));
+
+        let resolved = resolve(12.0);
+        let label = resolved.label.unwrap();
+        assert_eq!(resolved.miter_limit, 4.0);
+        assert_eq!(label.size, 14.0);
+        assert_eq!(label.halo_color, Color::from_rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn test_resolve_symbol_shape() {
+        let shape_styles = ShapeStyles {
+            symbol_shape: SymbolShape::Square,
+            expressions: ShapeStyleExpressions {
+                symbol_shape: Some(
+                    r#"["match", ["get", "kind"], "shop", "star", "park", "tree", "triangle"]"#
+                        .parse()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let resolve = |kind: &str| {
+            let attrs = Some(HashMap::from([("kind".to_string(), JsonValue::from(kind))]));
+            shape_styles
+                .resolve(&EvaluationContext::new(&attrs))
+                .symbol_shape
+        };
+        assert_eq!(resolve("shop"), SymbolShape::Star);
+        assert_eq!(resolve("school"), SymbolShape::Triangle);
+        // Unknown shape names fall back to the static shape
+        assert_eq!(resolve("park"), SymbolShape::Square);
+
+        assert_eq!("arrow".parse::<SymbolShape>().unwrap(), SymbolShape::Arrow);
+        assert!("hexagon".parse::<SymbolShape>().is_err());
+    }
+}
@@ -0,0 +1,842 @@
+use std::{collections::HashMap, error::Error, str::FromStr};
+
+use crate::{
+    utils::color::{Color, ColorRamp},
+    JsonValue,
+};
+
+/// Style expression evaluated per feature, in the JSON array form of MapLibre expressions,
+/// e.g. `["match", ["get", "highway"], "motorway", "#e892a2", "#ffffff"]`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Get(String),
+    Has(String),
+    GeometryType,
+    Zoom,
+    Compare(CompareOp, Box<Expression>, Box<Expression>),
+    All(Vec<Expression>),
+    Any(Vec<Expression>),
+    Not(Box<Expression>),
+    Math(MathOp, Vec<Expression>),
+    Coalesce(Vec<Expression>),
+    Rgba(Vec<Expression>),
+    Match {
+        input: Box<Expression>,
+        cases: Vec<(Vec<Value>, Expression)>,
+        fallback: Box<Expression>,
+    },
+    Case {
+        branches: Vec<(Expression, Expression)>,
+        fallback: Box<Expression>,
+    },
+    Step {
+        input: Box<Expression>,
+        base: Box<Expression>,
+        stops: Vec<(f64, Expression)>,
+    },
+    Interpolate {
+        interpolation: Interpolation,
+        input: Box<Expression>,
+        stops: Vec<(f64, Expression)>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MathOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Exponential interpolation with the given base, a base of 1 is linear.
+    Exponential(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Color(Color),
+}
+
+impl Value {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            Value::Color(v) => Some(v.clone()),
+            Value::String(v) => v.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(v) => *v,
+            Value::Number(v) => *v != 0.0,
+            Value::String(v) => !v.is_empty(),
+            Value::Color(_) => true,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Number(v)
+    }
+}
+
+impl From<Color> for Value {
+    fn from(v: Color) -> Self {
+        Value::Color(v)
+    }
+}
+
+impl From<&JsonValue> for Value {
+    fn from(json: &JsonValue) -> Self {
+        match json {
+            JsonValue::Bool(v) => Value::Bool(*v),
+            JsonValue::Number(v) => v.as_f64().map_or(Value::Null, Value::Number),
+            JsonValue::String(v) => Value::String(v.clone()),
+            _ => Value::Null,
+        }
+    }
+}
+
+/// Inputs available to expressions when evaluating them for a feature.
+#[derive(Clone, Debug, Default)]
+pub struct EvaluationContext<'a> {
+    pub attrs: Option<&'a HashMap<String, JsonValue>>,
+    /// Geometry type of the feature, see [`crate::feature::Shape::geometry_type`].
+    pub geometry_type: Option<&'a str>,
+    /// Fractional zoom of the map view.
+    pub zoom: f64,
+}
+
+impl<'a> EvaluationContext<'a> {
+    pub fn new(attrs: &'a Option<HashMap<String, JsonValue>>) -> Self {
+        Self {
+            attrs: attrs.as_ref(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_geometry_type(mut self, v: &'a str) -> Self {
+        self.geometry_type = Some(v);
+        self
+    }
+
+    pub fn with_zoom(mut self, v: f64) -> Self {
+        self.zoom = v;
+        self
+    }
+}
+
+impl Expression {
+    /// Interpolate values over zoom, e.g. line widths growing with zoom.
+    pub fn zoom_interpolate(
+        interpolation: Interpolation,
+        stops: Vec<(f64, impl Into<Value>)>,
+    ) -> Self {
+        Expression::Interpolate {
+            interpolation,
+            input: Box::new(Expression::Zoom),
+            stops: stops
+                .into_iter()
+                .map(|(stop, value)| (stop, Expression::Literal(value.into())))
+                .collect(),
+        }
+    }
+
+    /// Color an input from `min` to `max` with the stops of a color ramp, e.g. a numeric
+    /// attribute with `ColorRamp::viridis()`. Colors between stops are interpolated in linear
+    /// space like in other expressions, so they match [`ColorRamp::sample`].
+    pub fn color_ramp(input: Expression, min: f64, max: f64, ramp: &ColorRamp) -> Self {
+        Expression::Interpolate {
+            interpolation: Interpolation::Linear,
+            input: Box::new(input),
+            stops: ramp
+                .stops()
+                .iter()
+                .map(|(stop, color)| {
+                    (
+                        min + (max - min) * stop,
+                        Expression::Literal(Value::Color(color.clone())),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Step values over zoom, `base` applies below the first stop.
+    pub fn zoom_step(base: impl Into<Value>, stops: Vec<(f64, impl Into<Value>)>) -> Self {
+        Expression::Step {
+            input: Box::new(Expression::Zoom),
+            base: Box::new(Expression::Literal(base.into())),
+            stops: stops
+                .into_iter()
+                .map(|(stop, value)| (stop, Expression::Literal(value.into())))
+                .collect(),
+        }
+    }
+
+    pub fn is_zoom_dependent(&self) -> bool {
+        match self {
+            Expression::Zoom => true,
+            Expression::Literal(_)
+            | Expression::Get(_)
+            | Expression::Has(_)
+            | Expression::GeometryType => false,
+            Expression::Compare(_, a, b) => a.is_zoom_dependent() || b.is_zoom_dependent(),
+            Expression::Not(item) => item.is_zoom_dependent(),
+            Expression::All(items)
+            | Expression::Any(items)
+            | Expression::Math(_, items)
+            | Expression::Coalesce(items)
+            | Expression::Rgba(items) => items.iter().any(|item| item.is_zoom_dependent()),
+            Expression::Match {
+                input,
+                cases,
+                fallback,
+            } => {
+                input.is_zoom_dependent()
+                    || fallback.is_zoom_dependent()
+                    || cases.iter().any(|(_, output)| output.is_zoom_dependent())
+            }
+            Expression::Case { branches, fallback } => {
+                fallback.is_zoom_dependent()
+                    || branches.iter().any(|(condition, output)| {
+                        condition.is_zoom_dependent() || output.is_zoom_dependent()
+                    })
+            }
+            Expression::Step { input, base, stops } => {
+                input.is_zoom_dependent()
+                    || base.is_zoom_dependent()
+                    || stops.iter().any(|(_, output)| output.is_zoom_dependent())
+            }
+            Expression::Interpolate { input, stops, .. } => {
+                input.is_zoom_dependent()
+                    || stops.iter().any(|(_, output)| output.is_zoom_dependent())
+            }
+        }
+    }
+
+    /// Upper bound of the numbers the expression evaluates to at a zoom, for any feature.
+    /// Outputs that are not numbers are ignored, `None` when the numbers are unbounded, e.g.
+    /// read from attributes or computed.
+    pub fn max_number(&self, zoom: f64) -> Option<f64> {
+        fn max_of<'a>(items: impl IntoIterator<Item = &'a Expression>, zoom: f64) -> Option<f64> {
+            items.into_iter().try_fold(f64::NEG_INFINITY, |max, item| {
+                Some(max.max(item.max_number(zoom)?))
+            })
+        }
+
+        match self {
+            Expression::Literal(value) => Some(value.as_number().unwrap_or(f64::NEG_INFINITY)),
+            Expression::Zoom => Some(zoom),
+            Expression::Get(_) | Expression::Math(..) => None,
+            Expression::Has(_)
+            | Expression::GeometryType
+            | Expression::Compare(..)
+            | Expression::All(_)
+            | Expression::Any(_)
+            | Expression::Not(_)
+            | Expression::Rgba(_) => Some(f64::NEG_INFINITY),
+            Expression::Coalesce(items) => max_of(items, zoom),
+            Expression::Match {
+                cases, fallback, ..
+            } => max_of(
+                cases
+                    .iter()
+                    .map(|(_, output)| output)
+                    .chain([fallback.as_ref()]),
+                zoom,
+            ),
+            Expression::Case { branches, fallback } => max_of(
+                branches
+                    .iter()
+                    .map(|(_, output)| output)
+                    .chain([fallback.as_ref()]),
+                zoom,
+            ),
+            // Interpolated values stay between the outputs of their stops
+            Expression::Step { base, stops, .. } => max_of(
+                [base.as_ref()]
+                    .into_iter()
+                    .chain(stops.iter().map(|(_, output)| output)),
+                zoom,
+            ),
+            Expression::Interpolate { stops, .. } => {
+                max_of(stops.iter().map(|(_, output)| output), zoom)
+            }
+        }
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let items = match json {
+            JsonValue::Array(items) => items,
+            _ => return Ok(Expression::Literal(json.into())),
+        };
+
+        let operator = items
+            .first()
+            .and_then(|item| item.as_str())
+            .ok_or("Expression operator must be a string")?;
+        let args = &items[1..];
+
+        let arg = |i: usize| -> Result<Box<Expression>, Box<dyn Error>> {
+            let json = args
+                .get(i)
+                .ok_or_else(|| format!("Missing argument {} of `{}`", i, operator))?;
+            Ok(Box::new(Expression::from_json(json)?))
+        };
+        let all_args = || -> Result<Vec<Expression>, Box<dyn Error>> {
+            args.iter().map(Expression::from_json).collect()
+        };
+
+        let expression = match operator {
+            "literal" => Expression::Literal(args.first().ok_or("Missing literal value")?.into()),
+            "get" => Expression::Get(
+                args.first()
+                    .and_then(|v| v.as_str())
+                    .ok_or("`get` requires an attribute name")?
+                    .to_string(),
+            ),
+            "has" => Expression::Has(
+                args.first()
+                    .and_then(|v| v.as_str())
+                    .ok_or("`has` requires an attribute name")?
+                    .to_string(),
+            ),
+            "geometry-type" => Expression::GeometryType,
+            "zoom" => Expression::Zoom,
+            "==" => Expression::Compare(CompareOp::Eq, arg(0)?, arg(1)?),
+            "!=" => Expression::Compare(CompareOp::Ne, arg(0)?, arg(1)?),
+            "<" => Expression::Compare(CompareOp::Lt, arg(0)?, arg(1)?),
+            "<=" => Expression::Compare(CompareOp::Le, arg(0)?, arg(1)?),
+            ">" => Expression::Compare(CompareOp::Gt, arg(0)?, arg(1)?),
+            ">=" => Expression::Compare(CompareOp::Ge, arg(0)?, arg(1)?),
+            "all" => Expression::All(all_args()?),
+            "any" => Expression::Any(all_args()?),
+            "!" => Expression::Not(arg(0)?),
+            "+" => Expression::Math(MathOp::Add, all_args()?),
+            "-" => Expression::Math(MathOp::Sub, all_args()?),
+            "*" => Expression::Math(MathOp::Mul, all_args()?),
+            "/" => Expression::Math(MathOp::Div, all_args()?),
+            "%" => Expression::Math(MathOp::Rem, all_args()?),
+            "coalesce" => Expression::Coalesce(all_args()?),
+            "rgb" | "rgba" => Expression::Rgba(all_args()?),
+            "match" => {
+                if args.len() < 4 || args.len() % 2 != 0 {
+                    return Err(
+                        "`match` requires an input, label/output pairs and a fallback".into(),
+                    );
+                }
+
+                let mut cases = Vec::new();
+                for pair in args[1..args.len() - 1].chunks(2) {
+                    let labels = match &pair[0] {
+                        JsonValue::Array(labels) => labels.iter().map(Value::from).collect(),
+                        label => vec![label.into()],
+                    };
+                    cases.push((labels, Expression::from_json(&pair[1])?));
+                }
+
+                Expression::Match {
+                    input: arg(0)?,
+                    cases,
+                    fallback: arg(args.len() - 1)?,
+                }
+            }
+            "case" => {
+                if args.len() < 3 || args.len() % 2 != 1 {
+                    return Err("`case` requires condition/output pairs and a fallback".into());
+                }
+
+                let mut branches = Vec::new();
+                for pair in args[..args.len() - 1].chunks(2) {
+                    branches.push((
+                        Expression::from_json(&pair[0])?,
+                        Expression::from_json(&pair[1])?,
+                    ));
+                }
+
+                Expression::Case {
+                    branches,
+                    fallback: arg(args.len() - 1)?,
+                }
+            }
+            "step" => {
+                if args.len() < 2 || args.len() % 2 != 0 {
+                    return Err(
+                        "`step` requires an input, a base output and stop/output pairs".into(),
+                    );
+                }
+
+                Expression::Step {
+                    input: arg(0)?,
+                    base: arg(1)?,
+                    stops: parse_stops(&args[2..])?,
+                }
+            }
+            "interpolate" => {
+                if args.len() < 4 || args.len() % 2 != 0 {
+                    return Err(
+                        "`interpolate` requires an interpolation, an input and stop/output pairs"
+                            .into(),
+                    );
+                }
+
+                let interpolation = match args[0].get(0).and_then(|v| v.as_str()) {
+                    Some("linear") => Interpolation::Linear,
+                    Some("exponential") => Interpolation::Exponential(
+                        args[0]
+                            .get(1)
+                            .and_then(|v| v.as_f64())
+                            .ok_or("Exponential interpolation requires a base")?,
+                    ),
+                    _ => return Err(format!("Unsupported interpolation: {}", args[0]).into()),
+                };
+
+                Expression::Interpolate {
+                    interpolation,
+                    input: arg(1)?,
+                    stops: parse_stops(&args[2..])?,
+                }
+            }
+            _ => return Err(format!("Unsupported expression operator: {}", operator).into()),
+        };
+
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, context: &EvaluationContext) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Get(name) => context
+                .attrs
+                .and_then(|attrs| attrs.get(name))
+                .map_or(Value::Null, Value::from),
+            Expression::GeometryType => context
+                .geometry_type
+                .map_or(Value::Null, |v| Value::String(v.to_string())),
+            Expression::Zoom => Value::Number(context.zoom),
+            Expression::Has(name) => {
+                Value::Bool(context.attrs.is_some_and(|attrs| attrs.contains_key(name)))
+            }
+            Expression::Compare(op, a, b) => {
+                Value::Bool(compare(*op, &a.evaluate(context), &b.evaluate(context)))
+            }
+            Expression::All(items) => {
+                Value::Bool(items.iter().all(|item| item.evaluate(context).is_truthy()))
+            }
+            Expression::Any(items) => {
+                Value::Bool(items.iter().any(|item| item.evaluate(context).is_truthy()))
+            }
+            Expression::Not(item) => Value::Bool(!item.evaluate(context).is_truthy()),
+            Expression::Math(op, items) => {
+                let numbers: Option<Vec<f64>> = items
+                    .iter()
+                    .map(|item| item.evaluate(context).as_number())
+                    .collect();
+
+                match numbers {
+                    Some(numbers) if !numbers.is_empty() => Value::Number(math(*op, &numbers)),
+                    _ => Value::Null,
+                }
+            }
+            Expression::Coalesce(items) => items
+                .iter()
+                .map(|item| item.evaluate(context))
+                .find(|value| *value != Value::Null)
+                .unwrap_or(Value::Null),
+            Expression::Rgba(items) => {
+                let channels: Option<Vec<f64>> = items
+                    .iter()
+                    .map(|item| item.evaluate(context).as_number())
+                    .collect();
+
+                match channels.as_deref() {
+                    Some([r, g, b]) => Value::Color(Color::from_rgb(*r as u8, *g as u8, *b as u8)),
+                    Some([r, g, b, a]) => {
+                        Value::Color(Color::from_rgba(*r as u8, *g as u8, *b as u8, *a))
+                    }
+                    _ => Value::Null,
+                }
+            }
+            Expression::Match {
+                input,
+                cases,
+                fallback,
+            } => {
+                let input = input.evaluate(context);
+
+                cases
+                    .iter()
+                    .find(|(labels, _)| {
+                        labels
+                            .iter()
+                            .any(|label| compare(CompareOp::Eq, label, &input))
+                    })
+                    .map_or_else(
+                        || fallback.evaluate(context),
+                        |(_, output)| output.evaluate(context),
+                    )
+            }
+            Expression::Case { branches, fallback } => branches
+                .iter()
+                .find(|(condition, _)| condition.evaluate(context).is_truthy())
+                .map_or_else(
+                    || fallback.evaluate(context),
+                    |(_, output)| output.evaluate(context),
+                ),
+            Expression::Step { input, base, stops } => {
+                let input = match input.evaluate(context).as_number() {
+                    Some(input) => input,
+                    None => return base.evaluate(context),
+                };
+
+                stops
+                    .iter()
+                    .rev()
+                    .find(|(stop, _)| input >= *stop)
+                    .map_or_else(
+                        || base.evaluate(context),
+                        |(_, output)| output.evaluate(context),
+                    )
+            }
+            Expression::Interpolate {
+                interpolation,
+                input,
+                stops,
+            } => {
+                let input = match input.evaluate(context).as_number() {
+                    Some(input) => input,
+                    None => return Value::Null,
+                };
+
+                interpolate(*interpolation, input, stops, context)
+            }
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expression::from_json(&serde_json::from_str(s)?)
+    }
+}
+
+impl From<Value> for Expression {
+    fn from(value: Value) -> Self {
+        Expression::Literal(value)
+    }
+}
+
+fn parse_stops(args: &[JsonValue]) -> Result<Vec<(f64, Expression)>, Box<dyn Error>> {
+    let mut stops = Vec::new();
+    for pair in args.chunks(2) {
+        let stop = pair[0].as_f64().ok_or("Stop input must be a number")?;
+        if stops.last().is_some_and(|(last, _)| stop <= *last) {
+            return Err("Stop inputs must be in strictly ascending order".into());
+        }
+
+        stops.push((stop, Expression::from_json(&pair[1])?));
+    }
+
+    Ok(stops)
+}
+
+fn compare(op: CompareOp, a: &Value, b: &Value) -> bool {
+    let ordering = match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+
+    match op {
+        CompareOp::Eq => a == b,
+        CompareOp::Ne => a != b,
+        CompareOp::Lt => ordering.is_some_and(|o| o.is_lt()),
+        CompareOp::Le => ordering.is_some_and(|o| o.is_le()),
+        CompareOp::Gt => ordering.is_some_and(|o| o.is_gt()),
+        CompareOp::Ge => ordering.is_some_and(|o| o.is_ge()),
+    }
+}
+
+fn math(op: MathOp, numbers: &[f64]) -> f64 {
+    match (op, numbers) {
+        (MathOp::Sub, [v]) => -v,
+        (MathOp::Add, _) => numbers.iter().sum(),
+        (MathOp::Mul, _) => numbers.iter().product(),
+        (MathOp::Sub, [first, rest @ ..]) => rest.iter().fold(*first, |acc, v| acc - v),
+        (MathOp::Div, [first, rest @ ..]) => rest.iter().fold(*first, |acc, v| acc / v),
+        (MathOp::Rem, [first, rest @ ..]) => rest.iter().fold(*first, |acc, v| acc % v),
+        _ => f64::NAN,
+    }
+}
+
+fn interpolate(
+    interpolation: Interpolation,
+    input: f64,
+    stops: &[(f64, Expression)],
+    context: &EvaluationContext,
+) -> Value {
+    let upper = stops.iter().position(|(stop, _)| *stop > input);
+
+    let (lower, upper) = match upper {
+        None => {
+            return stops
+                .last()
+                .map_or(Value::Null, |(_, v)| v.evaluate(context))
+        }
+        Some(0) => return stops[0].1.evaluate(context),
+        Some(i) => (&stops[i - 1], &stops[i]),
+    };
+
+    let progress = input - lower.0;
+    let range = upper.0 - lower.0;
+    let t = match interpolation {
+        Interpolation::Exponential(base) if base != 1.0 => {
+            (base.powf(progress) - 1.0) / (base.powf(range) - 1.0)
+        }
+        _ => progress / range,
+    };
+
+    match (lower.1.evaluate(context), upper.1.evaluate(context)) {
+        (Value::Number(a), Value::Number(b)) => Value::Number(a + (b - a) * t),
+        (a, b) => match (a.as_color(), b.as_color()) {
+            // Linear space like color ramps, mixes of saturated colors don't get too dark
+            (Some(a), Some(b)) => Value::Color(a.lerp_linear(&b, t)),
+            _ => Value::Null,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn attrs(json: JsonValue) -> Option<HashMap<String, JsonValue>> {
+        Some(
+            json.as_object()
+                .unwrap()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_get_and_compare() {
+        let attrs = attrs(json!({"class": "motorway", "admin_level": 2}));
+        let context = EvaluationContext::new(&attrs);
+
+        let expression: Expression = r#"["==", ["get", "class"], "motorway"]"#.parse().unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Bool(true));
+
+        let expression: Expression = r#"["<=", ["get", "admin_level"], 2]"#.parse().unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Bool(true));
+
+        let expression: Expression = r#"["all", ["has", "class"], ["!", ["has", "name"]]]"#
+            .parse()
+            .unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Bool(true));
+
+        let expression: Expression = r#"["*", ["get", "admin_level"], 1.5, 2]"#.parse().unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Number(6.0));
+
+        let expression: Expression = r#"["==", ["geometry-type"], "LineString"]"#.parse().unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Bool(false));
+        assert_eq!(
+            expression.evaluate(&context.with_geometry_type("LineString")),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_match_case_step() {
+        let attrs = attrs(json!({"highway": "primary", "lanes": 3}));
+        let context = EvaluationContext::new(&attrs);
+
+        let expression: Expression =
+            r#"["match", ["get", "highway"], ["motorway", "trunk"], 6, "primary", 4, 1]"#
+                .parse()
+                .unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Number(4.0));
+
+        let expression: Expression =
+            r#"["case", [">", ["get", "lanes"], 4], "wide", [">", ["get", "lanes"], 2], "medium", "narrow"]"#
+                .parse()
+                .unwrap();
+        assert_eq!(
+            expression.evaluate(&context),
+            Value::String("medium".to_string())
+        );
+
+        let expression: Expression =
+            r#"["step", ["get", "lanes"], 1, 2, 2, 4, 4]"#.parse().unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let attrs = attrs(json!({"density": 50}));
+        let context = EvaluationContext::new(&attrs);
+
+        let expression: Expression =
+            r#"["interpolate", ["linear"], ["get", "density"], 0, 0, 100, 10]"#
+                .parse()
+                .unwrap();
+        assert_eq!(expression.evaluate(&context), Value::Number(5.0));
+
+        let expression: Expression =
+            r##"["interpolate", ["linear"], ["get", "density"], 0, "#000000", 100, ["rgb", 200, 100, 0]]"##
+                .parse()
+                .unwrap();
+        assert_eq!(
+            expression.evaluate(&context),
+            Value::Color(Color::from_rgb(146, 71, 0))
+        );
+
+        let expression: Expression =
+            r#"["interpolate", ["linear"], ["get", "density"], 0, "navy", 100, "hsl(0, 100%, 50%)"]"#
+                .parse()
+                .unwrap();
+        assert_eq!(
+            expression.evaluate(&context),
+            Value::Color(Color::from_rgb(188, 0, 92))
+        );
+
+        let expression = Expression::color_ramp(
+            Expression::Get("density".to_string()),
+            0.0,
+            90.0,
+            &ColorRamp::viridis(),
+        );
+        assert_eq!(
+            expression.evaluate(&context).as_color(),
+            Some(ColorRamp::viridis().stops()[5].1.clone())
+        );
+
+        assert!(
+            r#"["interpolate", ["linear"], ["get", "density"], 10, 0, 5, 1]"#
+                .parse::<Expression>()
+                .is_err()
+        );
+        assert!(r#"["unknown", 1]"#.parse::<Expression>().is_err());
+    }
+
+    #[test]
+    fn test_color_ramp_sample() {
+        let ramp = ColorRamp::viridis();
+        let expression =
+            Expression::color_ramp(Expression::Get("density".to_string()), 0.0, 200.0, &ramp);
+
+        for density in [0.0, 10.0, 25.0, 50.0, 137.0, 199.0, 200.0, 250.0] {
+            let attrs = attrs(json!({ "density": density }));
+            let context = EvaluationContext::new(&attrs);
+            assert_eq!(
+                expression.evaluate(&context).as_color(),
+                Some(ramp.sample(density / 200.0)),
+                "density {}",
+                density
+            );
+        }
+    }
+
+    #[test]
+    fn test_zoom() {
+        let context = EvaluationContext::default().with_zoom(12.0);
+
+        let expression: Expression =
+            r#"["interpolate", ["exponential", 2], ["zoom"], 10, 1, 14, 16]"#
+                .parse()
+                .unwrap();
+        assert!(expression.is_zoom_dependent());
+        // (2^2 - 1) / (2^4 - 1) = 0.2
+        assert_eq!(expression.evaluate(&context), Value::Number(4.0));
+
+        let expression = Expression::zoom_interpolate(
+            Interpolation::Linear,
+            vec![
+                (10.0, Color::from_rgb(0, 0, 0)),
+                (14.0, Color::from_rgb(200, 0, 0)),
+            ],
+        );
+        assert_eq!(
+            expression.evaluate(&context),
+            Value::Color(Color::from_rgb(146, 0, 0))
+        );
+        assert_eq!(
+            expression.evaluate(&EvaluationContext::default().with_zoom(20.0)),
+            Value::Color(Color::from_rgb(200, 0, 0))
+        );
+
+        let expression = Expression::zoom_step(1.0, vec![(5.0, 2.0), (12.0, 4.0)]);
+        assert_eq!(expression.evaluate(&context), Value::Number(4.0));
+        assert_eq!(
+            expression.evaluate(&EvaluationContext::default().with_zoom(3.0)),
+            Value::Number(1.0)
+        );
+
+        let expression: Expression = r#"["get", "width"]"#.parse().unwrap();
+        assert!(!expression.is_zoom_dependent());
+    }
+
+    #[test]
+    fn test_max_number() {
+        let expression: Expression =
+            r#"["match", ["get", "highway"], ["motorway", "trunk"], 6, "primary", 4, 1]"#
+                .parse()
+                .unwrap();
+        assert_eq!(expression.max_number(10.0), Some(6.0));
+
+        let expression = Expression::zoom_step(1.0, vec![(5.0, 2.0), (12.0, 4.0)]);
+        assert_eq!(expression.max_number(3.0), Some(4.0));
+
+        let expression: Expression = r#"["coalesce", ["zoom"], "none"]"#.parse().unwrap();
+        assert_eq!(expression.max_number(3.0), Some(3.0));
+
+        let expression: Expression = r#"["case", ["has", "width"], ["get", "width"], 2]"#
+            .parse()
+            .unwrap();
+        assert_eq!(expression.max_number(10.0), None);
+    }
+}
@@ -0,0 +1,1022 @@
+use std::{collections::HashMap, error::Error, str::FromStr};
+
+use crate::{
+    feature::style::{
+        expression::{CompareOp, Expression, Interpolation, MathOp, Value},
+        ShapeStyles,
+    },
+    layer::vector_tiled_layer::{VectorTiledLayer, VectorTiledLayerOptions},
+    map::Map,
+    utils::color::Color,
+    JsonValue,
+};
+
+/// Layers imported from a MapLibre / Mapbox GL style document.
+///
+/// Zoom levels in the document, e.g. `minzoom` and zoom stops, are read as zoom levels of the
+/// map's tiling.
+pub struct GlStyle {
+    pub background_color: Option<Color>,
+    /// Layers in drawing order, consecutive style layers of the same source are merged.
+    pub layers: Vec<GlStyleLayer>,
+    /// Unsupported sources, layers and properties which were skipped.
+    pub warnings: Vec<String>,
+}
+
+pub struct GlStyleLayer {
+    pub name: String,
+    pub source: String,
+    pub url_template: String,
+    pub zoom_max: Option<usize>,
+    pub layers_shape_styles: Vec<(String, ShapeStyles)>,
+}
+
+impl GlStyleLayer {
+    pub fn to_layer(&self) -> VectorTiledLayer {
+        let mut options =
+            VectorTiledLayerOptions::default().with_layers_shape_styles(&self.layers_shape_styles);
+        if let Some(zoom_max) = self.zoom_max {
+            options = options.with_zoom_max(zoom_max);
+        }
+
+        VectorTiledLayer::new(&self.url_template, options)
+    }
+}
+
+struct VectorSource {
+    url_template: String,
+    zoom_max: Option<usize>,
+}
+
+enum Property {
+    Static(Value),
+    Expression(Expression),
+}
+
+impl GlStyle {
+    pub fn add_to_map(&self, map: &mut Map) -> Result<(), Box<dyn Error>> {
+        if let Some(background_color) = &self.background_color {
+            map.set_background_color(background_color.clone());
+        }
+
+        for layer in &self.layers {
+            map.add_layer(&layer.name, Box::new(layer.to_layer()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let sources = json
+            .get("sources")
+            .and_then(|v| v.as_object())
+            .ok_or("Style requires sources")?;
+        let style_layers = json
+            .get("layers")
+            .and_then(|v| v.as_array())
+            .ok_or("Style requires layers")?;
+
+        let mut warnings = Vec::new();
+
+        let mut vector_sources: HashMap<&str, VectorSource> = HashMap::new();
+        for (source_id, source) in sources {
+            match parse_source(source) {
+                Ok(vector_source) => {
+                    vector_sources.insert(source_id, vector_source);
+                }
+                Err(err) => warnings.push(format!("Source `{}` skipped: {}", source_id, err)),
+            }
+        }
+
+        let mut background_color = None;
+        let mut layers: Vec<GlStyleLayer> = Vec::new();
+
+        for style_layer in style_layers {
+            let id = style_layer
+                .get("id")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let r#type = style_layer
+                .get("type")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+
+            let visibility = style_layer
+                .get("layout")
+                .and_then(|v| v.get("visibility"))
+                .and_then(|v| v.as_str());
+            if visibility == Some("none") {
+                continue;
+            }
+
+            let mut layer_warnings = Vec::new();
+
+            match r#type {
+                "background" => {
+                    let paint = style_layer.get("paint");
+                    let color = paint.and_then(|v| v.get("background-color"));
+                    let opacity = paint.and_then(|v| v.get("background-opacity"));
+
+                    match color_property(color, opacity, &mut layer_warnings) {
+                        Ok(Property::Static(color)) => background_color = color.as_color(),
+                        Ok(Property::Expression(_)) => layer_warnings
+                            .push("expressions are not supported for backgrounds".to_string()),
+                        Err(err) => layer_warnings.push(err.to_string()),
+                    }
+                }
+                "fill" | "line" | "circle" => {
+                    if let Some((source, source_layer, shape_styles)) =
+                        parse_style_layer(style_layer, &vector_sources, &mut layer_warnings)
+                    {
+                        match layers.last_mut() {
+                            Some(layer) if layer.source == source => {
+                                layer.layers_shape_styles.push((source_layer, shape_styles));
+                            }
+                            _ => {
+                                let vector_source = &vector_sources[source.as_str()];
+                                let count =
+                                    layers.iter().filter(|layer| layer.source == source).count();
+
+                                layers.push(GlStyleLayer {
+                                    name: if count == 0 {
+                                        source.clone()
+                                    } else {
+                                        format!("{}-{}", source, count)
+                                    },
+                                    source,
+                                    url_template: vector_source.url_template.clone(),
+                                    zoom_max: vector_source.zoom_max,
+                                    layers_shape_styles: vec![(source_layer, shape_styles)],
+                                });
+                            }
+                        }
+                    }
+                }
+                _ => layer_warnings.push(format!("layer type `{}` is not supported", r#type)),
+            }
+
+            warnings.extend(
+                layer_warnings
+                    .into_iter()
+                    .map(|warning| format!("Layer `{}`: {}", id, warning)),
+            );
+        }
+
+        for warning in &warnings {
+            log::warn!("{}", warning);
+        }
+
+        Ok(Self {
+            background_color,
+            layers,
+            warnings,
+        })
+    }
+}
+
+impl FromStr for GlStyle {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GlStyle::from_json(&serde_json::from_str(s)?)
+    }
+}
+
+fn parse_source(source: &JsonValue) -> Result<VectorSource, Box<dyn Error>> {
+    let r#type = source.get("type").and_then(|v| v.as_str());
+    if r#type != Some("vector") {
+        return Err(format!(
+            "source type {:?} is not supported",
+            r#type.unwrap_or_default()
+        )
+        .into());
+    }
+
+    let url_template = source
+        .get("tiles")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.as_str())
+        .ok_or("only sources with `tiles` are supported")?;
+
+    Ok(VectorSource {
+        url_template: url_template.to_string(),
+        zoom_max: source
+            .get("maxzoom")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize),
+    })
+}
+
+/// Convert a fill, line or circle style layer, returning its source, source layer and styles.
+fn parse_style_layer(
+    style_layer: &JsonValue,
+    vector_sources: &HashMap<&str, VectorSource>,
+    warnings: &mut Vec<String>,
+) -> Option<(String, String, ShapeStyles)> {
+    let r#type = style_layer.get("type").and_then(|v| v.as_str())?;
+
+    let source = style_layer.get("source").and_then(|v| v.as_str());
+    let source = match source {
+        Some(source) if vector_sources.contains_key(source) => source,
+        _ => {
+            warnings.push(format!(
+                "source {:?} is not available",
+                source.unwrap_or_default()
+            ));
+            return None;
+        }
+    };
+    let source_layer = match style_layer.get("source-layer").and_then(|v| v.as_str()) {
+        Some(source_layer) => source_layer,
+        None => {
+            warnings.push("`source-layer` is required".to_string());
+            return None;
+        }
+    };
+
+    let mut conditions = Vec::new();
+    if let Some(zoom_min) = style_layer.get("minzoom").and_then(|v| v.as_f64()) {
+        conditions.push(Expression::Compare(
+            CompareOp::Ge,
+            Box::new(Expression::Zoom),
+            Box::new(Expression::Literal(Value::Number(zoom_min))),
+        ));
+    }
+    if let Some(zoom_max) = style_layer.get("maxzoom").and_then(|v| v.as_f64()) {
+        conditions.push(Expression::Compare(
+            CompareOp::Lt,
+            Box::new(Expression::Zoom),
+            Box::new(Expression::Literal(Value::Number(zoom_max))),
+        ));
+    }
+    let filter = match style_layer.get("filter") {
+        Some(filter) => match parse_filter(filter, warnings) {
+            Ok(filter) => Some(filter),
+            Err(err) => {
+                warnings.push(format!("filter is not supported: {}", err));
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    let empty = serde_json::Map::new();
+    let paint = style_layer
+        .get("paint")
+        .and_then(|v| v.as_object())
+        .unwrap_or(&empty);
+    let layout = style_layer
+        .get("layout")
+        .and_then(|v| v.as_object())
+        .unwrap_or(&empty);
+
+    let supported_paint: &[&str] = match r#type {
+        "fill" => &[
+            "fill-color",
+            "fill-opacity",
+            "fill-outline-color",
+            "fill-pattern",
+        ],
+        "line" => &[
+            "line-color",
+            "line-opacity",
+            "line-width",
+            "line-dasharray",
+            "line-pattern",
+        ],
+        "circle" => &["circle-color", "circle-opacity", "circle-radius"],
+        _ => &[],
+    };
+    for key in paint.keys() {
+        if !supported_paint.contains(&key.as_str()) {
+            warnings.push(format!("paint property `{}` is not supported", key));
+        }
+    }
+    let supported_layout: &[&str] = match r#type {
+        "line" => &["line-cap", "line-join", "line-miter-limit"],
+        _ => &[],
+    };
+    for key in layout.keys() {
+        if key != "visibility" && !supported_layout.contains(&key.as_str()) {
+            warnings.push(format!("layout property `{}` is not supported", key));
+        }
+    }
+
+    let mut shape_styles = ShapeStyles {
+        fill_enabled: false,
+        fill_color: Color::from_rgb(0, 0, 0),
+        stroke_enabled: false,
+        stroke_color: Color::from_rgb(0, 0, 0),
+        stroke_width: 1.0,
+        symbol_size: 10.0,
+        ..Default::default()
+    };
+
+    match r#type {
+        "fill" => {
+            shape_styles.fill_enabled = true;
+
+            let color =
+                color_property(paint.get("fill-color"), paint.get("fill-opacity"), warnings)
+                    .and_then(|property| {
+                        apply_color(
+                            property,
+                            &mut shape_styles.fill_color,
+                            &mut shape_styles.expressions.fill_color,
+                        )
+                    });
+            warn_on_error(warnings, "fill-color", color);
+
+            if let Some(pattern) = paint.get("fill-pattern") {
+                let pattern = static_str(pattern).map(|pattern| {
+                    shape_styles.fill_pattern = Some(pattern.to_string());
+                });
+                warn_on_error(warnings, "fill-pattern", pattern);
+            }
+
+            if let Some(outline_color) = paint.get("fill-outline-color") {
+                shape_styles.stroke_enabled = true;
+
+                let color =
+                    color_property(Some(outline_color), paint.get("fill-opacity"), warnings)
+                        .and_then(|property| {
+                            apply_color(
+                                property,
+                                &mut shape_styles.stroke_color,
+                                &mut shape_styles.expressions.stroke_color,
+                            )
+                        });
+                warn_on_error(warnings, "fill-outline-color", color);
+            }
+        }
+        "line" => {
+            shape_styles.stroke_enabled = true;
+
+            let color =
+                color_property(paint.get("line-color"), paint.get("line-opacity"), warnings)
+                    .and_then(|property| {
+                        apply_color(
+                            property,
+                            &mut shape_styles.stroke_color,
+                            &mut shape_styles.expressions.stroke_color,
+                        )
+                    });
+            warn_on_error(warnings, "line-color", color);
+
+            if let Some(width) = paint.get("line-width") {
+                let width = parse_property(width).and_then(|property| {
+                    apply_number(
+                        property,
+                        &mut shape_styles.stroke_width,
+                        &mut shape_styles.expressions.stroke_width,
+                    )
+                });
+                warn_on_error(warnings, "line-width", width);
+            }
+
+            if let Some(dash_array) = paint.get("line-dasharray") {
+                // Dash lengths are in line widths
+                let values = dash_array.as_array().and_then(|values| {
+                    values
+                        .iter()
+                        .map(|v| v.as_f64().map(|v| v as f32 * shape_styles.stroke_width))
+                        .collect::<Option<Vec<f32>>>()
+                });
+                let dash_array = match values {
+                    Some(values) => {
+                        shape_styles.dash_array = Some(values);
+                        Ok(())
+                    }
+                    None => Err(format!("expected an array of numbers, got {}", dash_array).into()),
+                };
+                warn_on_error(warnings, "line-dasharray", dash_array);
+
+                if shape_styles.expressions.stroke_width.is_some() {
+                    warnings.push(
+                        "`line-dasharray` is not scaled by data driven or zoom dependent line widths"
+                            .to_string(),
+                    );
+                }
+            }
+
+            if let Some(pattern) = paint.get("line-pattern") {
+                let pattern = static_str(pattern).map(|pattern| {
+                    shape_styles.stroke_pattern = Some(pattern.to_string());
+                });
+                warn_on_error(warnings, "line-pattern", pattern);
+            }
+
+            if let Some(cap) = layout.get("line-cap") {
+                let cap = static_str(cap).and_then(|cap| {
+                    shape_styles.line_cap = cap.parse()?;
+                    Ok(())
+                });
+                warn_on_error(warnings, "line-cap", cap);
+            }
+
+            if let Some(join) = layout.get("line-join") {
+                let join = static_str(join).and_then(|join| {
+                    shape_styles.line_join = join.parse()?;
+                    Ok(())
+                });
+                warn_on_error(warnings, "line-join", join);
+            }
+
+            if let Some(limit) = layout.get("line-miter-limit") {
+                let limit = parse_property(limit).and_then(|property| {
+                    apply_number(
+                        property,
+                        &mut shape_styles.miter_limit,
+                        &mut shape_styles.expressions.miter_limit,
+                    )
+                });
+                warn_on_error(warnings, "line-miter-limit", limit);
+            }
+        }
+        "circle" => {
+            shape_styles.fill_enabled = true;
+
+            let color = color_property(
+                paint.get("circle-color"),
+                paint.get("circle-opacity"),
+                warnings,
+            )
+            .and_then(|property| {
+                apply_color(
+                    property,
+                    &mut shape_styles.fill_color,
+                    &mut shape_styles.expressions.fill_color,
+                )
+            });
+            warn_on_error(warnings, "circle-color", color);
+
+            if let Some(radius) = paint.get("circle-radius") {
+                // Symbol size is the diameter
+                let size = parse_property(radius)
+                    .map(|property| match property {
+                        Property::Static(Value::Number(v)) => {
+                            Property::Static(Value::Number(v * 2.0))
+                        }
+                        Property::Static(v) => Property::Static(v),
+                        Property::Expression(e) => Property::Expression(Expression::Math(
+                            MathOp::Mul,
+                            vec![e, Expression::Literal(Value::Number(2.0))],
+                        )),
+                    })
+                    .and_then(|property| {
+                        apply_number(
+                            property,
+                            &mut shape_styles.symbol_size,
+                            &mut shape_styles.expressions.symbol_size,
+                        )
+                    });
+                warn_on_error(warnings, "circle-radius", size);
+            }
+        }
+        _ => {}
+    }
+
+    shape_styles.expressions.visible = match conditions.len() {
+        0 => None,
+        1 => conditions.pop(),
+        _ => Some(Expression::All(conditions)),
+    };
+    shape_styles.expressions.filter = match (geometry_type_filter(r#type), filter) {
+        (Some(geometry_type_filter), Some(filter)) => {
+            Some(Expression::All(vec![geometry_type_filter, filter]))
+        }
+        (geometry_type_filter, filter) => geometry_type_filter.or(filter),
+    };
+
+    Some((source.to_string(), source_layer.to_string(), shape_styles))
+}
+
+/// Filter of the geometry types a layer type draws, as MapLibre skips other geometries.
+fn geometry_type_filter(r#type: &str) -> Option<Expression> {
+    let geometry_types: &[&str] = match r#type {
+        "fill" => &["Polygon"],
+        "line" => &["LineString", "Polygon"],
+        "circle" => &["Point"],
+        _ => return None,
+    };
+
+    Some(Expression::Match {
+        input: Box::new(Expression::GeometryType),
+        cases: vec![(
+            geometry_types
+                .iter()
+                .map(|geometry_type| Value::String(geometry_type.to_string()))
+                .collect(),
+            Expression::Literal(Value::Bool(true)),
+        )],
+        fallback: Box::new(Expression::Literal(Value::Bool(false))),
+    })
+}
+
+fn warn_on_error(warnings: &mut Vec<String>, key: &str, result: Result<(), Box<dyn Error>>) {
+    if let Err(err) = result {
+        warnings.push(format!("`{}` is not supported: {}", key, err));
+    }
+}
+
+fn static_str(json: &JsonValue) -> Result<&str, Box<dyn Error>> {
+    json.as_str()
+        .ok_or_else(|| format!("expected a string, got {}", json).into())
+}
+
+fn parse_property(json: &JsonValue) -> Result<Property, Box<dyn Error>> {
+    match json {
+        JsonValue::Array(_) => Ok(Property::Expression(Expression::from_json(json)?)),
+        JsonValue::Object(_) => Ok(Property::Expression(parse_function(json)?)),
+        _ => Ok(Property::Static(json.into())),
+    }
+}
+
+/// Parse a color property with its opacity, colors default to black.
+fn color_property(
+    color: Option<&JsonValue>,
+    opacity: Option<&JsonValue>,
+    warnings: &mut Vec<String>,
+) -> Result<Property, Box<dyn Error>> {
+    let color = match color {
+        Some(color) => parse_property(color)?,
+        None => Property::Static(Value::Color(Color::from_rgb(0, 0, 0))),
+    };
+    let opacity = match opacity {
+        Some(opacity) => parse_property(opacity)?,
+        None => return Ok(color),
+    };
+
+    match (color, opacity) {
+        (Property::Static(color), opacity) => {
+            let mut color = color
+                .as_color()
+                .ok_or_else(|| format!("unsupported color {:?}", color))?;
+
+            match opacity {
+                Property::Static(opacity) => {
+                    color.a *= opacity.as_number().ok_or("opacity must be a number")?;
+                    Ok(Property::Static(Value::Color(color)))
+                }
+                Property::Expression(opacity) => {
+                    let channel = |v: f64| Expression::Literal(Value::Number(v));
+
+                    Ok(Property::Expression(Expression::Rgba(vec![
+                        channel(color.r as f64),
+                        channel(color.g as f64),
+                        channel(color.b as f64),
+                        Expression::Math(MathOp::Mul, vec![channel(color.a), opacity]),
+                    ])))
+                }
+            }
+        }
+        (color, _) => {
+            warnings.push("opacity is ignored for colors given as expressions".to_string());
+            Ok(color)
+        }
+    }
+}
+
+fn apply_color(
+    property: Property,
+    color: &mut Color,
+    expression: &mut Option<Expression>,
+) -> Result<(), Box<dyn Error>> {
+    match property {
+        Property::Static(value) => {
+            *color = value
+                .as_color()
+                .ok_or_else(|| format!("unsupported color {:?}", value))?;
+        }
+        Property::Expression(e) => *expression = Some(e),
+    }
+
+    Ok(())
+}
+
+fn apply_number(
+    property: Property,
+    number: &mut f32,
+    expression: &mut Option<Expression>,
+) -> Result<(), Box<dyn Error>> {
+    match property {
+        Property::Static(value) => {
+            *number = value
+                .as_number()
+                .ok_or_else(|| format!("expected a number, got {:?}", value))?
+                as f32;
+        }
+        Property::Expression(e) => *expression = Some(e),
+    }
+
+    Ok(())
+}
+
+/// Convert a legacy function, e.g. `{"base": 1.4, "stops": [[5, 1], [12, 4]]}`, to an expression.
+fn parse_function(json: &JsonValue) -> Result<Expression, Box<dyn Error>> {
+    let input = match json.get("property").and_then(|v| v.as_str()) {
+        Some(property) => Expression::Get(property.to_string()),
+        None => Expression::Zoom,
+    };
+    let r#type = json
+        .get("type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("exponential");
+
+    if r#type == "identity" {
+        return Ok(input);
+    }
+
+    let stops = json
+        .get("stops")
+        .and_then(|v| v.as_array())
+        .ok_or("function requires stops")?
+        .iter()
+        .map(|stop| match (stop.get(0), stop.get(1)) {
+            (Some(JsonValue::Object(_)), _) => {
+                Err("zoom and property functions are not supported".into())
+            }
+            (Some(input), Some(output)) => Ok((input, Expression::Literal(output.into()))),
+            _ => Err("function stops must be pairs".into()),
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let numeric_stops = || -> Result<Vec<(f64, Expression)>, Box<dyn Error>> {
+        stops
+            .iter()
+            .map(|(input, output)| {
+                input
+                    .as_f64()
+                    .map(|input| (input, output.clone()))
+                    .ok_or_else(|| "stop inputs must be numbers".into())
+            })
+            .collect()
+    };
+
+    match r#type {
+        "exponential" => {
+            let base = json.get("base").and_then(|v| v.as_f64()).unwrap_or(1.0);
+
+            Ok(Expression::Interpolate {
+                interpolation: if base == 1.0 {
+                    Interpolation::Linear
+                } else {
+                    Interpolation::Exponential(base)
+                },
+                input: Box::new(input),
+                stops: numeric_stops()?,
+            })
+        }
+        "interval" => {
+            let mut stops = numeric_stops()?;
+            if stops.is_empty() {
+                return Err("function requires stops".into());
+            }
+            let (_, base) = stops.remove(0);
+
+            Ok(Expression::Step {
+                input: Box::new(input),
+                base: Box::new(base),
+                stops,
+            })
+        }
+        "categorical" => Ok(Expression::Match {
+            input: Box::new(input),
+            cases: stops
+                .into_iter()
+                .map(|(input, output)| (vec![input.into()], output))
+                .collect(),
+            fallback: Box::new(Expression::Literal(
+                json.get("default").map_or(Value::Null, Value::from),
+            )),
+        }),
+        _ => Err(format!("function type `{}` is not supported", r#type).into()),
+    }
+}
+
+/// Parse a filter in either the expression or the legacy syntax.
+fn parse_filter(
+    json: &JsonValue,
+    warnings: &mut Vec<String>,
+) -> Result<Expression, Box<dyn Error>> {
+    if is_legacy_filter(json) {
+        parse_legacy_filter(json, warnings)
+    } else {
+        Expression::from_json(json)
+    }
+}
+
+/// Detect legacy filters the way MapLibre does, e.g. `["==", "class", "motorway"]`.
+fn is_legacy_filter(json: &JsonValue) -> bool {
+    let items = match json.as_array() {
+        Some(items) => items,
+        None => return false,
+    };
+
+    match items.first().and_then(|v| v.as_str()) {
+        Some("!in" | "!has" | "none") => true,
+        Some("has") => matches!(items.get(1).and_then(|v| v.as_str()), Some("$id" | "$type")),
+        Some("in") => items.len() >= 3 && items[1].is_string() && !items[2].is_array(),
+        Some("==" | "!=" | "<" | "<=" | ">" | ">=") => {
+            items.len() == 3 && !items[1].is_array() && !items[2].is_array()
+        }
+        Some("all" | "any") => items[1..].iter().any(is_legacy_filter),
+        _ => false,
+    }
+}
+
+fn parse_legacy_filter(
+    json: &JsonValue,
+    warnings: &mut Vec<String>,
+) -> Result<Expression, Box<dyn Error>> {
+    let items = json.as_array().ok_or("filter must be an array")?;
+    let operator = items
+        .first()
+        .and_then(|v| v.as_str())
+        .ok_or("filter operator must be a string")?;
+    let key = items.get(1).and_then(|v| v.as_str());
+
+    let attribute = match key {
+        Some("$type") => Expression::GeometryType,
+        Some("$id") => {
+            warnings.push("`$id` filters are not supported and ignored".to_string());
+            return Ok(Expression::Literal(Value::Bool(true)));
+        }
+        Some(key) => Expression::Get(key.to_string()),
+        None => Expression::Literal(Value::Null),
+    };
+    let values = || items.iter().skip(2).map(|v| Expression::Literal(v.into()));
+    let compare = |op: CompareOp| -> Result<Expression, Box<dyn Error>> {
+        let value = values()
+            .next()
+            .ok_or_else(|| format!("`{}` filter requires a value", operator))?;
+        Ok(Expression::Compare(
+            op,
+            Box::new(attribute.clone()),
+            Box::new(value),
+        ))
+    };
+    let any_of = || {
+        Expression::Any(
+            values()
+                .map(|value| {
+                    Expression::Compare(CompareOp::Eq, Box::new(attribute.clone()), Box::new(value))
+                })
+                .collect(),
+        )
+    };
+    let filters = |warnings: &mut Vec<String>| -> Result<Vec<Expression>, Box<dyn Error>> {
+        items[1..]
+            .iter()
+            .map(|item| parse_filter(item, warnings))
+            .collect()
+    };
+
+    let expression = match operator {
+        "==" => compare(CompareOp::Eq)?,
+        "!=" => compare(CompareOp::Ne)?,
+        "<" => compare(CompareOp::Lt)?,
+        "<=" => compare(CompareOp::Le)?,
+        ">" => compare(CompareOp::Gt)?,
+        ">=" => compare(CompareOp::Ge)?,
+        "in" => any_of(),
+        "!in" => Expression::Not(Box::new(any_of())),
+        "has" | "!has" => {
+            let has = match key.ok_or("`has` filter requires a key")? {
+                "$type" => Expression::Literal(Value::Bool(true)),
+                key => Expression::Has(key.to_string()),
+            };
+            if operator == "has" {
+                has
+            } else {
+                Expression::Not(Box::new(has))
+            }
+        }
+        "all" => Expression::All(filters(warnings)?),
+        "any" => Expression::Any(filters(warnings)?),
+        "none" => Expression::Not(Box::new(Expression::Any(filters(warnings)?))),
+        _ => return Err(format!("Unsupported filter: {}", operator).into()),
+    };
+
+    Ok(expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Coord, Rect};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        feature::style::{expression::EvaluationContext, LineCap},
+        render::tessellation::vector_tile::VectorTileTessellation,
+        vector_tile::VectorTile,
+    };
+
+    #[test]
+    fn test_from_json() {
+        let style: GlStyle = r##"{
+            "version": 8,
+            "sources": {
+                "maplibre": {
+                    "type": "vector",
+                    "tiles": ["https://demotiles.maplibre.org/tiles/{z}/{x}/{y}.pbf"],
+                    "maxzoom": 6
+                },
+                "satellite": {
+                    "type": "raster",
+                    "tiles": ["https://example.com/{z}/{x}/{y}.png"]
+                }
+            },
+            "layers": [
+                {
+                    "id": "background",
+                    "type": "background",
+                    "paint": { "background-color": "#d8f2ff" }
+                },
+                {
+                    "id": "countries-fill",
+                    "type": "fill",
+                    "source": "maplibre",
+                    "source-layer": "countries",
+                    "paint": {
+                        "fill-color": "#d6c7ff",
+                        "fill-opacity": 0.5,
+                        "fill-outline-color": "#ffffff",
+                        "fill-pattern": "hatching"
+                    }
+                },
+                {
+                    "id": "hidden",
+                    "type": "line",
+                    "source": "maplibre",
+                    "source-layer": "countries",
+                    "layout": { "visibility": "none" }
+                },
+                {
+                    "id": "boundaries",
+                    "type": "line",
+                    "source": "maplibre",
+                    "source-layer": "geolines",
+                    "minzoom": 2,
+                    "filter": ["==", "name", "Equator"],
+                    "layout": { "line-cap": "round" },
+                    "paint": {
+                        "line-color": "#1077b0",
+                        "line-dasharray": [2, 1],
+                        "line-width": { "base": 1.5, "stops": [[2, 1], [6, 4]] }
+                    }
+                },
+                {
+                    "id": "satellite",
+                    "type": "raster",
+                    "source": "satellite"
+                },
+                {
+                    "id": "cities",
+                    "type": "circle",
+                    "source": "maplibre",
+                    "source-layer": "centroids",
+                    "paint": { "circle-radius": 3, "circle-color": "hsl(0, 100%, 50%)" }
+                }
+            ]
+        }"##
+        .parse()
+        .unwrap();
+
+        assert_eq!(style.background_color, Some(Color::from_rgb(216, 242, 255)));
+        assert_eq!(style.warnings.len(), 3);
+
+        assert_eq!(style.layers.len(), 1);
+        let layer = &style.layers[0];
+        assert_eq!(layer.name, "maplibre");
+        assert_eq!(layer.zoom_max, Some(6));
+
+        let source_layers: Vec<&str> = layer
+            .layers_shape_styles
+            .iter()
+            .map(|(source_layer, _)| source_layer.as_str())
+            .collect();
+        assert_eq!(source_layers, vec!["countries", "geolines", "centroids"]);
+
+        let fill = &layer.layers_shape_styles[0].1;
+        assert!(fill.fill_enabled && fill.stroke_enabled);
+        assert_eq!(fill.fill_color, Color::from_rgba(214, 199, 255, 0.5));
+        assert_eq!(fill.stroke_color, Color::from_rgba(255, 255, 255, 0.5));
+        assert_eq!(fill.fill_pattern, Some("hatching".to_string()));
+
+        let line = &layer.layers_shape_styles[1].1;
+        assert!(!line.fill_enabled && line.stroke_enabled);
+        assert_eq!(line.line_cap, LineCap::Round);
+        assert_eq!(line.dash_array, Some(vec![2.0, 1.0]));
+        let attrs = Some(HashMap::from([("name".to_string(), json!("Equator"))]));
+        let context = EvaluationContext::new(&attrs).with_geometry_type("LineString");
+        let resolved = line.resolve(&context.clone().with_zoom(6.0));
+        assert!(resolved.visible);
+        assert_eq!(resolved.stroke_width, 4.0);
+        assert!(!line.resolve(&context).visible);
+        assert!(
+            !line
+                .resolve(
+                    &EvaluationContext::default()
+                        .with_geometry_type("LineString")
+                        .with_zoom(6.0)
+                )
+                .visible
+        );
+
+        let circle = &layer.layers_shape_styles[2].1;
+        assert_eq!(circle.symbol_size, 6.0);
+        assert_eq!(circle.fill_color, Color::from_rgb(255, 0, 0));
+    }
+
+    #[test]
+    fn test_parse_legacy_filter() {
+        let filter = json!([
+            "all",
+            ["in", "class", "motorway", "trunk"],
+            ["<=", "admin_level", 2]
+        ]);
+        assert!(is_legacy_filter(&filter));
+
+        let filter = parse_filter(&filter, &mut Vec::new()).unwrap();
+        let evaluate = |attrs: JsonValue| {
+            let attrs = serde_json::from_value(attrs).unwrap();
+            filter.evaluate(&EvaluationContext::new(&Some(attrs)))
+        };
+        assert_eq!(
+            evaluate(json!({"class": "trunk", "admin_level": 2})),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            evaluate(json!({"class": "primary", "admin_level": 2})),
+            Value::Bool(false)
+        );
+
+        let filter = json!(["==", ["get", "class"], "motorway"]);
+        assert!(!is_legacy_filter(&filter));
+
+        let filter = parse_filter(&json!(["==", "$type", "Polygon"]), &mut Vec::new()).unwrap();
+        assert_eq!(
+            filter.evaluate(&EvaluationContext::default().with_geometry_type("Polygon")),
+            Value::Bool(true)
+        );
+
+        let mut warnings = Vec::new();
+        let filter = parse_filter(&json!(["==", "$id", 1]), &mut warnings).unwrap();
+        assert_eq!(filter, Expression::Literal(Value::Bool(true)));
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_geometry_type_filters() {
+        let style_layer = |r#type: &str, source_layer: &str| {
+            json!({
+                "id": format!("{}-{}", source_layer, r#type),
+                "type": r#type,
+                "source": "maplibre",
+                "source-layer": source_layer,
+            })
+        };
+        let style_layers: Vec<JsonValue> = ["countries", "geolines", "centroids"]
+            .iter()
+            .flat_map(|source_layer| {
+                ["fill", "line", "circle"].map(|r#type| style_layer(r#type, source_layer))
+            })
+            .collect();
+        let style = GlStyle::from_json(&json!({
+            "version": 8,
+            "sources": {
+                "maplibre": {
+                    "type": "vector",
+                    "tiles": ["https://demotiles.maplibre.org/tiles/{z}/{x}/{y}.pbf"]
+                }
+            },
+            "layers": style_layers,
+        }))
+        .unwrap();
+        let layers_shape_styles = &style.layers[0].layers_shape_styles;
+
+        let half = 20037508.34278924;
+        let vector_tile = VectorTile::from_data(
+            std::fs::read("../assets/mvt.pbf").unwrap(),
+            Rect::new(Coord { x: -half, y: -half }, Coord { x: half, y: half }),
+        )
+        .unwrap();
+        let tessellation = VectorTileTessellation::new(&vector_tile, layers_shape_styles);
+        let drawn: Vec<bool> = (0..layers_shape_styles.len())
+            .map(|index| {
+                tessellation
+                    .shape_metas
+                    .iter()
+                    .any(|shape_meta| shape_meta.shape_styles_index == index)
+            })
+            .collect();
+
+        // Fills draw polygons, lines draw lines and polygon outlines, circles draw points
+        assert_eq!(
+            drawn,
+            vec![true, true, false, false, true, false, false, false, true]
+        );
+    }
+}
@@ -1,6 +1,14 @@
+use std::any::Any;
+
+use geo::Geometry;
+
 use crate::{
     event::Event,
-    map::{context::MapState, Map, MapOptions},
+    feature::Feature,
+    map::{
+        context::{MapState, MapView},
+        Map, MapOptions,
+    },
     render::{InterRenderers, MapRenderer},
 };
 
@@ -9,12 +17,18 @@ pub mod image_layer;
 pub mod image_tiled_layer;
 pub mod vector_tiled_layer;
 
+pub(crate) mod query;
 pub(crate) mod tiled;
 
 pub trait Layer: Send + Sync {
     fn r#type(&self) -> LayerType;
     fn on_add_to_map(&mut self, map: &Map);
     fn on_remove_from_map(&mut self, map: &Map);
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Drop the state kept for a view, e.g. the tiles of a finished export.
+    fn clear_view(&mut self, view: MapView, map_renderer: &mut MapRenderer);
+    fn is_loaded(&self, view: MapView) -> bool;
+    fn query_features(&self, query: &FeatureQuery) -> Vec<QueriedFeature>;
     fn set_name(&mut self, name: &str);
     fn update(
         &mut self,
@@ -31,3 +45,21 @@ pub enum LayerType {
     ImageTiledLayer,
     VectorTiledLayer,
 }
+
+/// Geometry to query rendered features with, in map coordinates.
+pub struct FeatureQuery {
+    pub geometry: Geometry,
+    pub tolerance: f64,
+    /// Map units of one style pixel, e.g. for symbol sizes and stroke widths.
+    pub pixel_size: f64,
+    /// Fractional zoom of the map view, for zoom dependent styles.
+    pub zoom: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct QueriedFeature {
+    pub layer: String,
+    /// Source layer name in vector tiles.
+    pub source_layer: Option<String>,
+    pub feature: Feature,
+}
@@ -1,23 +1,61 @@
-use std::sync::Arc;
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
 
 use dashmap::DashMap;
 use tokio::sync::mpsc;
 
 use crate::{
     event::Event,
-    feature::{style::ShapeStyles, Feature, Features},
-    layer::{Layer, LayerType},
-    map::{context::MapState, Map, MapOptions},
-    render::{draw::feature::FeatureDrawable, InterRenderers, MapRenderer},
+    feature::{
+        style::{expression::EvaluationContext, ShapeStyles},
+        Feature, Features,
+    },
+    layer::{query::hit_test, FeatureQuery, Layer, LayerType, QueriedFeature},
+    map::{
+        context::{MapState, MapView},
+        Map, MapOptions,
+    },
+    render::{
+        draw::{feature::FeatureDrawable, feature_batch::FeatureBatchDrawable},
+        text::feature_labels,
+        InterRenderers, MapRenderer,
+    },
 };
 
+/// Max number of features in a batch, updating a feature only rebuilds the batch of its chunk.
+const BATCH_CHUNK_SIZE: usize = 1024;
+
 pub struct FeatureLayer {
     options: FeatureLayerOptions,
 
     name: String,
     event_sender: Option<mpsc::UnboundedSender<Event>>,
 
-    features: Arc<DashMap<String, Feature>>,
+    features: Arc<DashMap<String, FeatureItem>>,
+    /// Increased by every change of the features.
+    revision: u64,
+    /// Revision of the features the draw items in the renderer are of.
+    drawn_revision: Option<u64>,
+    /// Numbers of features in each chunk, new features fill the first chunk with room.
+    chunk_sizes: Vec<usize>,
+    /// Draw item ids of the features in batches, their labels are already added.
+    batch_item_ids: HashSet<String>,
+}
+
+struct FeatureItem {
+    feature: Feature,
+    revision: u64,
+    /// Index of the chunk the feature is batched in, kept when the feature is updated.
+    chunk: usize,
+}
+
+impl FeatureItem {
+    fn draw_item_id(&self) -> String {
+        format!("{}@{}", self.feature.id(), self.revision)
+    }
 }
 
 impl FeatureLayer {
@@ -29,33 +67,142 @@ impl FeatureLayer {
             event_sender: None,
 
             features: Arc::new(DashMap::new()),
+            revision: 0,
+            drawn_revision: None,
+            chunk_sizes: Vec::new(),
+            batch_item_ids: HashSet::new(),
         }
     }
 
+    /// Add a feature, or replace the feature with the same id.
     pub fn add_feature(&mut self, feature: Feature) {
-        self.features.insert(feature.id().to_string(), feature);
+        self.insert_feature(feature);
+        self.request_redraw();
+    }
 
-        if let Some(event_sender) = &self.event_sender {
-            let _ = event_sender.send(Event::MapRequestRedraw);
+    pub fn add_features(&mut self, features: Vec<Feature>) {
+        for feature in features {
+            self.insert_feature(feature);
         }
+        self.request_redraw();
     }
 
     pub fn add_features_from_geojson(&mut self, geojson: &str) {
         if let Ok(features) = Features::from_geojson(geojson) {
             match features {
                 Features::Single(feature) => {
-                    self.features.insert(feature.id().to_string(), feature);
+                    self.insert_feature(feature);
                 }
                 Features::Collection(features) => {
                     for feature in features {
-                        self.features.insert(feature.id().to_string(), feature);
+                        self.insert_feature(feature);
                     }
                 }
             }
 
-            if let Some(event_sender) = &self.event_sender {
-                let _ = event_sender.send(Event::MapRequestRedraw);
+            self.request_redraw();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.revision += 1;
+        self.features.clear();
+        self.chunk_sizes.clear();
+        self.request_redraw();
+    }
+
+    pub fn feature(&self, id: &str) -> Option<Feature> {
+        self.features.get(id).map(|item| item.feature.clone())
+    }
+
+    pub fn feature_ids(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .map(|pair| pair.key().clone())
+            .collect()
+    }
+
+    pub fn remove_feature(&mut self, id: &str) -> Option<Feature> {
+        let removed = self.take_feature(id);
+        if removed.is_some() {
+            self.request_redraw();
+        }
+
+        removed
+    }
+
+    pub fn remove_features(&mut self, ids: &[&str]) {
+        for id in ids {
+            self.take_feature(id);
+        }
+        self.request_redraw();
+    }
+
+    /// Replace all features of the layer.
+    pub fn set_features(&mut self, features: Vec<Feature>) {
+        self.revision += 1;
+        self.features.clear();
+        self.chunk_sizes.clear();
+        for feature in features {
+            self.insert_feature(feature);
+        }
+        self.request_redraw();
+    }
+
+    /// Replace an existing feature, returns false if there is no feature with the same id.
+    pub fn update_feature(&mut self, feature: Feature) -> bool {
+        if !self.features.contains_key(feature.id()) {
+            return false;
+        }
+
+        self.insert_feature(feature);
+        self.request_redraw();
+
+        true
+    }
+
+    fn insert_feature(&mut self, feature: Feature) {
+        self.revision += 1;
+
+        let existing_chunk = self.features.get(feature.id()).map(|item| item.chunk);
+        let chunk = match existing_chunk {
+            Some(chunk) => chunk,
+            None => {
+                let chunk = self
+                    .chunk_sizes
+                    .iter()
+                    .position(|size| *size < BATCH_CHUNK_SIZE)
+                    .unwrap_or(self.chunk_sizes.len());
+                if chunk == self.chunk_sizes.len() {
+                    self.chunk_sizes.push(0);
+                }
+                self.chunk_sizes[chunk] += 1;
+
+                chunk
             }
+        };
+
+        self.features.insert(
+            feature.id().to_string(),
+            FeatureItem {
+                feature,
+                revision: self.revision,
+                chunk,
+            },
+        );
+    }
+
+    fn take_feature(&mut self, id: &str) -> Option<Feature> {
+        let (_, item) = self.features.remove(id)?;
+        self.revision += 1;
+        self.chunk_sizes[item.chunk] -= 1;
+
+        Some(item.feature)
+    }
+
+    fn request_redraw(&self) {
+        if let Some(event_sender) = &self.event_sender {
+            let _ = event_sender.send(Event::MapRequestRedraw);
         }
     }
 }
@@ -65,12 +212,45 @@ impl Layer for FeatureLayer {
         LayerType::FeatureLayer
     }
 
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clear_view(&mut self, _view: MapView, _map_renderer: &mut MapRenderer) {}
+
+    fn is_loaded(&self, _view: MapView) -> bool {
+        true
+    }
+
     fn on_add_to_map(&mut self, map: &Map) {
         self.event_sender = Some(map.event_sender.clone());
+        self.drawn_revision = None;
     }
 
     fn on_remove_from_map(&mut self, _map: &Map) {
         self.event_sender = None;
+        self.drawn_revision = None;
+    }
+
+    fn query_features(&self, query: &FeatureQuery) -> Vec<QueriedFeature> {
+        self.features
+            .iter()
+            .filter(|pair| {
+                let feature = &pair.value().feature;
+                let shape_styles = self.options.shape_styles.resolve(
+                    &EvaluationContext::new(feature.attrs())
+                        .with_geometry_type(feature.shape().geometry_type())
+                        .with_zoom(query.zoom),
+                );
+
+                shape_styles.visible && hit_test(feature.shape(), &shape_styles, query)
+            })
+            .map(|pair| QueriedFeature {
+                layer: self.name.clone(),
+                source_layer: None,
+                feature: pair.value().feature.clone(),
+            })
+            .collect()
     }
 
     fn set_name(&mut self, name: &str) {
@@ -79,35 +259,104 @@ impl Layer for FeatureLayer {
 
     fn update(
         &mut self,
-        _map_options: &MapOptions,
-        _map_state: &MapState,
+        map_options: &MapOptions,
+        map_state: &MapState,
         map_renderer: &mut MapRenderer,
         _inter_renderers: &mut InterRenderers,
     ) {
+        // Draw items only change with the features
+        if self.drawn_revision == Some(self.revision) {
+            return;
+        }
+
+        let zoom = map_options.tiling.zoom_for_resolution(map_state.zoom_res);
+        let mut draw_item_ids = HashSet::new();
+
+        let batched = self.options.batched
+            && map_renderer.supports_feature_batches()
+            && FeatureBatchDrawable::supports(&self.options.shape_styles);
+        let mut batch_item_ids = HashSet::new();
+        // Latest revision and feature ids of the batched features of each chunk
+        let mut chunks: BTreeMap<usize, (u64, Vec<String>)> = BTreeMap::new();
+
         for pair in self.features.iter() {
-            let feature_id = pair.key();
-            let feature = pair.value();
+            let item = pair.value();
+            let draw_item_id = item.draw_item_id();
+
+            if batched && !item.feature.shape().is_points() {
+                if !self.batch_item_ids.contains(&draw_item_id) {
+                    let labels = feature_labels(&item.feature, &self.options.shape_styles, zoom);
+                    map_renderer.add_layer_labels(&self.name, &draw_item_id, labels);
+                }
 
-            if !map_renderer.contains_layer_draw_item(&self.name, &feature_id) {
+                let (revision, feature_ids) = chunks.entry(item.chunk).or_default();
+                *revision = (*revision).max(item.revision);
+                feature_ids.push(pair.key().clone());
+                batch_item_ids.insert(draw_item_id.clone());
+            } else if !map_renderer.contains_layer_draw_item(&self.name, &draw_item_id) {
                 let drawable = FeatureDrawable::new(
                     &map_renderer,
-                    &feature,
+                    &item.feature,
                     self.options.z,
                     &self.options.shape_styles,
                 );
 
-                map_renderer.add_layer_draw_item(&self.name, &feature_id, drawable.into());
+                map_renderer.add_layer_draw_item(&self.name, &draw_item_id, drawable.into());
+
+                let labels = feature_labels(&item.feature, &self.options.shape_styles, zoom);
+                map_renderer.add_layer_labels(&self.name, &draw_item_id, labels);
+            }
+
+            draw_item_ids.insert(draw_item_id);
+        }
+
+        for (chunk, (revision, feature_ids)) in chunks {
+            // Revisions only increase, so the latest revision and the number of batched features
+            // identify the features of a chunk, its batch is rebuilt when any of them is updated
+            // or removed
+            let batch_item_id = format!("batch{}@{}@{}", chunk, revision, feature_ids.len());
+
+            if !map_renderer.contains_layer_draw_item(&self.name, &batch_item_id) {
+                let features = feature_ids
+                    .iter()
+                    .filter_map(|id| self.features.get(id).map(|item| item.feature.clone()))
+                    .collect();
+                let drawable = FeatureBatchDrawable::new(
+                    map_renderer,
+                    features,
+                    self.options.z,
+                    &self.options.shape_styles,
+                );
+
+                map_renderer.add_layer_draw_item(&self.name, &batch_item_id, drawable.into());
             }
+
+            draw_item_ids.insert(batch_item_id);
         }
+        self.batch_item_ids = batch_item_ids;
+
+        // Remove draw items of updated or removed features
+        map_renderer.retain_layer_draw_items(&self.name, |item_id| draw_item_ids.contains(item_id));
+
+        self.drawn_revision = Some(self.revision);
     }
 }
 
 pub struct FeatureLayerOptions {
     shape_styles: ShapeStyles,
     z: f64,
+    batched: bool,
 }
 
 impl FeatureLayerOptions {
+    /// Draw polygons and lines in batches, much faster with many features. Updating a feature
+    /// only rebuilds the batch it is in. Points and styles with patterns, gradients or dashes are
+    /// still drawn per feature.
+    pub fn with_batched(mut self, v: bool) -> Self {
+        self.batched = v;
+        self
+    }
+
     pub fn with_shape_styles(mut self, v: ShapeStyles) -> Self {
         self.shape_styles = v;
         self
@@ -124,6 +373,86 @@ impl Default for FeatureLayerOptions {
         Self {
             shape_styles: ShapeStyles::default(),
             z: 0.0,
+            batched: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::Point;
+
+    use super::*;
+    use crate::feature::Shape;
+
+    fn point_feature(id: &str, x: f64, y: f64) -> Feature {
+        Feature::new(id, Shape::Geometry(Point::new(x, y).into()), None)
+    }
+
+    #[test]
+    fn test_update_remove_features() {
+        let mut layer = FeatureLayer::new(FeatureLayerOptions::default());
+
+        layer.add_features(vec![
+            point_feature("a", 0.0, 0.0),
+            point_feature("b", 1.0, 1.0),
+        ]);
+        assert_eq!(layer.features.len(), 2);
+
+        let draw_item_id = layer.features.get("a").unwrap().draw_item_id();
+        assert!(layer.update_feature(point_feature("a", 2.0, 2.0)));
+        assert_ne!(
+            layer.features.get("a").unwrap().draw_item_id(),
+            draw_item_id
+        );
+        assert!(!layer.update_feature(point_feature("c", 0.0, 0.0)));
+
+        assert_eq!(layer.remove_feature("b").unwrap().id(), "b");
+        assert!(layer.remove_feature("b").is_none());
+        assert_eq!(layer.feature_ids(), vec!["a".to_string()]);
+
+        layer.set_features(vec![
+            point_feature("c", 0.0, 0.0),
+            point_feature("d", 0.0, 0.0),
+        ]);
+        assert!(layer.feature("a").is_none());
+        assert_eq!(layer.features.len(), 2);
+
+        layer.remove_features(&["c"]);
+        assert_eq!(layer.feature_ids(), vec!["d".to_string()]);
+
+        layer.clear();
+        assert!(layer.features.is_empty());
+    }
+
+    #[test]
+    fn test_batch_chunks() {
+        let mut layer = FeatureLayer::new(FeatureLayerOptions::default());
+
+        layer.add_features(
+            (0..=BATCH_CHUNK_SIZE)
+                .map(|i| point_feature(&i.to_string(), 0.0, 0.0))
+                .collect(),
+        );
+        assert_eq!(layer.chunk_sizes, vec![BATCH_CHUNK_SIZE, 1]);
+        let last_id = BATCH_CHUNK_SIZE.to_string();
+        assert_eq!(layer.features.get(&last_id).unwrap().chunk, 1);
+
+        // Updated features stay in their chunk
+        assert!(layer.update_feature(point_feature(&last_id, 1.0, 1.0)));
+        assert_eq!(layer.features.get(&last_id).unwrap().chunk, 1);
+        assert_eq!(layer.chunk_sizes, vec![BATCH_CHUNK_SIZE, 1]);
+
+        // New features fill the first chunk with room
+        layer.remove_feature("0");
+        layer.add_feature(point_feature("new", 0.0, 0.0));
+        assert_eq!(layer.features.get("new").unwrap().chunk, 0);
+        assert_eq!(layer.chunk_sizes, vec![BATCH_CHUNK_SIZE, 1]);
+
+        layer.remove_features(&[&last_id]);
+        assert_eq!(layer.chunk_sizes, vec![BATCH_CHUNK_SIZE, 0]);
+
+        layer.set_features(vec![point_feature("a", 0.0, 0.0)]);
+        assert_eq!(layer.chunk_sizes, vec![1]);
+    }
+}
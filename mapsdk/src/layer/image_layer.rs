@@ -1,6 +1,9 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, RwLock,
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 
 use geo::Rect;
@@ -11,8 +14,11 @@ use tokio::sync::mpsc;
 use crate::{
     env,
     event::Event,
-    layer::{Layer, LayerType},
-    map::{context::MapState, Map, MapOptions},
+    layer::{FeatureQuery, Layer, LayerType, QueriedFeature},
+    map::{
+        context::{MapState, MapView},
+        Map, MapOptions,
+    },
     render::{draw::image::ImageDrawable, InterRenderers, MapRenderer},
     utils::{http::HttpClient, image::image_from_url},
 };
@@ -28,6 +34,7 @@ pub struct ImageLayer {
     image_id: String,
     image: Arc<RwLock<Option<RgbaImage>>>,
     image_requested: bool,
+    image_loading: Arc<AtomicBool>,
     image_updated: Arc<AtomicBool>,
 }
 
@@ -44,6 +51,7 @@ impl ImageLayer {
             image_id: nanoid!(),
             image: Arc::new(RwLock::new(None)),
             image_requested: false,
+            image_loading: Arc::new(AtomicBool::new(false)),
             image_updated: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -54,6 +62,16 @@ impl Layer for ImageLayer {
         LayerType::ImageLayer
     }
 
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clear_view(&mut self, _view: MapView, _map_renderer: &mut MapRenderer) {}
+
+    fn is_loaded(&self, _view: MapView) -> bool {
+        self.image_requested && !self.image_loading.load(Ordering::SeqCst)
+    }
+
     fn on_add_to_map(&mut self, map: &Map) {
         self.event_sender = Some(map.event_sender.clone());
     }
@@ -62,6 +80,10 @@ impl Layer for ImageLayer {
         self.event_sender = None;
     }
 
+    fn query_features(&self, _query: &FeatureQuery) -> Vec<QueriedFeature> {
+        Vec::new()
+    }
+
     fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
@@ -75,11 +97,13 @@ impl Layer for ImageLayer {
     ) {
         if !self.image_requested {
             self.image_requested = true;
+            self.image_loading.store(true, Ordering::SeqCst);
 
             env::spawn({
                 let url = self.url.clone();
                 let headers = self.options.headers.clone();
                 let image = self.image.clone();
+                let image_loading = self.image_loading.clone();
                 let image_updated = self.image_updated.clone();
                 let event_sender = self.event_sender.clone();
 
@@ -103,6 +127,8 @@ impl Layer for ImageLayer {
                             log::error!("Load image from {} error: {}", url, err);
                         }
                     }
+
+                    image_loading.store(false, Ordering::SeqCst);
                 }
             });
         }
@@ -1,7 +1,10 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use dashmap::{DashMap, DashSet};
-use geo::Intersects;
+use dashmap::DashSet;
 use image::RgbaImage;
 use moka::sync::Cache;
 use tokio::{sync::mpsc, task::JoinHandle};
@@ -9,10 +12,16 @@ use tokio::{sync::mpsc, task::JoinHandle};
 use crate::{
     env,
     layer::{
-        tiled::{format_tile_url, tile_ids_in_view},
-        Event, Layer, LayerType,
+        tiled::{
+            format_tile_url, other_views_tile_ids, publish_tile_failed, publish_tile_loaded,
+            tile_ids_in_view, tile_in_view, ViewTiles,
+        },
+        Event, FeatureQuery, Layer, LayerType, QueriedFeature,
+    },
+    map::{
+        context::{MapState, MapView},
+        Map, MapOptions,
     },
-    map::{context::MapState, Map, MapOptions},
     render::{draw::image::ImageDrawable, InterRenderers, MapRenderer},
     tiling::TileId,
     utils::http::{HttpPool, HttpRequest, HttpResponse},
@@ -29,9 +38,11 @@ pub struct ImageTiledLayer {
     tile_response_handle: Option<JoinHandle<()>>,
 
     requesting_tile_ids: Arc<DashSet<TileId>>,
+    failed_tile_ids: Arc<DashSet<TileId>>,
 
     tiles_cache: Cache<TileId, RgbaImage>,
-    tiles: Arc<DashMap<TileId, RgbaImage>>,
+    /// Tiles of the live view and of exports in progress.
+    views: HashMap<MapView, ViewTiles<RgbaImage>>,
 }
 
 impl ImageTiledLayer {
@@ -49,9 +60,10 @@ impl ImageTiledLayer {
             tile_response_handle: None,
 
             requesting_tile_ids: Arc::new(DashSet::new()),
+            failed_tile_ids: Arc::new(DashSet::new()),
 
             tiles_cache: Cache::new(cache_size),
-            tiles: Arc::new(DashMap::new()),
+            views: HashMap::new(),
         }
     }
 }
@@ -61,6 +73,21 @@ impl Layer for ImageTiledLayer {
         LayerType::ImageTiledLayer
     }
 
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clear_view(&mut self, view: MapView, map_renderer: &mut MapRenderer) {
+        self.views.remove(&view);
+        map_renderer.clear_layer_draw_items(&view.layer_key(&self.name));
+    }
+
+    fn is_loaded(&self, view: MapView) -> bool {
+        self.views
+            .get(&view)
+            .is_some_and(|view_tiles| view_tiles.loaded)
+    }
+
     fn on_add_to_map(&mut self, map: &Map) {
         if self.event_sender.is_some() {
             return;
@@ -73,8 +100,10 @@ impl Layer for ImageTiledLayer {
         self.tile_fetcher = Some(HttpPool::new(self.options.concurrent, tile_response_sender));
 
         self.tile_response_handle = Some(env::spawn({
+            let name = self.name.clone();
             let tiles_cache = self.tiles_cache.clone();
             let requesting_tile_ids = self.requesting_tile_ids.clone();
+            let failed_tile_ids = self.failed_tile_ids.clone();
             let event_sender = self.event_sender.clone();
 
             async move {
@@ -83,25 +112,45 @@ impl Layer for ImageTiledLayer {
 
                     if let Some(http_response) = http_response {
                         let tile_id = http_response.id.clone();
-                        if let Ok(bytes) = http_response.bytes().await {
-                            requesting_tile_ids.remove(&tile_id);
-
-                            env::spawn({
-                                let tiles_cache = tiles_cache.clone();
-                                let event_sender = event_sender.clone();
-
-                                async move {
-                                    if let Ok(image) = image::load_from_memory(&bytes) {
-                                        let tile = image.to_rgba8();
-
-                                        tiles_cache.insert(tile_id.clone(), tile);
-
-                                        if let Some(event_sender) = &event_sender {
-                                            let _ = event_sender.send(Event::MapRequestRedraw);
+                        match http_response.bytes().await {
+                            Ok(bytes) => {
+                                requesting_tile_ids.remove(&tile_id);
+
+                                env::spawn({
+                                    let name = name.clone();
+                                    let tiles_cache = tiles_cache.clone();
+                                    let failed_tile_ids = failed_tile_ids.clone();
+                                    let event_sender = event_sender.clone();
+
+                                    async move {
+                                        match image::load_from_memory(&bytes) {
+                                            Ok(image) => {
+                                                let tile = image.to_rgba8();
+
+                                                tiles_cache.insert(tile_id.clone(), tile);
+
+                                                publish_tile_loaded(&event_sender, &name, &tile_id);
+                                            }
+                                            Err(err) => {
+                                                failed_tile_ids.insert(tile_id.clone());
+
+                                                publish_tile_failed(
+                                                    &event_sender,
+                                                    &name,
+                                                    &tile_id,
+                                                    &err,
+                                                );
+                                            }
                                         }
                                     }
-                                }
-                            });
+                                });
+                            }
+                            Err(err) => {
+                                failed_tile_ids.insert(tile_id.clone());
+                                requesting_tile_ids.remove(&tile_id);
+
+                                publish_tile_failed(&event_sender, &name, &tile_id, &err);
+                            }
                         }
                     }
                 }
@@ -125,6 +174,10 @@ impl Layer for ImageTiledLayer {
         self.tile_response_handle = None;
     }
 
+    fn query_features(&self, _query: &FeatureQuery) -> Vec<QueriedFeature> {
+        Vec::new()
+    }
+
     fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
@@ -136,16 +189,22 @@ impl Layer for ImageTiledLayer {
         map_renderer: &mut MapRenderer,
         _inter_renderers: &mut InterRenderers,
     ) {
-        let tile_ids = tile_ids_in_view(map_state, &map_options.tiling);
+        let tile_ids = tile_ids_in_view(map_state, &map_options.tiling, map_options.world_copy);
         let center_tile_id = map_options
             .tiling
             .get_tile_id(map_state.zoom, &map_state.center);
 
+        // Each view keeps its own tiles, tiles needed by other views are still requested
+        let other_tile_ids = other_views_tile_ids(&self.views, map_state.view);
+        let layer_key = map_state.view.layer_key(&self.name);
+        let mut view_tiles = self.views.remove(&map_state.view).unwrap_or_default();
+        view_tiles.tile_ids = tile_ids.clone();
+
         // Cancel tile requestes that are no longer needed
         {
             let mut cancel_tile_ids: Vec<TileId> = Vec::new();
             for tile_id in self.requesting_tile_ids.iter() {
-                if !tile_ids.contains(&tile_id) {
+                if !tile_ids.contains(&tile_id) && !other_tile_ids.contains(&tile_id) {
                     cancel_tile_ids.push(tile_id.clone());
                 }
             }
@@ -157,22 +216,33 @@ impl Layer for ImageTiledLayer {
                     tile_fetcher.cancel(&tile_id);
                 }
             });
+
+            // Failed tiles are retried once they come into view again
+            self.failed_tile_ids
+                .retain(|tile_id| tile_ids.contains(tile_id) || other_tile_ids.contains(tile_id));
         }
 
         // Load tiles from cache if possible
         tile_ids.iter().for_each(|tile_id| {
-            if !self.tiles.contains_key(tile_id) {
+            if !view_tiles.tiles.contains_key(tile_id) {
                 if let Some(tile) = self.tiles_cache.get(tile_id) {
-                    self.tiles.insert(tile_id.clone(), tile.clone());
+                    view_tiles.tiles.insert(tile_id.clone(), tile.clone());
                 }
             }
         });
 
+        let tile_ids_loaded = tile_ids.iter().all(|tile_id| {
+            view_tiles.tiles.contains_key(tile_id) || self.failed_tile_ids.contains(tile_id)
+        });
+
         // Load tiles from server
         {
             let mut load_tile_ids = tile_ids
                 .iter()
-                .filter(|tile_id| !self.tiles.contains_key(&tile_id))
+                .filter(|tile_id| {
+                    !view_tiles.tiles.contains_key(tile_id)
+                        && !self.failed_tile_ids.contains(tile_id)
+                })
                 .collect::<Vec<_>>();
 
             load_tile_ids.sort_by_key(|tile_id| {
@@ -213,23 +283,26 @@ impl Layer for ImageTiledLayer {
         {
             let mut dirty_tiles: HashSet<TileId> = HashSet::new();
 
-            for pair in self.tiles.iter() {
+            for pair in view_tiles.tiles.iter() {
                 let tile_id = pair.key();
 
                 if !tile_ids.contains(tile_id) {
                     dirty_tiles.insert(tile_id.clone());
                 }
 
-                if let Some(bbox) = map_options.tiling.get_tile_bbox(&tile_id) {
-                    if !bbox.to_polygon().intersects(map_state.view_bounds()) {
-                        dirty_tiles.insert(tile_id.clone());
-                    }
+                if !tile_in_view(
+                    map_state,
+                    &map_options.tiling,
+                    tile_id,
+                    map_options.world_copy,
+                ) {
+                    dirty_tiles.insert(tile_id.clone());
                 }
             }
 
             // Keep resample tiles if possible
             'tiles: for tile_id in tile_ids {
-                if !self.tiles.contains_key(&tile_id) {
+                if !view_tiles.tiles.contains_key(&tile_id) {
                     for level in 1..=self.options.max_up_scale_level {
                         if let Some(parent_tile_id) =
                             map_options.tiling.roll_up_tile_id(&tile_id, level)
@@ -239,7 +312,7 @@ impl Layer for ImageTiledLayer {
                                     if let Some(up_tile_id) =
                                         map_options.tiling.roll_up_tile_id(&tile_id, up_level)
                                     {
-                                        if self.tiles.contains_key(&up_tile_id)
+                                        if view_tiles.tiles.contains_key(&up_tile_id)
                                             && !dirty_tiles.contains(&up_tile_id)
                                         {
                                             continue 'tiles;
@@ -247,7 +320,7 @@ impl Layer for ImageTiledLayer {
                                     }
                                 }
 
-                                self.tiles.insert(parent_tile_id.clone(), parent_tile);
+                                view_tiles.tiles.insert(parent_tile_id.clone(), parent_tile);
                                 dirty_tiles.remove(&parent_tile_id);
 
                                 for cover_level in 1..=level {
@@ -265,7 +338,7 @@ impl Layer for ImageTiledLayer {
                     let child_tile_ids = map_options.tiling.drill_down_tile_ids(&tile_id, 1);
                     for child_tile_id in child_tile_ids {
                         if let Some(child_tile) = self.tiles_cache.get(&child_tile_id) {
-                            self.tiles.insert(child_tile_id.clone(), child_tile);
+                            view_tiles.tiles.insert(child_tile_id.clone(), child_tile);
                             dirty_tiles.remove(&child_tile_id);
                         }
                     }
@@ -273,24 +346,27 @@ impl Layer for ImageTiledLayer {
             }
 
             for tile_id in dirty_tiles {
-                self.tiles.remove(&tile_id);
+                view_tiles.tiles.remove(&tile_id);
 
-                map_renderer.remove_layer_draw_item(&self.name, &tile_id);
+                map_renderer.remove_layer_draw_item(&layer_key, &tile_id);
             }
         }
 
-        for pair in self.tiles.iter() {
+        for pair in view_tiles.tiles.iter() {
             let tile_id = pair.key();
             let image = pair.value();
 
             if let Some(bbox) = map_options.tiling.get_tile_bbox(&tile_id) {
-                if !map_renderer.contains_layer_draw_item(&self.name, tile_id) {
+                if !map_renderer.contains_layer_draw_item(&layer_key, tile_id) {
                     let drawable = ImageDrawable::new(&map_renderer, &image, &bbox, self.options.z);
 
-                    map_renderer.add_layer_draw_item(&self.name, tile_id, drawable.into());
+                    map_renderer.add_layer_draw_item(&layer_key, tile_id, drawable.into());
                 }
             }
         }
+
+        view_tiles.loaded = tile_ids_loaded;
+        self.views.insert(map_state.view, view_tiles);
     }
 }
 
@@ -0,0 +1,155 @@
+use geo::*;
+
+use crate::{
+    feature::{style::ShapeStyles, Shape},
+    layer::FeatureQuery,
+};
+
+/// Test if a shape drawn with the given styles is hit by the query.
+pub fn hit_test(shape: &Shape, shape_styles: &ShapeStyles, query: &FeatureQuery) -> bool {
+    match shape {
+        Shape::Circle { center, radius } => {
+            query.geometry.euclidean_distance(&Point::from(*center)) <= radius + query.tolerance
+        }
+        Shape::Geometry(geom) => hit_test_geometry(geom, shape_styles, query),
+    }
+}
+
+fn hit_test_geometry(geom: &Geometry, shape_styles: &ShapeStyles, query: &FeatureQuery) -> bool {
+    let stroke_width = if shape_styles.stroke_enabled {
+        shape_styles.stroke_width as f64 * query.pixel_size
+    } else {
+        0.0
+    };
+
+    match geom {
+        Geometry::Point(_) | Geometry::MultiPoint(_) => {
+            let radius = shape_styles.symbol_size as f64 * query.pixel_size / 2.0;
+            query.geometry.euclidean_distance(geom) <= radius + query.tolerance
+        }
+        Geometry::Line(_) | Geometry::LineString(_) | Geometry::MultiLineString(_) => {
+            query.geometry.euclidean_distance(geom) <= stroke_width / 2.0 + query.tolerance
+        }
+        Geometry::Polygon(_)
+        | Geometry::MultiPolygon(_)
+        | Geometry::Rect(_)
+        | Geometry::Triangle(_) => {
+            if shape_styles.fill_enabled
+                && query.geometry.euclidean_distance(geom) <= query.tolerance
+            {
+                return true;
+            }
+
+            // Outlines may be drawn on one side only, so use the full width on both
+            shape_styles.stroke_enabled
+                && query.geometry.euclidean_distance(&outlines(geom))
+                    <= stroke_width + query.tolerance
+        }
+        Geometry::GeometryCollection(collection) => collection
+            .iter()
+            .any(|geom| hit_test_geometry(geom, shape_styles, query)),
+    }
+}
+
+fn outlines(geom: &Geometry) -> MultiLineString {
+    let polygons = match geom {
+        Geometry::Polygon(polygon) => vec![polygon.clone()],
+        Geometry::MultiPolygon(multi_polygon) => multi_polygon.0.clone(),
+        Geometry::Rect(rect) => vec![rect.to_polygon()],
+        Geometry::Triangle(triangle) => vec![triangle.to_polygon()],
+        _ => Vec::new(),
+    };
+
+    MultiLineString::new(
+        polygons
+            .into_iter()
+            .flat_map(|polygon| {
+                let (exterior, interiors) = polygon.into_inner();
+                std::iter::once(exterior).chain(interiors)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point_query(x: f64, y: f64, tolerance: f64) -> FeatureQuery {
+        FeatureQuery {
+            geometry: Point::new(x, y).into(),
+            tolerance,
+            pixel_size: 1.0,
+            zoom: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_hit_test_point() {
+        let shape = Shape::Geometry(Point::new(0.0, 0.0).into());
+        let shape_styles = ShapeStyles {
+            symbol_size: 10.0,
+            ..Default::default()
+        };
+
+        assert!(hit_test(&shape, &shape_styles, &point_query(4.0, 0.0, 0.0)));
+        assert!(!hit_test(
+            &shape,
+            &shape_styles,
+            &point_query(6.0, 0.0, 0.0)
+        ));
+        assert!(hit_test(&shape, &shape_styles, &point_query(6.0, 0.0, 2.0)));
+    }
+
+    #[test]
+    fn test_hit_test_line() {
+        let shape = Shape::Geometry(line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0)].into());
+        let shape_styles = ShapeStyles {
+            stroke_width: 4.0,
+            ..Default::default()
+        };
+
+        assert!(hit_test(&shape, &shape_styles, &point_query(5.0, 1.5, 0.0)));
+        assert!(!hit_test(
+            &shape,
+            &shape_styles,
+            &point_query(5.0, 3.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn test_hit_test_polygon_with_hole() {
+        let shape = Shape::Geometry(
+            polygon!(
+                exterior: [(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0), (x: 0.0, y: 10.0)],
+                interiors: [[(x: 3.0, y: 3.0), (x: 7.0, y: 3.0), (x: 7.0, y: 7.0), (x: 3.0, y: 7.0)]],
+            )
+            .into(),
+        );
+        let shape_styles = ShapeStyles {
+            stroke_width: 1.0,
+            ..Default::default()
+        };
+
+        assert!(hit_test(&shape, &shape_styles, &point_query(1.0, 1.0, 0.0)));
+        assert!(!hit_test(
+            &shape,
+            &shape_styles,
+            &point_query(5.0, 5.0, 0.0)
+        ));
+        assert!(hit_test(&shape, &shape_styles, &point_query(3.5, 5.0, 0.0)));
+        assert!(!hit_test(
+            &shape,
+            &shape_styles,
+            &point_query(12.0, 5.0, 0.0)
+        ));
+
+        let box_query = FeatureQuery {
+            geometry: Rect::new((4.5, 4.5), (5.5, 5.5)).to_polygon().into(),
+            tolerance: 0.0,
+            pixel_size: 1.0,
+            zoom: 0.0,
+        };
+        assert!(!hit_test(&shape, &shape_styles, &box_query));
+    }
+}
@@ -1,16 +1,85 @@
-use geo::{BoundingRect, Coord, Intersects};
+use std::collections::{HashMap, HashSet};
+
+use dashmap::DashMap;
+use geo::{BoundingRect, Coord, Intersects, Polygon, Translate};
+use tokio::sync::mpsc;
 
 use crate::{
-    map::context::MapState,
+    event::{Event, MapEvent},
+    map::context::{MapState, MapView},
     tiling::{TileId, Tiling},
 };
 
-pub fn tile_ids_in_view(map_state: &MapState, tiling: &Tiling) -> Vec<TileId> {
-    let mut tile_ids = Vec::new();
+/// Tiles of a tiled layer in a view, each view loads and keeps its own tiles.
+pub struct ViewTiles<T> {
+    pub tiles: DashMap<TileId, T>,
+    /// Ids of the tiles the view needs, their requests are kept while other views update.
+    pub tile_ids: Vec<TileId>,
+    pub loaded: bool,
+}
+
+impl<T> Default for ViewTiles<T> {
+    fn default() -> Self {
+        Self {
+            tiles: DashMap::new(),
+            tile_ids: Vec::new(),
+            loaded: false,
+        }
+    }
+}
+
+/// Ids of the tiles needed by the views other than the given one.
+pub fn other_views_tile_ids<T>(
+    views: &HashMap<MapView, ViewTiles<T>>,
+    view: MapView,
+) -> HashSet<TileId> {
+    views
+        .iter()
+        .filter(|(other_view, _)| **other_view != view)
+        .flat_map(|(_, view_tiles)| view_tiles.tile_ids.iter().cloned())
+        .collect()
+}
+
+/// Ids of the tiles in view, x indices are wrapped into the tiling width when `world_copy` is on.
+pub fn tile_ids_in_view(map_state: &MapState, tiling: &Tiling, world_copy: bool) -> Vec<TileId> {
+    tile_ids_in_bounds(map_state.view_bounds(), map_state.zoom, tiling, world_copy)
+}
 
-    let z = map_state.zoom;
+/// Whether the tile, or one of its world copies when `world_copy` is on, intersects the view.
+pub fn tile_in_view(
+    map_state: &MapState,
+    tiling: &Tiling,
+    tile_id: &TileId,
+    world_copy: bool,
+) -> bool {
+    let view_bounds = map_state.view_bounds();
 
-    if let Some(view_rect) = map_state.view_bounds().bounding_rect() {
+    if let Some(bbox) = tiling.get_tile_bbox(tile_id) {
+        if !world_copy {
+            return bbox.to_polygon().intersects(view_bounds);
+        }
+
+        if let Some(view_rect) = view_bounds.bounding_rect() {
+            return tiling.world_copies(&view_rect).any(|copy| {
+                bbox.translate(copy as f64 * tiling.map_size(), 0.0)
+                    .to_polygon()
+                    .intersects(view_bounds)
+            });
+        }
+    }
+
+    false
+}
+
+fn tile_ids_in_bounds(
+    view_bounds: &Polygon,
+    z: usize,
+    tiling: &Tiling,
+    world_copy: bool,
+) -> Vec<TileId> {
+    let mut tile_ids = Vec::new();
+
+    if let Some(view_rect) = view_bounds.bounding_rect() {
         if let (Some(lt), Some(lb), Some(rt), Some(rb)) = (
             tiling.get_tile_id(
                 z,
@@ -43,17 +112,26 @@ pub fn tile_ids_in_view(map_state: &MapState, tiling: &Tiling) -> Vec<TileId> {
         ) {
             let max_x_y = tiling.get_max_x_y(z);
 
-            let min_x = lt.x.min(lb.x).min(rt.x).min(rb.x).max(0);
-            let max_x = lt.x.max(lb.x).max(rt.x).max(rb.x).min(max_x_y);
+            let mut min_x = lt.x.min(lb.x).min(rt.x).min(rb.x);
+            let mut max_x = lt.x.max(lb.x).max(rt.x).max(rb.x);
             let min_y = lt.y.min(lb.y).min(rt.y).min(rb.y).max(0);
             let max_y = lt.y.max(lb.y).max(rt.y).max(rb.y).min(max_x_y);
 
+            if !world_copy {
+                min_x = min_x.max(0);
+                max_x = max_x.min(max_x_y);
+            }
+
             for x in min_x..=max_x {
                 for y in min_y..=max_y {
                     let tile_id = TileId { z, x, y };
                     if let Some(bbox) = tiling.get_tile_bbox(&tile_id) {
-                        if bbox.to_polygon().intersects(map_state.view_bounds()) {
-                            tile_ids.push(tile_id);
+                        if bbox.to_polygon().intersects(view_bounds) {
+                            // Tiles of world copies are drawn from the tile of the world
+                            let tile_id = tiling.wrap_tile_id(&tile_id);
+                            if !tile_ids.contains(&tile_id) {
+                                tile_ids.push(tile_id);
+                            }
                         }
                     }
                 }
@@ -87,10 +165,95 @@ pub fn format_tile_url(
     url
 }
 
+pub fn publish_tile_loaded(
+    event_sender: &Option<mpsc::UnboundedSender<Event>>,
+    layer: &str,
+    tile_id: &TileId,
+) {
+    if let Some(event_sender) = event_sender {
+        let _ = event_sender.send(Event::Publish(MapEvent::TileLoaded {
+            layer: layer.to_string(),
+            tile_id: tile_id.clone(),
+        }));
+        let _ = event_sender.send(Event::MapRequestRedraw);
+    }
+}
+
+pub fn publish_tile_failed(
+    event_sender: &Option<mpsc::UnboundedSender<Event>>,
+    layer: &str,
+    tile_id: &TileId,
+    error: &impl ToString,
+) {
+    log::error!(
+        "Load tile {:?} of layer [{}] error: {}",
+        tile_id,
+        layer,
+        error.to_string()
+    );
+
+    if let Some(event_sender) = event_sender {
+        let _ = event_sender.send(Event::Publish(MapEvent::TileFailed {
+            layer: layer.to_string(),
+            tile_id: tile_id.clone(),
+            error: error.to_string(),
+        }));
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use geo::Rect;
+
     use super::*;
 
+    #[test]
+    fn test_tile_ids_in_bounds() {
+        let tiling = Tiling::default();
+        let half = tiling.map_size() / 2.0;
+
+        // Across the antimeridian
+        let view_bounds = Rect::new(
+            Coord {
+                x: half - 1000.0,
+                y: 1000.0,
+            },
+            Coord {
+                x: half + 1000.0,
+                y: 2000.0,
+            },
+        )
+        .to_polygon();
+
+        let tile_ids = tile_ids_in_bounds(&view_bounds, 2, &tiling, true);
+        assert_eq!(
+            tile_ids,
+            vec![TileId { z: 2, x: 3, y: 1 }, TileId { z: 2, x: 0, y: 1 }]
+        );
+
+        let tile_ids = tile_ids_in_bounds(&view_bounds, 2, &tiling, false);
+        assert_eq!(tile_ids, vec![TileId { z: 2, x: 3, y: 1 }]);
+
+        // Wider than the world
+        let view_bounds = Rect::new(
+            Coord {
+                x: -half * 3.0,
+                y: 1000.0,
+            },
+            Coord {
+                x: half * 3.0,
+                y: 2000.0,
+            },
+        )
+        .to_polygon();
+
+        let tile_ids = tile_ids_in_bounds(&view_bounds, 1, &tiling, true);
+        assert_eq!(
+            tile_ids,
+            vec![TileId { z: 1, x: 0, y: 0 }, TileId { z: 1, x: 1, y: 0 }]
+        );
+    }
+
     #[test]
     fn test_format_tile_url() {
         assert_eq!(
@@ -1,20 +1,33 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use dashmap::{DashMap, DashSet};
-use geo::Intersects;
+use dashmap::DashSet;
+use geo::{BoundingRect, Coord, Intersects, Rect};
 use moka::sync::Cache;
 use tokio::{sync::mpsc, task::JoinHandle};
 
 use crate::{
     env,
-    feature::style::ShapeStyles,
+    feature::style::{expression::EvaluationContext, ShapeStyles},
     layer::{
-        tiled::{format_tile_url, tile_ids_in_view},
-        Event, Layer, LayerType,
+        query::hit_test,
+        tiled::{
+            format_tile_url, other_views_tile_ids, publish_tile_failed, publish_tile_loaded,
+            tile_ids_in_view, tile_in_view, ViewTiles,
+        },
+        Event, FeatureQuery, Layer, LayerType, QueriedFeature,
+    },
+    map::{
+        context::{MapState, MapView},
+        Map, MapOptions,
     },
-    map::{context::MapState, Map, MapOptions},
     render::{
-        draw::vector_tile::VectorTileDrawable, tessellation::vector_tile::VectorTileTessellation,
+        draw::vector_tile::VectorTileDrawable,
+        tessellation::vector_tile::VectorTileTessellation,
+        text::{feature_labels, Label},
         InterRenderers, MapRenderer,
     },
     tiling::TileId,
@@ -33,9 +46,17 @@ pub struct VectorTiledLayer {
     tile_response_handle: Option<JoinHandle<()>>,
 
     requesting_tile_ids: Arc<DashSet<TileId>>,
+    failed_tile_ids: Arc<DashSet<TileId>>,
 
-    tiles_cache: Cache<TileId, VectorTileTessellation>,
-    tiles: Arc<DashMap<TileId, VectorTileTessellation>>,
+    tiles_cache: Cache<TileId, VectorTileData>,
+    /// Tiles of the live view and of exports in progress.
+    views: HashMap<MapView, ViewTiles<VectorTileData>>,
+}
+
+#[derive(Clone)]
+struct VectorTileData {
+    vector_tile: Arc<VectorTile>,
+    tessellation: VectorTileTessellation,
 }
 
 impl VectorTiledLayer {
@@ -53,11 +74,31 @@ impl VectorTiledLayer {
             tile_response_handle: None,
 
             requesting_tile_ids: Arc::new(DashSet::new()),
+            failed_tile_ids: Arc::new(DashSet::new()),
 
             tiles_cache: Cache::new(cache_size),
-            tiles: Arc::new(DashMap::new()),
+            views: HashMap::new(),
         }
     }
+
+    fn tile_labels(&self, vector_tile: &VectorTile, zoom: f64) -> Vec<Label> {
+        let mut labels = Vec::new();
+
+        for (layer_name, shape_styles) in &self.options.layers_shape_styles {
+            if shape_styles.label.is_none() {
+                continue;
+            }
+
+            if let Some(layer) = vector_tile.layers().get(layer_name) {
+                for feature in &layer.features {
+                    let feature = vector_tile.to_map_feature(feature);
+                    labels.extend(feature_labels(&feature, shape_styles, zoom));
+                }
+            }
+        }
+
+        labels
+    }
 }
 
 impl Layer for VectorTiledLayer {
@@ -65,6 +106,21 @@ impl Layer for VectorTiledLayer {
         LayerType::VectorTiledLayer
     }
 
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clear_view(&mut self, view: MapView, map_renderer: &mut MapRenderer) {
+        self.views.remove(&view);
+        map_renderer.clear_layer_draw_items(&view.layer_key(&self.name));
+    }
+
+    fn is_loaded(&self, view: MapView) -> bool {
+        self.views
+            .get(&view)
+            .is_some_and(|view_tiles| view_tiles.loaded)
+    }
+
     fn on_add_to_map(&mut self, map: &Map) {
         if self.event_sender.is_some() {
             return;
@@ -80,8 +136,10 @@ impl Layer for VectorTiledLayer {
             let tiling = map.options.tiling.clone();
             let layers_shape_styles = self.options.layers_shape_styles.clone();
 
+            let name = self.name.clone();
             let tiles_cache = self.tiles_cache.clone();
             let requesting_tile_ids = self.requesting_tile_ids.clone();
+            let failed_tile_ids = self.failed_tile_ids.clone();
             let event_sender = self.event_sender.clone();
 
             async move {
@@ -91,31 +149,57 @@ impl Layer for VectorTiledLayer {
                     if let Some(http_response) = http_response {
                         let tile_id = http_response.id.clone();
                         if let Some(tile_bbox) = tiling.get_tile_bbox(&tile_id) {
-                            if let Ok(bytes) = http_response.bytes().await {
-                                requesting_tile_ids.remove(&tile_id);
-
-                                env::spawn({
-                                    let layers_shape_styles = layers_shape_styles.clone();
-                                    let tiles_cache = tiles_cache.clone();
-                                    let event_sender = event_sender.clone();
-
-                                    async move {
-                                        if let Ok(vector_tile) =
-                                            VectorTile::from_data(bytes.to_vec(), tile_bbox)
-                                        {
-                                            let tile = VectorTileTessellation::new(
-                                                &vector_tile,
-                                                &layers_shape_styles,
-                                            );
-
-                                            tiles_cache.insert(tile_id.clone(), tile);
-
-                                            if let Some(event_sender) = &event_sender {
-                                                let _ = event_sender.send(Event::MapRequestRedraw);
+                            match http_response.bytes().await {
+                                Ok(bytes) => {
+                                    requesting_tile_ids.remove(&tile_id);
+
+                                    env::spawn({
+                                        let name = name.clone();
+                                        let layers_shape_styles = layers_shape_styles.clone();
+                                        let tiles_cache = tiles_cache.clone();
+                                        let failed_tile_ids = failed_tile_ids.clone();
+                                        let event_sender = event_sender.clone();
+
+                                        async move {
+                                            match VectorTile::from_data(bytes.to_vec(), tile_bbox) {
+                                                Ok(vector_tile) => {
+                                                    let tessellation = VectorTileTessellation::new(
+                                                        &vector_tile,
+                                                        &layers_shape_styles,
+                                                    );
+                                                    let tile = VectorTileData {
+                                                        vector_tile: Arc::new(vector_tile),
+                                                        tessellation,
+                                                    };
+
+                                                    tiles_cache.insert(tile_id.clone(), tile);
+
+                                                    publish_tile_loaded(
+                                                        &event_sender,
+                                                        &name,
+                                                        &tile_id,
+                                                    );
+                                                }
+                                                Err(err) => {
+                                                    failed_tile_ids.insert(tile_id.clone());
+
+                                                    publish_tile_failed(
+                                                        &event_sender,
+                                                        &name,
+                                                        &tile_id,
+                                                        &err,
+                                                    );
+                                                }
                                             }
                                         }
-                                    }
-                                });
+                                    });
+                                }
+                                Err(err) => {
+                                    failed_tile_ids.insert(tile_id.clone());
+                                    requesting_tile_ids.remove(&tile_id);
+
+                                    publish_tile_failed(&event_sender, &name, &tile_id, &err);
+                                }
                             }
                         }
                     }
@@ -140,6 +224,97 @@ impl Layer for VectorTiledLayer {
         self.tile_response_handle = None;
     }
 
+    fn query_features(&self, query: &FeatureQuery) -> Vec<QueriedFeature> {
+        let mut queried_features = Vec::new();
+
+        let query_rect = match query.geometry.bounding_rect() {
+            Some(rect) => rect,
+            None => return queried_features,
+        };
+        let search_rect = |size: f32| {
+            let margin = query.tolerance + size as f64 * query.pixel_size;
+            let margin = Coord {
+                x: margin,
+                y: margin,
+            };
+
+            Rect::new(query_rect.min() - margin, query_rect.max() + margin)
+        };
+
+        let Some(view_tiles) = self.views.get(&MapView::Live) else {
+            return queried_features;
+        };
+
+        // Parent and child tiles are kept while loading and overlap the drawn ones, the most
+        // detailed tile queries a feature first and its copies in other tiles are skipped
+        let mut tiles: Vec<_> = view_tiles.tiles.iter().collect();
+        tiles.sort_by_key(|pair| std::cmp::Reverse(pair.key().z));
+
+        let mut queried_ids = HashSet::new();
+
+        for pair in &tiles {
+            let vector_tile = &pair.value().vector_tile;
+
+            for (layer_name, shape_styles) in &self.options.layers_shape_styles {
+                // Sizes unbounded by the styles are resolved per feature
+                let max_size = shape_styles.max_size(query.zoom);
+                if max_size.is_some_and(|size| !search_rect(size).intersects(&vector_tile.bbox())) {
+                    continue;
+                }
+
+                if let Some(layer) = vector_tile.layers().get(layer_name) {
+                    for feature in &layer.features {
+                        let queried_id = (layer_name.as_str(), feature.id());
+                        if queried_ids.contains(&queried_id) {
+                            continue;
+                        }
+
+                        let resolve = || {
+                            shape_styles.resolve(
+                                &EvaluationContext::new(feature.attrs())
+                                    .with_geometry_type(feature.shape().geometry_type())
+                                    .with_zoom(query.zoom),
+                            )
+                        };
+
+                        let (size, resolved) = match max_size {
+                            Some(size) => (size, None),
+                            None => {
+                                let resolved = resolve();
+                                (
+                                    resolved.symbol_size.max(resolved.stroke_width),
+                                    Some(resolved),
+                                )
+                            }
+                        };
+                        let search_tile_rect = vector_tile.to_tile_rect(&search_rect(size));
+                        if !feature.shape().bbox().intersects(&search_tile_rect) {
+                            continue;
+                        }
+
+                        let shape_styles = resolved.unwrap_or_else(resolve);
+                        if !shape_styles.visible {
+                            continue;
+                        }
+
+                        let feature = vector_tile.to_map_feature(feature);
+
+                        if hit_test(feature.shape(), &shape_styles, query) {
+                            queried_ids.insert(queried_id);
+                            queried_features.push(QueriedFeature {
+                                layer: self.name.clone(),
+                                source_layer: Some(layer_name.clone()),
+                                feature,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        queried_features
+    }
+
     fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
@@ -151,16 +326,40 @@ impl Layer for VectorTiledLayer {
         map_renderer: &mut MapRenderer,
         inter_renderers: &mut InterRenderers,
     ) {
-        let tile_ids = tile_ids_in_view(map_state, &map_options.tiling);
-        let center_tile_id = map_options
-            .tiling
-            .get_tile_id(map_state.zoom, &map_state.center);
+        let mut tile_ids = tile_ids_in_view(map_state, &map_options.tiling, map_options.world_copy);
+        let mut tile_zoom = map_state.zoom;
+
+        // Overzoom tiles of the max zoom level beyond it
+        if let Some(zoom_max) = self.options.zoom_max {
+            if tile_zoom > zoom_max {
+                let level = (tile_zoom - zoom_max) as u32;
+                tile_zoom = zoom_max;
+
+                let mut overzoom_tile_ids: Vec<TileId> = Vec::new();
+                for tile_id in &tile_ids {
+                    if let Some(up_tile_id) = map_options.tiling.roll_up_tile_id(tile_id, level) {
+                        if !overzoom_tile_ids.contains(&up_tile_id) {
+                            overzoom_tile_ids.push(up_tile_id);
+                        }
+                    }
+                }
+                tile_ids = overzoom_tile_ids;
+            }
+        }
+
+        let center_tile_id = map_options.tiling.get_tile_id(tile_zoom, &map_state.center);
+
+        // Each view keeps its own tiles, tiles needed by other views are still requested
+        let other_tile_ids = other_views_tile_ids(&self.views, map_state.view);
+        let layer_key = map_state.view.layer_key(&self.name);
+        let mut view_tiles = self.views.remove(&map_state.view).unwrap_or_default();
+        view_tiles.tile_ids = tile_ids.clone();
 
         // Cancel tile requestes that are no longer needed
         {
             let mut cancel_tile_ids: Vec<TileId> = Vec::new();
             for tile_id in self.requesting_tile_ids.iter() {
-                if !tile_ids.contains(&tile_id) {
+                if !tile_ids.contains(&tile_id) && !other_tile_ids.contains(&tile_id) {
                     cancel_tile_ids.push(tile_id.clone());
                 }
             }
@@ -172,22 +371,33 @@ impl Layer for VectorTiledLayer {
                     tile_fetcher.cancel(&tile_id);
                 }
             });
+
+            // Failed tiles are retried once they come into view again
+            self.failed_tile_ids
+                .retain(|tile_id| tile_ids.contains(tile_id) || other_tile_ids.contains(tile_id));
         }
 
         // Load tiles from cache if possible
         tile_ids.iter().for_each(|tile_id| {
-            if !self.tiles.contains_key(tile_id) {
+            if !view_tiles.tiles.contains_key(tile_id) {
                 if let Some(tile) = self.tiles_cache.get(tile_id) {
-                    self.tiles.insert(tile_id.clone(), tile.clone());
+                    view_tiles.tiles.insert(tile_id.clone(), tile.clone());
                 }
             }
         });
 
+        let tile_ids_loaded = tile_ids.iter().all(|tile_id| {
+            view_tiles.tiles.contains_key(tile_id) || self.failed_tile_ids.contains(tile_id)
+        });
+
         // Load tiles from server
         {
             let mut load_tile_ids = tile_ids
                 .iter()
-                .filter(|tile_id| !self.tiles.contains_key(&tile_id))
+                .filter(|tile_id| {
+                    !view_tiles.tiles.contains_key(tile_id)
+                        && !self.failed_tile_ids.contains(tile_id)
+                })
                 .collect::<Vec<_>>();
 
             load_tile_ids.sort_by_key(|tile_id| {
@@ -228,23 +438,26 @@ impl Layer for VectorTiledLayer {
         {
             let mut dirty_tiles: HashSet<TileId> = HashSet::new();
 
-            for pair in self.tiles.iter() {
+            for pair in view_tiles.tiles.iter() {
                 let tile_id = pair.key();
 
                 if !tile_ids.contains(tile_id) {
                     dirty_tiles.insert(tile_id.clone());
                 }
 
-                if let Some(bbox) = map_options.tiling.get_tile_bbox(&tile_id) {
-                    if !bbox.to_polygon().intersects(map_state.view_bounds()) {
-                        dirty_tiles.insert(tile_id.clone());
-                    }
+                if !tile_in_view(
+                    map_state,
+                    &map_options.tiling,
+                    tile_id,
+                    map_options.world_copy,
+                ) {
+                    dirty_tiles.insert(tile_id.clone());
                 }
             }
 
             // Keep resample tiles if possible
             'tiles: for tile_id in tile_ids {
-                if !self.tiles.contains_key(&tile_id) {
+                if !view_tiles.tiles.contains_key(&tile_id) {
                     for level in 1..=self.options.max_up_scale_level {
                         if let Some(parent_tile_id) =
                             map_options.tiling.roll_up_tile_id(&tile_id, level)
@@ -254,7 +467,7 @@ impl Layer for VectorTiledLayer {
                                     if let Some(up_tile_id) =
                                         map_options.tiling.roll_up_tile_id(&tile_id, up_level)
                                     {
-                                        if self.tiles.contains_key(&up_tile_id)
+                                        if view_tiles.tiles.contains_key(&up_tile_id)
                                             && !dirty_tiles.contains(&up_tile_id)
                                         {
                                             continue 'tiles;
@@ -262,7 +475,7 @@ impl Layer for VectorTiledLayer {
                                     }
                                 }
 
-                                self.tiles.insert(parent_tile_id.clone(), parent_tile);
+                                view_tiles.tiles.insert(parent_tile_id.clone(), parent_tile);
                                 dirty_tiles.remove(&parent_tile_id);
 
                                 for cover_level in 1..=level {
@@ -280,7 +493,7 @@ impl Layer for VectorTiledLayer {
                     let child_tile_ids = map_options.tiling.drill_down_tile_ids(&tile_id, 1);
                     for child_tile_id in child_tile_ids {
                         if let Some(child_tile) = self.tiles_cache.get(&child_tile_id) {
-                            self.tiles.insert(child_tile_id.clone(), child_tile);
+                            view_tiles.tiles.insert(child_tile_id.clone(), child_tile);
                             dirty_tiles.remove(&child_tile_id);
                         }
                     }
@@ -288,29 +501,36 @@ impl Layer for VectorTiledLayer {
             }
 
             for tile_id in dirty_tiles {
-                self.tiles.remove(&tile_id);
+                view_tiles.tiles.remove(&tile_id);
 
-                map_renderer.remove_layer_draw_item(&self.name, &tile_id);
+                map_renderer.remove_layer_draw_item(&layer_key, &tile_id);
             }
         }
 
-        for pair in self.tiles.iter() {
+        let zoom = map_options.tiling.zoom_for_resolution(map_state.zoom_res);
+        for pair in view_tiles.tiles.iter() {
             let tile_id = pair.key();
             let tile = pair.value();
 
-            if !map_renderer.contains_layer_draw_item(&self.name, tile_id) {
+            if !map_renderer.contains_layer_draw_item(&layer_key, tile_id) {
                 let drawable = VectorTileDrawable::new(
                     tile_id,
-                    &tile,
+                    &tile.tessellation,
                     self.options.z,
                     &self.options.layers_shape_styles,
                     &map_renderer,
                     &inter_renderers,
                 );
 
-                map_renderer.add_layer_draw_item(&self.name, tile_id, drawable.into());
+                map_renderer.add_layer_draw_item(&layer_key, tile_id, drawable.into());
+
+                let labels = self.tile_labels(&tile.vector_tile, zoom);
+                map_renderer.add_layer_labels(&layer_key, tile_id, labels);
             }
         }
+
+        view_tiles.loaded = tile_ids_loaded;
+        self.views.insert(map_state.view, view_tiles);
     }
 }
 
@@ -322,6 +542,7 @@ pub struct VectorTiledLayerOptions {
     max_up_scale_level: u32,
     url_subdomains: Option<Vec<String>>,
     z: f64,
+    zoom_max: Option<usize>,
 }
 
 impl VectorTiledLayerOptions {
@@ -343,6 +564,8 @@ impl VectorTiledLayerOptions {
         self
     }
 
+    /// Styles of source layers in drawing order, a source layer may be styled by several
+    /// entries, e.g. with different filters.
     pub fn with_layers_shape_styles(mut self, v: &Vec<(impl ToString, ShapeStyles)>) -> Self {
         self.layers_shape_styles = v.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
         self
@@ -362,6 +585,12 @@ impl VectorTiledLayerOptions {
         self.z = v;
         self
     }
+
+    /// Max zoom level of the tiles, tiles of this level are scaled up beyond it.
+    pub fn with_zoom_max(mut self, v: usize) -> Self {
+        self.zoom_max = Some(v);
+        self
+    }
 }
 
 impl Default for VectorTiledLayerOptions {
@@ -374,6 +603,87 @@ impl Default for VectorTiledLayerOptions {
             max_up_scale_level: 5,
             url_subdomains: None,
             z: 0.0,
+            zoom_max: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::Point;
+
+    use super::*;
+    use crate::feature::style::expression::Expression;
+
+    fn test_layer() -> VectorTiledLayer {
+        let mut geolines_styles = ShapeStyles {
+            fill_enabled: false,
+            stroke_width: 1.0,
+            ..Default::default()
+        };
+        geolines_styles.expressions.stroke_width =
+            Some(Expression::zoom_step(1.0, vec![(0.0, 20.0)]));
+
+        let mut layer = VectorTiledLayer::new(
+            "",
+            VectorTiledLayerOptions::default().with_layers_shape_styles(&vec![
+                ("countries", ShapeStyles::default()),
+                ("geolines", geolines_styles),
+            ]),
+        );
+
+        let half = 20037508.34278924;
+        let vector_tile = VectorTile::from_data(
+            std::fs::read("../assets/mvt.pbf").unwrap(),
+            Rect::new(Coord { x: -half, y: -half }, Coord { x: half, y: half }),
+        )
+        .unwrap();
+        let tile = VectorTileData {
+            tessellation: VectorTileTessellation::new(
+                &vector_tile,
+                &layer.options.layers_shape_styles,
+            ),
+            vector_tile: Arc::new(vector_tile),
+        };
+
+        // The same features in a parent and a child tile, as while the child is loading
+        let view_tiles = ViewTiles::default();
+        view_tiles
+            .tiles
+            .insert(TileId { z: 0, x: 0, y: 0 }, tile.clone());
+        view_tiles.tiles.insert(TileId { z: 1, x: 0, y: 0 }, tile);
+        layer.views.insert(MapView::Live, view_tiles);
+
+        layer
+    }
+
+    fn query(layer: &VectorTiledLayer, x: f64, y: f64) -> Vec<QueriedFeature> {
+        layer.query_features(&FeatureQuery {
+            geometry: Point::new(x, y).into(),
+            tolerance: 0.0,
+            pixel_size: 1e4,
+            zoom: 2.0,
+        })
+    }
+
+    #[test]
+    fn test_query_features() {
+        let layer = test_layer();
+
+        // Paris, queried once across the tiles
+        let queried_features = query(&layer, 261e3, 6250e3);
+        assert_eq!(queried_features.len(), 1);
+        assert_eq!(
+            queried_features[0].source_layer.as_deref(),
+            Some("countries")
+        );
+
+        // 8 pixels off the equator in the Pacific, within the 20 pixel width of the expression
+        let queried_features = query(&layer, -15e6, 8e4);
+        assert_eq!(queried_features.len(), 1);
+        assert_eq!(
+            queried_features[0].source_layer.as_deref(),
+            Some("geolines")
+        );
+    }
+}
@@ -16,7 +16,9 @@ use std::fmt::Debug;
 
 use crate::render::targets::Window;
 
+pub mod event;
 pub mod feature;
+pub mod gl_style;
 pub mod layer;
 pub mod map;
 pub mod tiling;
@@ -24,11 +26,16 @@ pub mod utils;
 pub mod vector_tile;
 
 pub(crate) mod env;
-pub(crate) mod event;
 pub(crate) mod render;
 
 pub enum Canvas {
     Window(Window),
+    /// Render into an offscreen texture instead of a window surface, e.g. for servers and tests.
+    Offscreen {
+        width: u32,
+        height: u32,
+        pixel_ratio: f64,
+    },
 }
 
 pub trait CoordType: Debug + num_traits::Float {
@@ -1,5 +1,6 @@
 use std::{
     error::Error,
+    io::Cursor,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc, Mutex,
@@ -7,21 +8,37 @@ use std::{
     time::{Duration, Instant},
 };
 
-use geo::Coord;
-use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
+use ab_glyph::FontArc;
+use geo::{Coord, LineString, Point, Polygon, Rect};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::sleep,
+};
 
 use crate::{
     env,
-    event::Event,
-    layer::Layer,
+    event::{Event, MapEvent},
+    layer::{Layer, QueriedFeature},
     map::context::MapContext,
-    render::{InterRenderers, MapRenderer, MapRendererOptions, VectorTileRenderer},
+    render::{sprite::Icon, InterRenderers, MapRenderer, MapRendererOptions, VectorTileRenderer},
     tiling::Tiling,
     utils::color::Color,
     Canvas,
 };
 
 pub(crate) mod context;
+pub mod interaction;
+
+/// Maximum time to wait for layers to load when exporting an image.
+pub const EXPORT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of events kept for each subscriber before the oldest ones are dropped.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Idle time after the last view change before a [`MapEvent::MoveEnd`] is published.
+pub const MOVE_END_DELAY: Duration = Duration::from_millis(200);
 
 pub struct Map {
     pub options: MapOptions,
@@ -29,6 +46,7 @@ pub struct Map {
     pub(crate) context: Arc<Mutex<MapContext>>,
     pub(crate) event_sender: mpsc::UnboundedSender<Event>,
 
+    event_publisher: broadcast::Sender<MapEvent>,
     event_handle: JoinHandle<()>,
 
     anim_handle: Option<JoinHandle<()>>,
@@ -66,9 +84,11 @@ impl Map {
         let redraw_seq = Arc::new(AtomicU64::new(0));
 
         let (event_sender, mut event_receiver) = mpsc::unbounded_channel::<Event>();
+        let (event_publisher, _) = broadcast::channel::<MapEvent>(EVENT_CAPACITY);
 
         let event_handle = env::spawn({
             let redraw_seq = redraw_seq.clone();
+            let event_publisher = event_publisher.clone();
 
             async move {
                 loop {
@@ -81,6 +101,9 @@ impl Map {
                             Event::MapRequestRedraw => {
                                 redraw_seq.fetch_add(1, Ordering::SeqCst);
                             }
+                            Event::Publish(map_event) => {
+                                let _ = event_publisher.send(map_event);
+                            }
                         }
                     }
                 }
@@ -94,25 +117,65 @@ impl Map {
             let context = context.clone();
             let context_redraw_seq = context_redraw_seq.clone();
             let redraw_seq = redraw_seq.clone();
+            let event_publisher = event_publisher.clone();
 
             async move {
+                let mut published_view_seq = 0;
+                let mut moving = false;
+                let mut last_view_changed = Instant::now();
+
                 loop {
                     if redraw_seq.load(Ordering::SeqCst)
                         == context_redraw_seq.load(Ordering::SeqCst)
                     {
+                        if moving && last_view_changed.elapsed() >= MOVE_END_DELAY {
+                            moving = false;
+                            let _ = event_publisher.send(MapEvent::MoveEnd);
+                        }
+
                         sleep(Duration::from_millis(frame_interval)).await;
                     } else {
-                        context_redraw_seq
-                            .store(redraw_seq.load(Ordering::SeqCst), Ordering::SeqCst);
-
                         let now = Instant::now();
 
+                        let mut map_events = Vec::new();
+
+                        // Skip the frame rather than blocking the runtime while the context is
+                        // locked, it is redrawn on the next loop
                         {
-                            if let Ok(mut context) = context.lock() {
+                            if let Ok(mut context) = context.try_lock() {
+                                context_redraw_seq
+                                    .store(redraw_seq.load(Ordering::SeqCst), Ordering::SeqCst);
+
                                 context.redraw();
+
+                                let map_state = &context.map_state;
+                                if map_state.view_seq() != published_view_seq {
+                                    published_view_seq = map_state.view_seq();
+                                    last_view_changed = Instant::now();
+
+                                    if !moving {
+                                        moving = true;
+                                        map_events.push(MapEvent::MoveStart);
+                                    }
+
+                                    map_events.push(MapEvent::ViewChanged {
+                                        center: map_state.center,
+                                        zoom_res: map_state.zoom_res,
+                                        pitch: map_state.pitch,
+                                        yaw: map_state.yaw,
+                                    });
+                                }
+
+                                map_events.push(MapEvent::FrameRendered {
+                                    elapsed: now.elapsed(),
+                                });
                             }
                         }
 
+                        for map_event in map_events {
+                            let _ = event_publisher.send(map_event);
+                        }
+
                         let elapsed = now.elapsed().as_millis() as u64;
                         if frame_interval > elapsed {
                             sleep(Duration::from_millis(frame_interval - elapsed)).await;
@@ -128,11 +191,50 @@ impl Map {
             context,
             event_sender,
 
+            event_publisher,
             event_handle,
             anim_handle: None,
         }
     }
 
+    /// Add a TrueType or OpenType font for labels, referenced by name in
+    /// [`crate::feature::style::LabelStyle::font`]. Fonts should be added before the layers using
+    /// them.
+    pub fn add_font(&mut self, name: &str, data: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        let font = FontArc::try_from_vec(data)?;
+
+        {
+            if let Ok(mut context) = self.context.lock() {
+                context.map_renderer.add_font(name, font);
+            }
+        }
+
+        self.request_redraw();
+
+        Ok(())
+    }
+
+    /// Add an icon for icon styles from a raster image, e.g. PNG, or an SVG image. The pixel ratio
+    /// is the number of image pixels per logical pixel, SVG images are rasterized at it.
+    pub fn add_icon(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        pixel_ratio: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let icon = Icon::from_bytes(data, pixel_ratio as f32)?;
+
+        {
+            if let Ok(mut context) = self.context.lock() {
+                context.map_renderer.add_icon(name, icon);
+            }
+        }
+
+        self.request_redraw();
+
+        Ok(())
+    }
+
     pub fn add_layer(
         &mut self,
         name: &str,
@@ -154,11 +256,27 @@ impl Map {
             }
         }
 
+        self.publish_event(MapEvent::LayerAdded {
+            name: name.to_string(),
+        });
         self.request_redraw();
 
         Ok(())
     }
 
+    /// View change showing the rect inside the padded screen, without changing the view.
+    pub fn camera_for_bounds(
+        &self,
+        rect: &Rect,
+        padding: &Padding,
+        max_zoom: Option<usize>,
+    ) -> Option<MapViewChange> {
+        self.context
+            .lock()
+            .ok()?
+            .camera_for_bounds(rect, padding, max_zoom)
+    }
+
     pub fn center(&self) -> Option<Coord> {
         Some(self.context.lock().ok()?.map_state.center.clone())
     }
@@ -168,6 +286,81 @@ impl Map {
         self.fly_to(map_view_change, duration, 0);
     }
 
+    /// Render the current view at the given size and pixel ratio, and encode it in the given format.
+    ///
+    /// Waits until all layers are loaded for the export view, at most for [`EXPORT_TIMEOUT`]. The
+    /// map keeps rendering meanwhile, the export is rendered offscreen on the same device.
+    pub async fn export_image(
+        &self,
+        width: u32,
+        height: u32,
+        pixel_ratio: f64,
+        format: ImageFormat,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        if width == 0 || height == 0 || pixel_ratio <= 0.0 {
+            return Err("Invalid export image size or pixel ratio".into());
+        }
+
+        // The context is locked only for each update, so tiles keep loading in between
+        let image = env::spawn({
+            let context = self.context.clone();
+
+            async move {
+                let view = context.lock().ok()?.begin_export();
+
+                let instant = Instant::now();
+                loop {
+                    {
+                        let mut context = context.lock().ok()?;
+
+                        let loaded = context.update_export(view, width, height);
+                        if loaded || instant.elapsed() >= EXPORT_TIMEOUT {
+                            if !loaded {
+                                log::warn!(
+                                    "Export image timed out, some layers are not fully loaded"
+                                );
+                            }
+
+                            return context.render_export(view, width, height, pixel_ratio);
+                        }
+                    }
+
+                    sleep(Duration::from_millis(20)).await;
+                }
+            }
+        })
+        .await?
+        .ok_or("Failed to render export image")?;
+
+        let image = match format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+            _ => DynamicImage::ImageRgba8(image),
+        };
+
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format)?;
+
+        self.request_redraw();
+
+        Ok(bytes)
+    }
+
+    /// Show the rect inside the padded screen, with an animated transition if a duration is given.
+    pub fn fit_bounds(
+        &mut self,
+        rect: &Rect,
+        padding: &Padding,
+        max_zoom: Option<usize>,
+        animation: Option<Duration>,
+    ) {
+        if let Some(map_view_change) = self.camera_for_bounds(rect, padding, max_zoom) {
+            match animation {
+                Some(duration) => self.ease_to(&map_view_change, duration),
+                None => self.jump_to(&map_view_change),
+            }
+        }
+    }
+
     /// Fly up and down to the given view, with an animated transition.
     pub fn fly_to(
         &mut self,
@@ -194,9 +387,21 @@ impl Map {
             let from_pitch = self.pitch();
             let from_yaw = self.yaw();
 
-            let to_center = map_view_change.center.clone();
-            let to_zoom_res = map_view_change.zoom_res;
-            let to_pitch = map_view_change.pitch;
+            // Take the shorter way around the world
+            let to_center = match (&from_center, map_view_change.center) {
+                (Some(from_center), Some(mut to_center)) if self.options.world_copy => {
+                    to_center.x = self
+                        .options
+                        .tiling
+                        .nearest_world_x(to_center.x, from_center.x);
+                    Some(to_center)
+                }
+                (_, to_center) => to_center,
+            };
+            let to_zoom_res = map_view_change.resolve_zoom_res(&self.options.tiling);
+            let to_pitch = map_view_change
+                .pitch
+                .map(|pitch| pitch.clamp(0.0, self.options.pitch_max));
             let to_yaw = map_view_change.yaw;
 
             let zoom_up = 2.0_f64.powi(zoom_factor as i32) - 1.0;
@@ -219,12 +424,6 @@ impl Map {
 
                     {
                         if let Ok(mut context) = context.lock() {
-                            if let (Some(from_center), Some(to_center)) = (from_center, to_center) {
-                                let center = from_center * (1.0 - x) + to_center * x;
-
-                                context.set_center(center);
-                            }
-
                             {
                                 let to_zoom_res = to_zoom_res.unwrap_or(from_zoom_res);
                                 let zoom_res = (from_zoom_res * (1.0 - x) + to_zoom_res * x)
@@ -242,6 +441,12 @@ impl Map {
 
                                 context.set_pitch_yaw(pitch, yaw);
                             }
+
+                            if let (Some(from_center), Some(to_center)) = (from_center, to_center) {
+                                let center = from_center * (1.0 - x) + to_center * x;
+
+                                context.set_center(center);
+                            }
                         }
                     }
 
@@ -252,10 +457,6 @@ impl Map {
 
                 {
                     if let Ok(mut context) = context.lock() {
-                        if let Some(center) = to_center {
-                            context.set_center(center);
-                        }
-
                         if let Some(zoom_res) = to_zoom_res {
                             context.set_zoom_res(zoom_res, true);
                         }
@@ -265,6 +466,10 @@ impl Map {
                             let yaw = to_yaw.unwrap_or(context.map_state.yaw);
                             context.set_pitch_yaw(pitch, yaw);
                         }
+
+                        if let Some(center) = to_center {
+                            context.set_center(center);
+                        }
                     }
                 }
 
@@ -273,10 +478,16 @@ impl Map {
                 }
 
                 let _ = event_sender.send(Event::MapRequestRedraw);
+                let _ = event_sender.send(Event::Publish(MapEvent::AnimationFinished));
             }
         }));
     }
 
+    /// Numbers of GPU resources created while rendering the last frame.
+    pub fn frame_allocations(&self) -> Option<FrameAllocations> {
+        Some(self.context.lock().ok()?.map_renderer.frame_allocations())
+    }
+
     pub fn height(&self) -> Option<u32> {
         Some(self.context.lock().ok()?.map_renderer.height())
     }
@@ -287,11 +498,9 @@ impl Map {
 
         {
             if let Ok(mut context) = self.context.lock() {
-                if let Some(center) = map_view_change.center {
-                    context.set_center(center);
-                }
-
-                if let Some(zoom_res) = map_view_change.zoom_res {
+                if let Some(zoom_res) =
+                    map_view_change.resolve_zoom_res(&context.map_options.tiling)
+                {
                     context.set_zoom_res(zoom_res, true);
                 }
 
@@ -300,10 +509,16 @@ impl Map {
                     let yaw = map_view_change.yaw.unwrap_or(context.map_state.yaw);
                     context.set_pitch_yaw(pitch, yaw);
                 }
+
+                // Center last, the max bounds constrain it by zoom and yaw
+                if let Some(center) = map_view_change.center {
+                    context.set_center(center);
+                }
             }
         }
 
         self.request_redraw();
+        self.publish_event(MapEvent::AnimationFinished);
     }
 
     pub fn options(&self) -> &MapOptions {
@@ -318,25 +533,101 @@ impl Map {
             .unwrap_or(0.0)
     }
 
+    /// Query features rendered at a screen coordinate, top layers first.
+    ///
+    /// Symbol sizes and stroke widths are taken into account, `tolerance_px` extends the hit area.
+    pub fn query_rendered_features(
+        &self,
+        screen_coord: &Coord,
+        tolerance_px: f64,
+        layer_filter: Option<&[&str]>,
+    ) -> Vec<QueriedFeature> {
+        if let Ok(context) = self.context.lock() {
+            if let Some(map_coord) = context.to_map(screen_coord) {
+                return context.query_features(
+                    Point::from(map_coord).into(),
+                    tolerance_px,
+                    layer_filter,
+                );
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Query features rendered in a screen box, top layers first.
+    pub fn query_rendered_features_in_box(
+        &self,
+        screen_min: &Coord,
+        screen_max: &Coord,
+        layer_filter: Option<&[&str]>,
+    ) -> Vec<QueriedFeature> {
+        if let Ok(context) = self.context.lock() {
+            let corners = [
+                Coord {
+                    x: screen_min.x,
+                    y: screen_min.y,
+                },
+                Coord {
+                    x: screen_max.x,
+                    y: screen_min.y,
+                },
+                Coord {
+                    x: screen_max.x,
+                    y: screen_max.y,
+                },
+                Coord {
+                    x: screen_min.x,
+                    y: screen_max.y,
+                },
+            ];
+
+            // Unwrapped to keep the box continuous across the antimeridian
+            if let Some(map_coords) = corners
+                .iter()
+                .map(|corner| context.to_map_unwrapped(corner))
+                .collect::<Option<Vec<_>>>()
+            {
+                let polygon = Polygon::new(LineString::from(map_coords), vec![]);
+                return context.query_features(polygon.into(), 0.0, layer_filter);
+            }
+        }
+
+        Vec::new()
+    }
+
     pub fn redraw(&mut self) {
         self.request_redraw();
     }
 
     pub fn remove_layer(&mut self, name: &str) {
+        let mut removed = false;
+
         {
             if let Ok(mut context) = self.context.lock() {
                 if let Some(layer) = context.layers.get_mut(name) {
                     (*layer).on_remove_from_map(self);
                 }
 
-                context.layers.remove(name);
+                removed = context.layers.remove(name).is_some();
                 context.map_state.layers_order.retain(|x| *x != name);
+                context.map_renderer.clear_layer_draw_items(name);
             }
         }
 
+        if removed {
+            self.publish_event(MapEvent::LayerRemoved {
+                name: name.to_string(),
+            });
+        }
         self.request_redraw();
     }
 
+    /// Render the current view synchronously and read the frame back as an image.
+    pub fn render_to_image(&self) -> Option<RgbaImage> {
+        self.context.lock().ok()?.render_to_image()
+    }
+
     pub fn resolution(&self) -> Option<f64> {
         let zoom_res = self.context.lock().ok()?.map_state.zoom_res;
         let map_res_ratio = self.context.lock().ok()?.map_state.map_res_ratio;
@@ -354,6 +645,19 @@ impl Map {
         self.request_redraw();
     }
 
+    pub fn set_background_color(&mut self, color: Color) {
+        self.options.background_color = color.clone();
+
+        {
+            if let Ok(mut context) = self.context.lock() {
+                context.map_options.background_color = color.clone();
+                context.map_renderer.set_background_color(color.into());
+            }
+        }
+
+        self.request_redraw();
+    }
+
     pub fn set_center(&mut self, center: Coord) {
         self.cancel_anim();
 
@@ -371,13 +675,32 @@ impl Map {
 
         {
             if let Ok(mut context) = self.context.lock() {
-                context.set_pitch_yaw(pitch.clamp(0.0, self.options.pitch_max), yaw);
+                context.set_pitch_yaw(pitch, yaw);
             }
         }
 
         self.request_redraw();
     }
 
+    /// Set the fractional zoom level, e.g. 12.5.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.cancel_anim();
+
+        {
+            if let Ok(mut context) = self.context.lock() {
+                let zoom_res = context.map_options.tiling.resolution_for_zoom(zoom);
+                context.set_zoom_res(zoom_res, true);
+            }
+        }
+
+        self.request_redraw();
+    }
+
+    /// Subscribe to map events, each subscriber receives all events published after subscribing.
+    pub fn subscribe(&self) -> broadcast::Receiver<MapEvent> {
+        self.event_publisher.subscribe()
+    }
+
     pub fn to_map(&self, screen_coord: &Coord) -> Option<Coord> {
         self.context.lock().ok()?.to_map(screen_coord)
     }
@@ -390,6 +713,22 @@ impl Map {
         Some(self.context.lock().ok()?.map_renderer.width())
     }
 
+    /// Access a layer as its concrete type, e.g. to update features of a `FeatureLayer`.
+    pub fn with_layer_mut<L: Layer + 'static, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut L) -> R,
+    ) -> Option<R> {
+        let mut context = self.context.lock().ok()?;
+        let layer = context
+            .layers
+            .get_mut(name)?
+            .as_any_mut()
+            .downcast_mut::<L>()?;
+
+        Some(f(layer))
+    }
+
     pub fn yaw(&self) -> f64 {
         self.context
             .lock()
@@ -398,6 +737,20 @@ impl Map {
             .unwrap_or(0.0)
     }
 
+    /// Fractional zoom level of the current view, e.g. 12.5.
+    pub fn zoom(&self) -> f64 {
+        self.context
+            .lock()
+            .ok()
+            .map(|context| {
+                context
+                    .map_options
+                    .tiling
+                    .zoom_for_resolution(context.map_state.zoom_res)
+            })
+            .unwrap_or(0.0)
+    }
+
     pub fn zoom_around(&mut self, coord: &Coord, scalar: f64) {
         self.cancel_anim();
 
@@ -432,6 +785,10 @@ impl Map {
         self.anim_handle = None;
     }
 
+    fn publish_event(&self, map_event: MapEvent) {
+        let _ = self.event_sender.send(Event::Publish(map_event));
+    }
+
     fn request_redraw(&self) {
         let _ = self.event_sender.send(Event::MapRequestRedraw);
     }
@@ -441,6 +798,7 @@ impl Map {
 pub struct MapOptions {
     pub background_color: Color,
     pub center: Coord,
+    pub max_bounds: Option<Rect>,
     pub max_frame_rate: usize,
     pub pitch: f64, // degree
     pub pitch_max: f64,
@@ -457,6 +815,7 @@ impl Default for MapOptions {
         Self {
             background_color: Color::from_rgba(0, 0, 0, 0.0),
             center: Coord { x: 0.0, y: 0.0 },
+            max_bounds: None,
             max_frame_rate: 60,
             pitch: 0.0,
             pitch_max: 80.0,
@@ -481,6 +840,12 @@ impl MapOptions {
         self
     }
 
+    /// Keep the view inside the bounds, constraining center and zoom.
+    pub fn with_max_bounds(mut self, v: Rect) -> Self {
+        self.max_bounds = Some(v);
+        self
+    }
+
     pub fn with_pitch(mut self, v: f64) -> Self {
         self.pitch = v;
         self
@@ -520,6 +885,8 @@ impl MapOptions {
 #[derive(Default)]
 pub struct MapViewChange {
     pub center: Option<Coord>,
+    /// Fractional zoom level, used when `zoom_res` is not set.
+    pub zoom: Option<f64>,
     pub zoom_res: Option<f64>,
     pub pitch: Option<f64>,
     pub yaw: Option<f64>,
@@ -531,6 +898,11 @@ impl MapViewChange {
         self
     }
 
+    pub fn with_zoom(mut self, v: f64) -> Self {
+        self.zoom = Some(v);
+        self
+    }
+
     pub fn with_zoom_res(mut self, v: f64) -> Self {
         self.zoom_res = Some(v);
         self
@@ -545,4 +917,1124 @@ impl MapViewChange {
         self.yaw = Some(v);
         self
     }
+
+    /// Zoom resolution of the change, converting the zoom level through the tiling if needed.
+    pub fn resolve_zoom_res(&self, tiling: &Tiling) -> Option<f64> {
+        self.zoom_res
+            .or_else(|| self.zoom.map(|zoom| tiling.resolution_for_zoom(zoom)))
+    }
+}
+
+/// Screen padding in pixels, e.g. for overlays on the map.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Padding {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Padding {
+    pub fn uniform(v: f64) -> Self {
+        Self {
+            top: v,
+            right: v,
+            bottom: v,
+            left: v,
+        }
+    }
+}
+
+/// Numbers of GPU resources created while rendering a frame. Buffers and bind groups are reused
+/// across frames, so they are only created when draw items are added or their styles first apply.
+///
+/// Counted are uniform and text vertex buffers, and all bind groups. Textures and the vertex,
+/// index and storage buffers of draw items are created on layer updates between frames, they are
+/// not counted, nor are image readback buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameAllocations {
+    pub buffers: usize,
+    pub bind_groups: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use geo::{line_string, polygon, BoundingRect};
+
+    use super::*;
+    use crate::{
+        feature::{style::ShapeStyles, Feature, Shape},
+        layer::{
+            feature_layer::{FeatureLayer, FeatureLayerOptions},
+            image_layer::{ImageLayer, ImageLayerOptions},
+            vector_tiled_layer::{VectorTiledLayer, VectorTiledLayerOptions},
+        },
+        map::context::MapView,
+        tiling::TileId,
+    };
+
+    const GOLDEN_TOLERANCE: u8 = 8;
+
+    /// Serve the bodies returned for request paths over HTTP on a local port, returns the base
+    /// URL. Requests without a body get a 404.
+    pub(super) fn serve_http(handler: impl Fn(&str) -> Option<Vec<u8>> + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 4096];
+                let Ok(len) = stream.read(&mut request) else {
+                    continue;
+                };
+
+                let request = String::from_utf8_lossy(&request[..len]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match handler(path) {
+                    Some(body) => ("200 OK", body),
+                    None => ("404 Not Found", Vec::new()),
+                };
+
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        body.len()
+                    )
+                    .as_bytes(),
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+
+        url
+    }
+
+    /// Compare an image with the golden image `assets/golden/<name>.png`. Channels may differ by
+    /// [`GOLDEN_TOLERANCE`] for antialiasing of other adapters, in at most 1% of the pixels.
+    /// Setting `MAPSDK_UPDATE_GOLDEN` writes the image as the golden image instead.
+    fn assert_golden(name: &str, image: &RgbaImage) {
+        let path = format!("../assets/golden/{}.png", name);
+        if std::env::var_os("MAPSDK_UPDATE_GOLDEN").is_some() {
+            image.save(&path).unwrap();
+            return;
+        }
+
+        let golden = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), golden.dimensions(), "{}", path);
+
+        let differing = image
+            .pixels()
+            .zip(golden.pixels())
+            .filter(|(pixel, golden_pixel)| {
+                pixel
+                    .0
+                    .iter()
+                    .zip(golden_pixel.0)
+                    .any(|(v, golden_v)| v.abs_diff(golden_v) > GOLDEN_TOLERANCE)
+            })
+            .count();
+        assert!(
+            differing * 100 <= golden.pixels().len(),
+            "{} pixels differ from {}",
+            differing,
+            path
+        );
+    }
+
+    /// Export the map view at 128x128 and decode it, waiting for layers to load.
+    fn export_rgba(map: &Map) -> RgbaImage {
+        let bytes = pollster::block_on(map.export_image(128, 128, 1.0, ImageFormat::Png)).unwrap();
+        image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+            .unwrap()
+            .to_rgba8()
+    }
+
+    /// Offscreen 64x64 map for GPU tests.
+    ///
+    /// Panics without a wgpu adapter, so GPU tests never pass silently. Setting
+    /// `MAPSDK_SKIP_GPU_TESTS` skips them explicitly, then `None` is returned.
+    pub(super) fn test_map(options: &MapOptions) -> Option<Map> {
+        if std::env::var_os("MAPSDK_SKIP_GPU_TESTS").is_some() {
+            log::warn!("MAPSDK_SKIP_GPU_TESTS is set, skip GPU test");
+            return None;
+        }
+
+        let instance = wgpu::Instance::default();
+        let adapter_available = [false, true].iter().any(|force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                force_fallback_adapter: *force_fallback_adapter,
+                ..Default::default()
+            }))
+            .is_some()
+        });
+        assert!(
+            adapter_available,
+            "No wgpu adapter available, set MAPSDK_SKIP_GPU_TESTS to skip GPU tests"
+        );
+
+        Some(Map::new(
+            Canvas::Offscreen {
+                width: 64,
+                height: 64,
+                pixel_ratio: 1.0,
+            },
+            options,
+        ))
+    }
+
+    #[test]
+    fn test_render_to_image() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let mut feature_layer = FeatureLayer::new(
+            FeatureLayerOptions::default().with_shape_styles(ShapeStyles {
+                fill_color: Color::from_rgb(255, 0, 0),
+                stroke_enabled: false,
+                ..Default::default()
+            }),
+        );
+        feature_layer.add_feature(Feature::new(
+            "0",
+            Shape::Geometry(
+                polygon![
+                    (x: -5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: 5000000.0),
+                    (x: -5000000.0, y: 5000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(image.get_pixel(32, 32).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_batched_features() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let mut shape_styles = ShapeStyles {
+            stroke_enabled: false,
+            ..Default::default()
+        };
+        shape_styles.expressions.fill_color = Some(r#"["get", "color"]"#.parse().unwrap());
+
+        let mut feature_layer = FeatureLayer::new(
+            FeatureLayerOptions::default()
+                .with_shape_styles(shape_styles)
+                .with_batched(true),
+        );
+        for (id, x, color) in [("0", -10000000.0, "#ff0000"), ("1", 0.0, "#0000ff")] {
+            feature_layer.add_feature(Feature::new(
+                id,
+                Shape::Geometry(
+                    polygon![
+                        (x: x, y: -5000000.0),
+                        (x: x + 10000000.0, y: -5000000.0),
+                        (x: x + 10000000.0, y: 5000000.0),
+                        (x: x, y: 5000000.0),
+                    ]
+                    .into(),
+                ),
+                Some(HashMap::from([("color".to_string(), color.into())])),
+            ));
+        }
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.get_pixel(24, 32).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(40, 32).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(32, 8).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_updated_features() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let square = |id: &str, attrs| {
+            Feature::new(
+                id,
+                Shape::Geometry(
+                    polygon![
+                        (x: -5000000.0, y: -5000000.0),
+                        (x: 5000000.0, y: -5000000.0),
+                        (x: 5000000.0, y: 5000000.0),
+                        (x: -5000000.0, y: 5000000.0),
+                    ]
+                    .into(),
+                ),
+                attrs,
+            )
+        };
+        let mut shape_styles = ShapeStyles {
+            fill_color: Color::from_rgb(255, 0, 0),
+            stroke_enabled: false,
+            ..Default::default()
+        };
+        shape_styles.expressions.fill_color = Some(r#"["get", "color"]"#.parse().unwrap());
+
+        let mut feature_layer =
+            FeatureLayer::new(FeatureLayerOptions::default().with_shape_styles(shape_styles));
+        feature_layer.add_feature(square("0", None));
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.get_pixel(32, 32).0, [255, 0, 0, 255]);
+
+        // Draw items are kept while the features are unchanged, and follow their changes
+        map.render_to_image().unwrap();
+        map.with_layer_mut("feature", |layer: &mut FeatureLayer| {
+            layer.update_feature(square(
+                "0",
+                Some(HashMap::from([("color".to_string(), "#0000ff".into())])),
+            ))
+        });
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.get_pixel(32, 32).0, [0, 0, 255, 255]);
+
+        map.with_layer_mut("feature", |layer: &mut FeatureLayer| {
+            layer.remove_feature("0")
+        });
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.get_pixel(32, 32).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_stroke_inner_join() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+        let res = map.resolution().unwrap();
+
+        let mut line_layer = FeatureLayer::new(FeatureLayerOptions::default().with_shape_styles(
+            ShapeStyles {
+                stroke_color: Color::from_rgba(0, 0, 0, 0.5),
+                stroke_width: 16.0,
+                ..Default::default()
+            },
+        ));
+        // Right, then turn up at the center
+        line_layer.add_feature(Feature::new(
+            "line",
+            Shape::Geometry(
+                line_string![(x: -24.0 * res, y: 0.0), (x: 0.0, y: 0.0), (x: 0.0, y: 24.0 * res)]
+                    .into(),
+            ),
+            None,
+        ));
+        map.add_layer("line", Box::new(line_layer)).unwrap();
+
+        let image = map.render_to_image().unwrap();
+        let straight = image.get_pixel(16, 32).0;
+        assert!((100..=200).contains(&straight[0]), "{:?}", straight);
+        // Both segments cover the inner corner, it is only blended once when
+        // they are pulled back at the join
+        assert_eq!(image.get_pixel(30, 30).0, straight);
+        assert!(image.pixels().all(|pixel| pixel.0[0] >= straight[0]));
+    }
+
+    #[test]
+    fn test_frame_allocations() {
+        let Some(mut map) = test_map(&MapOptions::default()) else {
+            return;
+        };
+
+        let mut feature_layer = FeatureLayer::new(FeatureLayerOptions::default());
+        feature_layer.add_feature(Feature::new(
+            "polygon",
+            Shape::Geometry(
+                polygon![
+                    (x: -5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: 5000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        feature_layer.add_feature(Feature::new(
+            "point",
+            Shape::Geometry(Point::new(0.0, 0.0).into()),
+            None,
+        ));
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        map.render_to_image().unwrap();
+        let first_frame = map.frame_allocations().unwrap();
+        assert!(first_frame.buffers > 0);
+        assert!(first_frame.bind_groups > 0);
+
+        // Bind groups and their buffers are reused, also when the view changes
+        map.render_to_image().unwrap();
+        assert_eq!(map.frame_allocations(), Some(FrameAllocations::default()));
+
+        map.set_center(Coord {
+            x: 1000000.0,
+            y: 1000000.0,
+        });
+        map.render_to_image().unwrap();
+        assert_eq!(map.frame_allocations(), Some(FrameAllocations::default()));
+    }
+
+    #[test]
+    fn test_export_image() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let mut feature_layer = FeatureLayer::new(
+            FeatureLayerOptions::default().with_shape_styles(ShapeStyles {
+                fill_color: Color::from_rgb(255, 0, 0),
+                stroke_enabled: false,
+                ..Default::default()
+            }),
+        );
+        feature_layer.add_feature(Feature::new(
+            "square",
+            Shape::Geometry(
+                polygon![
+                    (x: -5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: 5000000.0),
+                    (x: -5000000.0, y: 5000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        map.add_layer("square", Box::new(feature_layer)).unwrap();
+
+        let mut line_layer = FeatureLayer::new(FeatureLayerOptions::default().with_shape_styles(
+            ShapeStyles {
+                stroke_color: Color::from_rgb(0, 255, 0),
+                stroke_width: 4.0,
+                ..Default::default()
+            },
+        ));
+        line_layer.add_feature(Feature::new(
+            "line",
+            Shape::Geometry(
+                line_string![(x: -10000000.0, y: -10000000.0), (x: 10000000.0, y: -10000000.0)]
+                    .into(),
+            ),
+            None,
+        ));
+        map.add_layer("line", Box::new(line_layer)).unwrap();
+
+        // Served image, only drawn once loaded
+        let image_url = serve_http(|_| {
+            let mut bytes = Vec::new();
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255])))
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .ok()?;
+            Some(bytes)
+        });
+        map.add_layer(
+            "image",
+            Box::new(ImageLayer::new(
+                &format!("{}/image.png", image_url),
+                Rect::new(
+                    Coord {
+                        x: -20000000.0,
+                        y: 12000000.0,
+                    },
+                    Coord {
+                        x: -12000000.0,
+                        y: 20000000.0,
+                    },
+                ),
+                ImageLayerOptions::default(),
+            )),
+        )
+        .unwrap();
+
+        let view_seq = map.context.lock().unwrap().map_state.view_seq();
+
+        let export = |pixel_ratio| {
+            let bytes =
+                pollster::block_on(map.export_image(128, 96, pixel_ratio, ImageFormat::Png))
+                    .unwrap();
+            image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+                .unwrap()
+                .to_rgba8()
+        };
+        // Antialiased width of the line across row 72
+        let line_width = |image: &RgbaImage| -> f64 {
+            (56..96)
+                .map(|y| (255 - image.get_pixel(64, y).0[0]) as f64 / 255.0)
+                .sum()
+        };
+
+        let image_1x = export(1.0);
+        let image = export(2.0);
+        assert_eq!(image.dimensions(), (128, 96));
+
+        // The world spans the 96 pixels of the smaller side, the square 24 pixels around the center
+        assert_eq!(image.get_pixel(64, 48).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(53, 48).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(50, 48).0, [255, 255, 255, 255]);
+
+        // Stroke widths scale with the pixel ratio
+        let ratio = line_width(&image) / line_width(&image_1x);
+        assert!((ratio - 2.0).abs() < 0.2, "line width ratio {}", ratio);
+
+        // The export waits for the image to load
+        assert_eq!(image.get_pixel(25, 10).0, [0, 0, 255, 255]);
+
+        // The map itself keeps its size and view
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(image.get_pixel(32, 32).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(21, 32).0, [255, 255, 255, 255]);
+        assert_eq!(map.context.lock().unwrap().map_state.view_seq(), view_seq);
+
+        assert!(pollster::block_on(map.export_image(0, 96, 1.0, ImageFormat::Png)).is_err());
+    }
+
+    #[test]
+    fn test_export_tiles() {
+        let Some(mut map) = test_map(&MapOptions::default()) else {
+            return;
+        };
+
+        let requested_paths = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_http({
+            let requested_paths = requested_paths.clone();
+            move |path| {
+                requested_paths.lock().unwrap().push(path.to_string());
+                std::fs::read("../assets/mvt.pbf").ok()
+            }
+        });
+        map.add_layer(
+            "vector",
+            Box::new(VectorTiledLayer::new(
+                &format!("{}/{{z}}/{{x}}/{{y}}.pbf", url),
+                VectorTiledLayerOptions::default(),
+            )),
+        )
+        .unwrap();
+
+        let live_loaded =
+            |map: &Map| map.context.lock().unwrap().layers["vector"].is_loaded(MapView::Live);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !live_loaded(&map) {
+            assert!(Instant::now() < deadline, "Timed out loading the live view");
+            map.render_to_image().unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        map.render_to_image().unwrap();
+
+        // Twice the size of the window, tiles are one zoom level more detailed
+        export_rgba(&map);
+        assert!(requested_paths
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|path| path.starts_with("/1/")));
+
+        // The live view keeps its tiles, the tiles of the export are dropped
+        assert!(live_loaded(&map));
+        let context = map.context.lock().unwrap();
+        let tile_id = TileId { z: 0, x: 0, y: 0 };
+        assert!(context
+            .map_renderer
+            .contains_layer_draw_item("vector", &tile_id));
+        assert!(!context
+            .map_renderer
+            .contains_layer_draw_item(&MapView::Export(1).layer_key("vector"), &tile_id));
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let Some(mut map) = test_map(&MapOptions::default()) else {
+            return;
+        };
+
+        let mut receiver = map.subscribe();
+
+        map.add_layer(
+            "feature",
+            Box::new(FeatureLayer::new(FeatureLayerOptions::default())),
+        )
+        .unwrap();
+        map.remove_layer("feature");
+        map.jump_to(&MapViewChange::default().with_zoom_res(1000.0));
+
+        // Events are published from the event loop, they should arrive well before the deadline
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut map_events = Vec::new();
+        while map_events.len() < 3 {
+            match receiver.try_recv() {
+                Ok(
+                    map_event @ (MapEvent::LayerAdded { .. }
+                    | MapEvent::LayerRemoved { .. }
+                    | MapEvent::AnimationFinished),
+                ) => map_events.push(map_event),
+                Ok(_) => {}
+                Err(broadcast::error::TryRecvError::Empty) => {
+                    assert!(
+                        Instant::now() < deadline,
+                        "Timed out waiting for map events, received {:?}",
+                        map_events
+                    );
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => panic!("Failed to receive map events: {}", err),
+            }
+        }
+
+        assert_eq!(
+            map_events,
+            vec![
+                MapEvent::LayerAdded {
+                    name: "feature".to_string()
+                },
+                MapEvent::LayerRemoved {
+                    name: "feature".to_string()
+                },
+                MapEvent::AnimationFinished,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_rendered_features() {
+        let Some(mut map) = test_map(&MapOptions::default()) else {
+            return;
+        };
+
+        let mut feature_layer = FeatureLayer::new(FeatureLayerOptions::default());
+        feature_layer.add_feature(Feature::new(
+            "0",
+            Shape::Geometry(
+                polygon![
+                    (x: -5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: -5000000.0),
+                    (x: 5000000.0, y: 5000000.0),
+                    (x: -5000000.0, y: 5000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        let queried_features = map.query_rendered_features(&Coord { x: 32.0, y: 32.0 }, 0.0, None);
+        assert_eq!(queried_features.len(), 1);
+        assert_eq!(queried_features[0].layer, "feature");
+        assert_eq!(queried_features[0].feature.id(), "0");
+
+        assert!(map
+            .query_rendered_features(&Coord { x: 1.0, y: 1.0 }, 0.0, None)
+            .is_empty());
+        assert!(map
+            .query_rendered_features(&Coord { x: 32.0, y: 32.0 }, 0.0, Some(&["other"]))
+            .is_empty());
+        assert_eq!(
+            map.query_rendered_features_in_box(
+                &Coord { x: 0.0, y: 0.0 },
+                &Coord { x: 64.0, y: 64.0 },
+                None
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_world_copy() {
+        let half = Tiling::default().map_size() / 2.0;
+
+        let Some(mut map) = test_map(
+            &MapOptions::default()
+                .with_background_color(Color::from_rgb(255, 255, 255))
+                .with_center(Coord {
+                    x: half + 1000.0,
+                    y: 0.0,
+                })
+                .with_zoom(1),
+        ) else {
+            return;
+        };
+
+        // Longitude is normalized into the world
+        let center = map.center().unwrap();
+        assert!((center.x - (-half + 1000.0)).abs() < 1e-6);
+
+        let mut shape_styles = ShapeStyles {
+            stroke_enabled: false,
+            ..Default::default()
+        };
+        shape_styles.expressions.fill_color = Some(r#"["get", "color"]"#.parse().unwrap());
+
+        let mut feature_layer =
+            FeatureLayer::new(FeatureLayerOptions::default().with_shape_styles(shape_styles));
+        for (id, x, color) in [("0", half - 5000000.0, "#ff0000"), ("1", -half, "#0000ff")] {
+            feature_layer.add_feature(Feature::new(
+                id,
+                Shape::Geometry(
+                    polygon![
+                        (x: x, y: -5000000.0),
+                        (x: x + 5000000.0, y: -5000000.0),
+                        (x: x + 5000000.0, y: 5000000.0),
+                        (x: x, y: 5000000.0),
+                    ]
+                    .into(),
+                ),
+                Some(HashMap::from([("color".to_string(), color.into())])),
+            ));
+        }
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        // Both sides of the antimeridian are drawn
+        let image = map.render_to_image().unwrap();
+        assert_eq!(image.get_pixel(26, 32).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(38, 32).0, [0, 0, 255, 255]);
+
+        // Coordinates east of the antimeridian are wrapped and shown in the nearest world copy
+        let map_coord = map.to_map(&Coord { x: 38.0, y: 32.0 }).unwrap();
+        assert!(map_coord.x > -half && map_coord.x < -half + 5000000.0);
+        let screen_coord = map.to_screen(&map_coord).unwrap();
+        assert!((screen_coord.x - 38.0).abs() < 1e-6);
+
+        let screen_coord = map
+            .to_screen(&Coord {
+                x: half - 2500000.0,
+                y: 0.0,
+            })
+            .unwrap();
+        assert!((screen_coord.x - 24.0).abs() < 1.0);
+
+        let queried_features = map.query_rendered_features(&Coord { x: 38.0, y: 32.0 }, 0.0, None);
+        assert_eq!(queried_features.len(), 1);
+        assert_eq!(queried_features[0].feature.id(), "1");
+        assert_eq!(
+            map.query_rendered_features_in_box(
+                &Coord { x: 20.0, y: 20.0 },
+                &Coord { x: 44.0, y: 44.0 },
+                None
+            )
+            .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_view_constraints() {
+        let max_bounds = Rect::new(
+            Coord {
+                x: -5000000.0,
+                y: -5000000.0,
+            },
+            Coord {
+                x: 5000000.0,
+                y: 5000000.0,
+            },
+        );
+
+        let Some(mut map) = test_map(
+            &MapOptions::default()
+                .with_max_bounds(max_bounds)
+                .with_pitch_max(60.0),
+        ) else {
+            return;
+        };
+
+        // The whole view fits in the bounds
+        let map_res = map.zoom_res() * 4.0;
+        assert!(map_res * 64.0 <= max_bounds.width() + 1e-6);
+
+        map.jump_to(&MapViewChange::default().with_pitch(120.0).with_yaw(10.0));
+        assert_eq!(map.pitch(), 60.0);
+        assert_eq!(map.yaw(), 10.0);
+
+        map.set_pitch_yaw(-10.0, 0.0);
+        assert_eq!(map.pitch(), 0.0);
+
+        map.jump_to(
+            &MapViewChange::default()
+                .with_center(Coord {
+                    x: 20000000.0,
+                    y: 0.0,
+                })
+                .with_zoom_res(Tiling::default().get_resolution(5)),
+        );
+        let center = map.center().unwrap();
+        let map_res = map.zoom_res() * 4.0;
+        assert!((center.x - (max_bounds.max().x - 32.0 * map_res)).abs() < 1e-6);
+        assert_eq!(center.y, 0.0);
+
+        // Zooming out stops at the bounds
+        map.zoom_around(&Coord { x: 0.0, y: 0.0 }, 1.0 / 1024.0);
+        let center = map.center().unwrap();
+        let map_res = map.zoom_res() * 4.0;
+        assert!(center.x - 32.0 * map_res >= max_bounds.min().x - 1e-6);
+        assert!(center.x + 32.0 * map_res <= max_bounds.max().x + 1e-6);
+
+        // The pitched view sees further, its footprint is kept inside the bounds
+        let zoom_res = map.zoom_res();
+        map.set_pitch_yaw(45.0, 0.0);
+        assert!(map.zoom_res() < zoom_res);
+        map.render_to_image().unwrap();
+        let view_rect = {
+            let context = map.context.lock().unwrap();
+            context.map_state.view_bounds().bounding_rect().unwrap()
+        };
+        assert!(view_rect.min().x >= max_bounds.min().x - 1e-6);
+        assert!(view_rect.min().y >= max_bounds.min().y - 1e-6);
+        assert!(view_rect.max().x <= max_bounds.max().x + 1e-6);
+        assert!(view_rect.max().y <= max_bounds.max().y + 1e-6);
+
+        // Zooming out at the bounds is stopped like at zoom_min
+        let zoom_res = map.zoom_res();
+        map.zoom_around(&Coord { x: 0.0, y: 0.0 }, 0.5);
+        assert_eq!(map.zoom_res(), zoom_res);
+    }
+
+    #[test]
+    fn test_view_constraints_zoom_max() {
+        // Bounds smaller than the view at zoom_max
+        let max_bounds = Rect::new(
+            Coord {
+                x: 1000000.0,
+                y: 1000000.0,
+            },
+            Coord {
+                x: 1001000.0,
+                y: 1001000.0,
+            },
+        );
+
+        let Some(mut map) = test_map(
+            &MapOptions::default()
+                .with_max_bounds(max_bounds)
+                .with_zoom_max(4),
+        ) else {
+            return;
+        };
+
+        // zoom_max is kept, the view is centered on the bounds
+        let tiling = Tiling::default();
+        assert_eq!(map.zoom_res(), tiling.get_resolution(4));
+        assert_eq!(map.center(), Some(max_bounds.center()));
+
+        map.zoom_around(&Coord { x: 0.0, y: 0.0 }, 4.0);
+        assert_eq!(map.zoom_res(), tiling.get_resolution(4));
+        map.zoom_around(&Coord { x: 0.0, y: 0.0 }, 0.25);
+        assert_eq!(map.zoom_res(), tiling.get_resolution(4));
+        assert_eq!(map.center(), Some(max_bounds.center()));
+    }
+
+    #[test]
+    fn test_camera_for_bounds() {
+        let Some(mut map) = test_map(&MapOptions::default()) else {
+            return;
+        };
+
+        let rect = Rect::new(
+            Coord {
+                x: 1000000.0,
+                y: -2500000.0,
+            },
+            Coord {
+                x: 11000000.0,
+                y: 2500000.0,
+            },
+        );
+
+        // The rect width fits the screen width
+        let map_view_change = map
+            .camera_for_bounds(&rect, &Padding::default(), None)
+            .unwrap();
+        let zoom_res = map_view_change.zoom_res.unwrap();
+        assert!((zoom_res * 4.0 * 64.0 - 10000000.0).abs() < 1e-6);
+        assert_eq!(
+            map_view_change.center,
+            Some(Coord {
+                x: 6000000.0,
+                y: 0.0
+            })
+        );
+
+        // The rect fits the right half of the screen
+        let padding = Padding {
+            left: 32.0,
+            ..Default::default()
+        };
+        let map_view_change = map.camera_for_bounds(&rect, &padding, None).unwrap();
+        let zoom_res = map_view_change.zoom_res.unwrap();
+        assert!((zoom_res * 4.0 * 32.0 - 10000000.0).abs() < 1e-6);
+        let center = map_view_change.center.unwrap();
+        assert!((center.x - 1000000.0).abs() < 1e-6);
+
+        // Rotated by 90 degrees, the rect height fits the screen width
+        map.set_pitch_yaw(0.0, 90.0);
+        let map_view_change = map
+            .camera_for_bounds(&rect, &Padding::default(), None)
+            .unwrap();
+        let zoom_res = map_view_change.zoom_res.unwrap();
+        assert!((zoom_res * 4.0 * 64.0 - 10000000.0).abs() < 1e-6);
+
+        // Pitched views see further, so they zoom out
+        map.set_pitch_yaw(45.0, 0.0);
+        let pitched_zoom_res = map
+            .camera_for_bounds(&rect, &Padding::default(), None)
+            .unwrap()
+            .zoom_res
+            .unwrap();
+        assert!(pitched_zoom_res > zoom_res);
+
+        assert!(map
+            .camera_for_bounds(&rect, &Padding::uniform(32.0), None)
+            .is_none());
+
+        // A point is shown at the max zoom
+        let point_rect = Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 0.0, y: 0.0 });
+        map.fit_bounds(&point_rect, &Padding::default(), Some(10), None);
+        assert_eq!(map.zoom_res(), Tiling::default().get_resolution(10));
+        assert_eq!(map.center(), Some(Coord { x: 0.0, y: 0.0 }));
+    }
+
+    #[test]
+    fn test_zoom() {
+        let Some(mut map) = test_map(&MapOptions::default().with_zoom(3)) else {
+            return;
+        };
+        assert_eq!(map.zoom(), 3.0);
+
+        map.set_zoom(12.5);
+        assert!((map.zoom() - 12.5).abs() < 1e-9);
+        assert!((map.zoom_res() - map.options().tiling.resolution_for_zoom(12.5)).abs() < 1e-9);
+
+        map.jump_to(&MapViewChange::default().with_zoom(4.25));
+        assert!((map.zoom() - 4.25).abs() < 1e-9);
+
+        // Zoom limits apply
+        map.set_zoom(30.0);
+        assert_eq!(map.zoom(), 20.0);
+    }
+
+    #[test]
+    fn test_golden_feature_layer() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let mut feature_layer = FeatureLayer::new(
+            FeatureLayerOptions::default().with_shape_styles(ShapeStyles {
+                fill_color: Color::from_rgb(255, 200, 0),
+                stroke_color: Color::from_rgb(0, 0, 160),
+                stroke_width: 3.0,
+                symbol_size: 12.0,
+                ..Default::default()
+            }),
+        );
+        feature_layer.add_feature(Feature::new(
+            "polygon",
+            Shape::Geometry(
+                polygon![
+                    (x: -15000000.0, y: -5000000.0),
+                    (x: 0.0, y: -15000000.0),
+                    (x: 5000000.0, y: 5000000.0),
+                    (x: -10000000.0, y: 10000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        feature_layer.add_feature(Feature::new(
+            "line",
+            Shape::Geometry(
+                line_string![
+                    (x: 5000000.0, y: -15000000.0),
+                    (x: 15000000.0, y: -5000000.0),
+                    (x: 10000000.0, y: 15000000.0),
+                ]
+                .into(),
+            ),
+            None,
+        ));
+        feature_layer.add_feature(Feature::new(
+            "point",
+            Shape::Geometry(Point::new(-12000000.0, 14000000.0).into()),
+            None,
+        ));
+        map.add_layer("feature", Box::new(feature_layer)).unwrap();
+
+        assert_golden("feature_layer", &export_rgba(&map));
+    }
+
+    #[test]
+    fn test_golden_image_layer() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        // Quarters in red, green, blue and black
+        let url = serve_http(|path| {
+            if path != "/image.png" {
+                return None;
+            }
+
+            let image = RgbaImage::from_fn(8, 8, |x, y| match (x < 4, y < 4) {
+                (true, true) => image::Rgba([255, 0, 0, 255]),
+                (false, true) => image::Rgba([0, 255, 0, 255]),
+                (true, false) => image::Rgba([0, 0, 255, 255]),
+                (false, false) => image::Rgba([0, 0, 0, 255]),
+            });
+            let mut bytes = Vec::new();
+            DynamicImage::ImageRgba8(image)
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .ok()?;
+            Some(bytes)
+        });
+        map.add_layer(
+            "image",
+            Box::new(ImageLayer::new(
+                &format!("{}/image.png", url),
+                Rect::new(
+                    Coord {
+                        x: -10000000.0,
+                        y: -5000000.0,
+                    },
+                    Coord {
+                        x: 15000000.0,
+                        y: 10000000.0,
+                    },
+                ),
+                ImageLayerOptions::default(),
+            )),
+        )
+        .unwrap();
+
+        assert_golden("image_layer", &export_rgba(&map));
+    }
+
+    #[test]
+    fn test_golden_vector_tiled_layer() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        // The world tile, other tiles are not found
+        let url = serve_http(|path| match path {
+            "/0/0/0.pbf" => std::fs::read("../assets/mvt.pbf").ok(),
+            _ => None,
+        });
+        map.add_layer(
+            "vector",
+            Box::new(VectorTiledLayer::new(
+                &format!("{}/{{z}}/{{x}}/{{y}}.pbf", url),
+                VectorTiledLayerOptions::default()
+                    .with_layers_shape_styles(&vec![
+                        (
+                            "countries",
+                            ShapeStyles {
+                                fill_color: Color::from_rgb(200, 220, 180),
+                                stroke_color: Color::from_rgb(80, 80, 80),
+                                stroke_width: 1.0,
+                                ..Default::default()
+                            },
+                        ),
+                        (
+                            "geolines",
+                            ShapeStyles {
+                                fill_enabled: false,
+                                stroke_color: Color::from_rgb(0, 0, 255),
+                                stroke_width: 1.0,
+                                dash_array: Some(vec![4.0, 2.0]),
+                                ..Default::default()
+                            },
+                        ),
+                    ])
+                    .with_zoom_max(0),
+            )),
+        )
+        .unwrap();
+
+        assert_golden("vector_tiled_layer", &export_rgba(&map));
+    }
+
+    #[test]
+    fn test_render_vector_tile_style_order() {
+        let Some(mut map) =
+            test_map(&MapOptions::default().with_background_color(Color::from_rgb(255, 255, 255)))
+        else {
+            return;
+        };
+
+        let url = serve_http(|path| match path {
+            "/0/0/0.pbf" => std::fs::read("../assets/mvt.pbf").ok(),
+            _ => None,
+        });
+        // A fill styled after a line is drawn over it
+        map.add_layer(
+            "vector",
+            Box::new(VectorTiledLayer::new(
+                &format!("{}/{{z}}/{{x}}/{{y}}.pbf", url),
+                VectorTiledLayerOptions::default()
+                    .with_layers_shape_styles(&vec![
+                        (
+                            "geolines",
+                            ShapeStyles {
+                                fill_enabled: false,
+                                stroke_color: Color::from_rgb(255, 0, 0),
+                                stroke_width: 8.0,
+                                ..Default::default()
+                            },
+                        ),
+                        (
+                            "countries",
+                            ShapeStyles {
+                                fill_color: Color::from_rgb(0, 0, 255),
+                                stroke_enabled: false,
+                                ..Default::default()
+                            },
+                        ),
+                    ])
+                    .with_zoom_max(0),
+            )),
+        )
+        .unwrap();
+        // The equator in Congo
+        map.set_center(Coord { x: 2.5e6, y: 0.0 });
+
+        let image = export_rgba(&map);
+        assert_eq!(image.get_pixel(64, 64).0, [0, 0, 255, 255]);
+    }
 }
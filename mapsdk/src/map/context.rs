@@ -1,11 +1,12 @@
 use std::{collections::HashMap, time::Instant};
 
-use geo::{polygon, Coord, Polygon, Rect};
+use geo::{polygon, BoundingRect, Coord, Geometry, Polygon, Rect, Translate};
 use glam::{DQuat, DVec3};
+use image::RgbaImage;
 
 use crate::{
-    layer::Layer,
-    map::MapOptions,
+    layer::{FeatureQuery, Layer, QueriedFeature},
+    map::{MapOptions, MapViewChange, Padding},
     render::{InterRenderers, MapRenderer},
 };
 
@@ -16,6 +17,8 @@ pub struct MapContext {
     pub layers: HashMap<String, Box<dyn Layer>>,
 
     pub animating: bool,
+    /// Number of exports begun, identifies the view of each export.
+    pub export_seq: u64,
 
     pub map_renderer: MapRenderer,
     pub inter_renderers: InterRenderers,
@@ -27,26 +30,96 @@ impl MapContext {
         map_renderer: MapRenderer,
         inter_renderers: InterRenderers,
     ) -> Self {
-        let map_state = MapState {
+        let mut map_state = MapState {
             center: map_options.center.clone(),
-            pitch: map_options.pitch,
+            map_res_ratio: map_options.tiling.tile_size() as f64
+                / map_renderer.width().min(map_renderer.height()) as f64,
+            pitch: map_options.pitch.clamp(0.0, map_options.pitch_max),
             yaw: map_options.yaw,
             zoom: map_options.zoom,
             zoom_res: map_options.tiling.get_resolution(map_options.zoom),
             ..Default::default()
         };
+        if map_options.world_copy {
+            map_state.center.x = map_options.tiling.wrap_x(map_state.center.x);
+        }
 
-        Self {
+        let mut context = Self {
             map_options: map_options.clone(),
             map_state,
 
             layers: HashMap::new(),
 
             animating: false,
+            export_seq: 0,
 
             map_renderer,
             inter_renderers,
-        }
+        };
+        context.apply_max_bounds();
+
+        context
+    }
+
+    /// Constrain a center and zoom resolution to keep the view inside the max bounds, if any.
+    ///
+    /// The view is the pitched footprint of the view bounds at the current pitch and yaw. When the
+    /// bounds are smaller than the view at `zoom_max`, the view is centered on them.
+    pub fn constrain_view(&self, center: Coord, zoom_res: f64) -> (Coord, f64) {
+        let max_bounds = match &self.map_options.max_bounds {
+            Some(max_bounds) => max_bounds,
+            None => return (center, zoom_res),
+        };
+
+        let (_, zoom_res_max) = self.zoom_res_range();
+        let zoom_res = zoom_res.min(zoom_res_max);
+
+        let (ext_x, ext_y) = self.view_extents();
+        let map_res = zoom_res * self.map_state.map_res_ratio;
+        let clamp = |v: f64, min: f64, max: f64| {
+            if min < max {
+                v.clamp(min, max)
+            } else {
+                (min + max) / 2.0
+            }
+        };
+
+        let center = Coord {
+            x: clamp(
+                center.x,
+                max_bounds.min().x + ext_x * map_res,
+                max_bounds.max().x - ext_x * map_res,
+            ),
+            y: clamp(
+                center.y,
+                max_bounds.min().y + ext_y * map_res,
+                max_bounds.max().y - ext_y * map_res,
+            ),
+        };
+
+        (center, zoom_res)
+    }
+
+    /// Zoom resolutions of `zoom_max` and `zoom_min`. With max bounds, the maximum is lowered until
+    /// the view fits inside them, but not below the resolution of `zoom_max`.
+    pub fn zoom_res_range(&self) -> (f64, f64) {
+        let tiling = &self.map_options.tiling;
+        let zoom_res_min = tiling.get_resolution(self.map_options.zoom_max);
+        let zoom_res_max = tiling.get_resolution(self.map_options.zoom_min);
+
+        let zoom_res_max = match &self.map_options.max_bounds {
+            Some(max_bounds) => {
+                let (ext_x, ext_y) = self.view_extents();
+                let map_res_ratio = self.map_state.map_res_ratio;
+                let bounds_zoom_res = (max_bounds.width() / 2.0 / (ext_x * map_res_ratio))
+                    .min(max_bounds.height() / 2.0 / (ext_y * map_res_ratio));
+
+                zoom_res_max.min(bounds_zoom_res).max(zoom_res_min)
+            }
+            None => zoom_res_max,
+        };
+
+        (zoom_res_min, zoom_res_max)
     }
 
     pub fn redraw(&mut self) {
@@ -61,24 +134,7 @@ impl MapContext {
             self.map_state.yaw,
         );
 
-        if self.map_state.view_bounds_seq != self.map_state.view_seq {
-            if let Some(view_bounds) = self.calc_view_bounds() {
-                self.map_state.view_bounds = view_bounds;
-            }
-            self.map_state.view_bounds_seq = self.map_state.view_seq;
-        }
-
-        if !self.animating {
-            for (id, layer) in &mut self.layers {
-                log::debug!("Update layer [{}]", id);
-                layer.update(
-                    &self.map_options,
-                    &self.map_state,
-                    &mut self.map_renderer,
-                    &mut self.inter_renderers,
-                );
-            }
-        }
+        self.update();
 
         self.map_renderer.render(
             &self.map_options,
@@ -92,10 +148,197 @@ impl MapContext {
         );
     }
 
+    /// Center and zoom resolution showing the rect inside the padded screen, at the current pitch and yaw.
+    pub fn camera_for_bounds(
+        &self,
+        rect: &Rect,
+        padding: &Padding,
+        max_zoom: Option<usize>,
+    ) -> Option<MapViewChange> {
+        let width = self.map_renderer.width() as f64 - padding.left - padding.right;
+        let height = self.map_renderer.height() as f64 - padding.top - padding.bottom;
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        // Extent of the rect in screen directions
+        let rect_center = rect.center();
+        let r = DQuat::from_axis_angle(DVec3::Z, self.map_state.yaw.to_radians());
+        let (extent_x, extent_y) = rect.to_polygon().exterior().coords().fold(
+            (0.0_f64, 0.0_f64),
+            |(extent_x, extent_y), coord| {
+                let v =
+                    r.inverse() * DVec3::new(coord.x - rect_center.x, coord.y - rect_center.y, 0.0);
+                (extent_x.max(v.x.abs()), extent_y.max(v.y.abs()))
+            },
+        );
+
+        let map_res = (extent_x * 2.0 / width).max(extent_y * 2.0 / height) * self.pitch_factor();
+
+        let tiling = &self.map_options.tiling;
+        let zoom_max = match max_zoom {
+            Some(max_zoom) => max_zoom.min(self.map_options.zoom_max),
+            None => self.map_options.zoom_max,
+        };
+        let zoom_res = (map_res / self.map_state.map_res_ratio).clamp(
+            tiling.get_resolution(zoom_max),
+            tiling.get_resolution(self.map_options.zoom_min),
+        );
+        let map_res = zoom_res * self.map_state.map_res_ratio;
+
+        // Move the center of the padded screen onto the center of the rect
+        let offset =
+            r * DVec3::new(
+                (padding.left - padding.right) / 2.0,
+                (padding.bottom - padding.top) / 2.0,
+                0.0,
+            ) * map_res;
+
+        Some(
+            MapViewChange::default()
+                .with_center(Coord {
+                    x: rect_center.x - offset.x,
+                    y: rect_center.y - offset.y,
+                })
+                .with_zoom_res(zoom_res),
+        )
+    }
+
+    /// Begin an export, returns the view its layers are updated and rendered in.
+    pub fn begin_export(&mut self) -> MapView {
+        self.export_seq += 1;
+
+        MapView::Export(self.export_seq)
+    }
+
+    /// Update layers for the view exported at the given size, returns whether all layers are
+    /// loaded for it. The tiles of the live view are kept.
+    pub fn update_export(&mut self, view: MapView, width: u32, height: u32) -> bool {
+        let export_state = self.export_state(view, width, height);
+
+        self.update_layers(&export_state);
+
+        self.layers.values().all(|layer| layer.is_loaded(view))
+    }
+
+    /// Render the view exported at the given size and pixel ratio, with the layers as last updated
+    /// for it. The export is finished, layers drop their state of the view afterwards.
+    pub fn render_export(
+        &mut self,
+        view: MapView,
+        width: u32,
+        height: u32,
+        pixel_ratio: f64,
+    ) -> Option<RgbaImage> {
+        let export_state = self.export_state(view, width, height);
+
+        let image = self.map_renderer.render_to_image_with_size(
+            width,
+            height,
+            pixel_ratio,
+            &self.map_options,
+            &export_state,
+            &self.inter_renderers,
+        );
+
+        for layer in self.layers.values_mut() {
+            layer.clear_view(view, &mut self.map_renderer);
+        }
+
+        image
+    }
+
+    pub fn query_features(
+        &self,
+        geometry: Geometry,
+        tolerance_px: f64,
+        layer_filter: Option<&[&str]>,
+    ) -> Vec<QueriedFeature> {
+        let map_res = self.map_state.zoom_res * self.map_state.map_res_ratio;
+
+        let query = FeatureQuery {
+            geometry,
+            tolerance: tolerance_px * map_res,
+            pixel_size: map_res * self.map_renderer.pixel_ratio(),
+            zoom: self
+                .map_options
+                .tiling
+                .zoom_for_resolution(self.map_state.zoom_res),
+        };
+
+        let layers = || {
+            // Top layers first
+            self.map_state
+                .layers_order
+                .iter()
+                .rev()
+                .filter(|name| match layer_filter {
+                    Some(filter) => filter.contains(&name.as_str()),
+                    None => true,
+                })
+                .filter_map(|name| self.layers.get(name))
+        };
+
+        let tiling = &self.map_options.tiling;
+        match query.geometry.bounding_rect() {
+            Some(rect) if self.map_options.world_copy => {
+                // Query each world copy overlapped by the geometry in the world itself
+                let copy_queries: Vec<FeatureQuery> = tiling
+                    .world_copies(&rect)
+                    .map(|copy| FeatureQuery {
+                        geometry: query
+                            .geometry
+                            .translate(-copy as f64 * tiling.map_size(), 0.0),
+                        tolerance: query.tolerance,
+                        pixel_size: query.pixel_size,
+                        zoom: query.zoom,
+                    })
+                    .collect();
+
+                layers()
+                    .flat_map(|layer| {
+                        // A feature shown in several world copies is queried once
+                        let mut queried_features: Vec<QueriedFeature> = Vec::new();
+                        for (i, query) in copy_queries.iter().enumerate() {
+                            let copy_features = layer.query_features(query);
+                            if i == 0 {
+                                queried_features = copy_features;
+                                continue;
+                            }
+
+                            for queried_feature in copy_features {
+                                if !queried_features.iter().any(|queried| {
+                                    queried.source_layer == queried_feature.source_layer
+                                        && queried.feature.id() == queried_feature.feature.id()
+                                }) {
+                                    queried_features.push(queried_feature);
+                                }
+                            }
+                        }
+
+                        queried_features
+                    })
+                    .collect()
+            }
+            _ => layers()
+                .flat_map(|layer| layer.query_features(&query))
+                .collect(),
+        }
+    }
+
+    pub fn render_to_image(&mut self) -> Option<RgbaImage> {
+        self.update();
+
+        self.map_renderer
+            .render_to_image(&self.map_options, &self.map_state, &self.inter_renderers)
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.map_state.map_res_ratio =
             self.map_options.tiling.tile_size() as f64 / width.min(height) as f64;
 
+        self.apply_max_bounds();
+
         self.map_renderer.resize(
             width,
             height,
@@ -107,14 +350,22 @@ impl MapContext {
         self.map_state.view_seq += 1;
     }
 
-    pub fn set_center(&mut self, center: Coord) {
+    pub fn set_center(&mut self, mut center: Coord) {
+        if self.map_options.world_copy {
+            center.x = self.map_options.tiling.wrap_x(center.x);
+        }
+
         self.map_state.center = center;
+        self.apply_max_bounds();
         self.map_state.view_seq += 1;
     }
 
     pub fn set_pitch_yaw(&mut self, pitch: f64, yaw: f64) {
+        let pitch = pitch.clamp(0.0, self.map_options.pitch_max);
+
         self.map_state.pitch = pitch;
         self.map_state.yaw = yaw;
+        self.apply_max_bounds();
 
         self.map_renderer.set_pitch_yaw(
             pitch,
@@ -128,14 +379,7 @@ impl MapContext {
     }
 
     pub fn set_zoom_res(&mut self, zoom_res: f64, update_zoom: bool) {
-        let zoom_res_max = self
-            .map_options
-            .tiling
-            .get_resolution(self.map_options.zoom_min);
-        let zoom_res_min = self
-            .map_options
-            .tiling
-            .get_resolution(self.map_options.zoom_max);
+        let (zoom_res_min, zoom_res_max) = self.zoom_res_range();
 
         let new_zoom_res = if self.animating {
             zoom_res
@@ -144,14 +388,29 @@ impl MapContext {
         };
 
         self.map_state.zoom_res = new_zoom_res;
+        self.apply_max_bounds();
         if update_zoom {
-            self.map_state.zoom = self.map_options.tiling.get_closest_lower_zoom(new_zoom_res);
+            self.map_state.zoom = self
+                .map_options
+                .tiling
+                .get_closest_lower_zoom(self.map_state.zoom_res);
         }
 
         self.map_state.view_seq += 1;
     }
 
     pub fn to_map(&self, screen_coord: &Coord) -> Option<Coord> {
+        let mut map_coord = self.to_map_unwrapped(screen_coord)?;
+
+        if self.map_options.world_copy {
+            map_coord.x = self.map_options.tiling.wrap_x(map_coord.x);
+        }
+
+        Some(map_coord)
+    }
+
+    /// Map coordinate of a screen coordinate, in the world copy it is shown in.
+    pub fn to_map_unwrapped(&self, screen_coord: &Coord) -> Option<Coord> {
         let screen_center_x = self.map_renderer.width() as f64 / 2.0;
         let screen_center_y = self.map_renderer.height() as f64 / 2.0;
 
@@ -195,8 +454,17 @@ impl MapContext {
         let map_center = self.map_state.center;
         let map_res = self.map_state.zoom_res * self.map_state.map_res_ratio;
 
+        // Use the world copy nearest to the center
+        let map_x = if self.map_options.world_copy {
+            self.map_options
+                .tiling
+                .nearest_world_x(map_coord.x, map_center.x)
+        } else {
+            map_coord.x
+        };
+
         let mp = DVec3::new(
-            (map_coord.x - map_center.x) / map_res,
+            (map_x - map_center.x) / map_res,
             (map_coord.y - map_center.y) / map_res,
             0.0,
         );
@@ -226,71 +494,198 @@ impl MapContext {
     }
 
     pub fn zoom_around(&mut self, coord: &Coord, scalar: f64) {
-        let zoom_res_max = self
-            .map_options
-            .tiling
-            .get_resolution(self.map_options.zoom_min);
-        let zoom_res_min = self
-            .map_options
-            .tiling
-            .get_resolution(self.map_options.zoom_max);
+        let (zoom_res_min, zoom_res_max) = self.zoom_res_range();
 
         let zoom_res = self.map_state.zoom_res;
         let new_zoom_res = (zoom_res / scalar).clamp(zoom_res_min, zoom_res_max);
 
         self.map_state.zoom_res = new_zoom_res;
         if new_zoom_res != zoom_res {
-            self.map_state.zoom = self.map_options.tiling.get_closest_lower_zoom(new_zoom_res);
-
             let center = self.map_state.center;
-            self.map_state.center = *coord + (center - *coord) * (new_zoom_res / zoom_res);
+            let mut coord = *coord;
+            if self.map_options.world_copy {
+                coord.x = self.map_options.tiling.nearest_world_x(coord.x, center.x);
+            }
+
+            self.map_state.center = coord + (center - coord) * (new_zoom_res / zoom_res);
         } else if new_zoom_res == zoom_res_max && scalar < 1.0 {
             let center = self.map_state.center;
             let origin_center = self.map_options.center;
             self.map_state.center = center + (origin_center - center) * (1.0 - scalar).powf(0.5);
         }
 
+        if self.map_options.world_copy {
+            self.map_state.center.x = self.map_options.tiling.wrap_x(self.map_state.center.x);
+        }
+
+        self.apply_max_bounds();
+        self.map_state.zoom = self
+            .map_options
+            .tiling
+            .get_closest_lower_zoom(self.map_state.zoom_res);
+
         self.map_state.view_seq += 1;
     }
 
+    fn apply_max_bounds(&mut self) {
+        let (center, zoom_res) =
+            self.constrain_view(self.map_state.center, self.map_state.zoom_res);
+
+        self.map_state.center = center;
+        self.map_state.zoom_res = zoom_res;
+    }
+
+    fn update(&mut self) {
+        if self.map_state.view_bounds_seq != self.map_state.view_seq {
+            if let Some(view_bounds) = self.calc_view_bounds() {
+                self.map_state.view_bounds = view_bounds;
+            }
+            self.map_state.view_bounds_seq = self.map_state.view_seq;
+        }
+
+        if !self.animating {
+            let map_state = self.map_state.clone();
+            self.update_layers(&map_state);
+        }
+    }
+
+    fn update_layers(&mut self, map_state: &MapState) {
+        for (id, layer) in &mut self.layers {
+            log::debug!("Update layer [{}]", id);
+            layer.update(
+                &self.map_options,
+                map_state,
+                &mut self.map_renderer,
+                &mut self.inter_renderers,
+            );
+        }
+    }
+
+    /// State of the current view rendered at another size, keeping the center and zoom resolution.
+    ///
+    /// Tiles are picked for the resolution of the export, e.g. of a higher zoom level when the
+    /// export is larger than the window.
+    fn export_state(&self, view: MapView, width: u32, height: u32) -> MapState {
+        let tiling = &self.map_options.tiling;
+
+        let mut map_state = self.map_state.clone();
+        map_state.view = view;
+        map_state.map_res_ratio = tiling.tile_size() as f64 / width.min(height) as f64;
+        map_state.view_bounds = view_bounds(&map_state, width as f64, height as f64);
+
+        let tile_res = map_state.zoom_res * map_state.map_res_ratio / self.map_state.map_res_ratio;
+        map_state.zoom = tiling
+            .get_closest_lower_zoom(tile_res)
+            .clamp(self.map_options.zoom_min, self.map_options.zoom_max);
+
+        map_state
+    }
+
+    /// Scale of the view bounds over the unpitched view, as the pitched view sees further.
+    fn pitch_factor(&self) -> f64 {
+        pitch_factor(
+            self.map_renderer.width() as f64,
+            self.map_renderer.height() as f64,
+            self.map_state.pitch,
+        )
+    }
+
+    /// Half extents of the view bounds along the map axes, in pixels of a map resolution of 1.
+    fn view_extents(&self) -> (f64, f64) {
+        view_corners(
+            self.map_renderer.width() as f64,
+            self.map_renderer.height() as f64,
+            self.map_state.pitch,
+            self.map_state.yaw,
+        )
+        .iter()
+        .fold((0.0_f64, 0.0_f64), |(ext_x, ext_y), v| {
+            (ext_x.max(v.x.abs()), ext_y.max(v.y.abs()))
+        })
+    }
+
     fn calc_view_bounds(&self) -> Option<Polygon> {
-        let center = self.map_state.center;
-        let map_res = self.map_state.zoom_res * self.map_state.map_res_ratio;
+        Some(view_bounds(
+            &self.map_state,
+            self.map_renderer.width() as f64,
+            self.map_renderer.height() as f64,
+        ))
+    }
+}
 
-        let hw = self.map_renderer.width() as f64 / 2.0;
-        let hh = self.map_renderer.height() as f64 / 2.0;
+/// Scale of the view bounds over the unpitched view of the given size, as the pitched view sees
+/// further.
+fn pitch_factor(width: f64, height: f64, pitch: f64) -> f64 {
+    let hw = width / 2.0;
+    let hh = height / 2.0;
 
-        let r = DQuat::from_axis_angle(DVec3::Z, self.map_state.yaw.to_radians());
+    let hd = (hw * hw + hh * hh).sqrt();
 
-        let hd = (hw * hw + hh * hh).sqrt();
+    let alpha = (hh / hd).atan();
+    let theta = alpha - pitch.to_radians();
 
-        let alpha = (hh / hd).atan();
-        let theta = alpha - self.map_state.pitch.to_radians();
+    const MAX_FACTOR: f64 = 3.0;
+    if theta > 0.0 {
+        (alpha.sin() / theta.sin()).min(MAX_FACTOR)
+    } else {
+        MAX_FACTOR
+    }
+}
 
-        const MAX_FACTOR: f64 = 3.0;
-        let factor = if theta > 0.0 {
-            (alpha.sin() / theta.sin()).min(MAX_FACTOR)
-        } else {
-            MAX_FACTOR
-        };
+/// Corners of the view of the given size relative to its center, left top, left bottom, right
+/// bottom and right top, rotated by the yaw and scaled by the pitch factor.
+fn view_corners(width: f64, height: f64, pitch: f64, yaw: f64) -> [DVec3; 4] {
+    let hw = width / 2.0;
+    let hh = height / 2.0;
+
+    let r = DQuat::from_axis_angle(DVec3::Z, yaw.to_radians());
+
+    let scale = pitch_factor(width, height, pitch);
 
-        let scale = map_res * factor;
+    [
+        r * DVec3::new(-hw, hh, 0.0) * scale,
+        r * DVec3::new(-hw, -hh, 0.0) * scale,
+        r * DVec3::new(hw, -hh, 0.0) * scale,
+        r * DVec3::new(hw, hh, 0.0) * scale,
+    ]
+}
+
+/// Bounds of the view of the given size, scaled by the pitch factor.
+fn view_bounds(map_state: &MapState, width: f64, height: f64) -> Polygon {
+    let center = map_state.center;
+    let map_res = map_state.zoom_res * map_state.map_res_ratio;
 
-        let v_lt = r * DVec3::new(-hw, hh, 0.0) * scale;
-        let v_lb = r * DVec3::new(-hw, -hh, 0.0) * scale;
-        let v_rt = r * DVec3::new(hw, hh, 0.0) * scale;
-        let v_rb = r * DVec3::new(hw, -hh, 0.0) * scale;
+    let [v_lt, v_lb, v_rb, v_rt] =
+        view_corners(width, height, map_state.pitch, map_state.yaw).map(|v| v * map_res);
 
-        Some(polygon![
-            (x: center.x + v_lt.x, y: center.y + v_lt.y),
-            (x: center.x + v_lb.x, y: center.y + v_lb.y),
-            (x: center.x + v_rb.x, y: center.y + v_rb.y),
-            (x: center.x + v_rt.x, y: center.y + v_rt.y),
-        ])
+    polygon![
+        (x: center.x + v_lt.x, y: center.y + v_lt.y),
+        (x: center.x + v_lb.x, y: center.y + v_lb.y),
+        (x: center.x + v_rb.x, y: center.y + v_rb.y),
+        (x: center.x + v_rt.x, y: center.y + v_rt.y),
+    ]
+}
+
+/// View a map state is rendered in. Tiled layers keep the tiles of each view apart, so an export
+/// loading its tiles doesn't disturb the live view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MapView {
+    #[default]
+    Live,
+    Export(u64),
+}
+
+impl MapView {
+    /// Name the draw items and labels of a layer are kept under in the renderer for this view.
+    pub fn layer_key(&self, layer_name: &str) -> String {
+        match self {
+            MapView::Live => layer_name.to_string(),
+            MapView::Export(id) => format!("{}@export{}", layer_name, id),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MapState {
     pub center: Coord,
     pub map_res_ratio: f64,
@@ -298,6 +693,7 @@ pub struct MapState {
     pub yaw: f64,
     pub zoom: usize,
     pub zoom_res: f64,
+    pub view: MapView,
 
     pub layers_order: Vec<String>,
 
@@ -315,6 +711,7 @@ impl Default for MapState {
             yaw: 0.0,
             zoom: 0,
             zoom_res: 1.0,
+            view: MapView::Live,
 
             layers_order: Vec::new(),
 
@@ -330,4 +727,8 @@ impl MapState {
     pub fn view_bounds(&self) -> &Polygon {
         &self.view_bounds
     }
+
+    pub fn view_seq(&self) -> u64 {
+        self.view_seq
+    }
 }
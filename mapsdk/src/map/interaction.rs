@@ -0,0 +1,451 @@
+use std::time::{Duration, Instant};
+
+use geo::Coord;
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+};
+
+use crate::map::Map;
+
+/// Maximum cursor movement in pixels between the clicks of a double click.
+pub const DOUBLE_CLICK_DISTANCE: f64 = 5.0;
+
+/// Drives a [`Map`] from winit window events: pan, zoom, rotate and pitch.
+///
+/// Feed it the events of the map window, e.g. in `ApplicationHandler::window_event`.
+#[derive(Default)]
+pub struct MapInteraction {
+    options: MapInteractionOptions,
+
+    cursor_position: Option<Coord>,
+    modifiers: ModifiersState,
+
+    drag_start: Option<(Coord, Coord)>, // cursor position, map center
+    dragging: bool,
+
+    rotate_start: Option<(Coord, f64, f64)>, // cursor position, pitch, yaw
+    rotating: bool,
+
+    last_click: Option<(Instant, Coord)>,
+}
+
+impl MapInteraction {
+    pub fn new(options: MapInteractionOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Handle a window event, returns whether the map was driven by it.
+    pub fn handle_window_event(&mut self, map: &mut Map, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => self.cursor_moved(
+                map,
+                Coord {
+                    x: position.x,
+                    y: position.y,
+                },
+            ),
+            WindowEvent::CursorLeft { .. } | WindowEvent::Focused(false) => {
+                self.dragging = false;
+                self.rotating = false;
+                false
+            }
+            WindowEvent::KeyboardInput { event, .. } => self.key_input(map, event),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => self.mouse_input(map, *state, *button),
+            WindowEvent::MouseWheel { delta, .. } if self.options.scroll_zoom => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y,
+                };
+
+                self.zoom_around_cursor(map, 2.0_f64.powf(y * self.options.scroll_zoom_speed))
+            }
+            WindowEvent::PinchGesture { delta, .. } if self.options.pinch_zoom => {
+                if delta.is_nan() {
+                    return false;
+                }
+
+                self.zoom_around_cursor(map, 2.0_f64.powf(delta * self.options.pinch_zoom_speed))
+            }
+            WindowEvent::RotationGesture { delta, .. } if self.options.drag_rotate => {
+                map.set_pitch_yaw(map.pitch(), map.yaw() - *delta as f64);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn options(&self) -> &MapInteractionOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut MapInteractionOptions {
+        &mut self.options
+    }
+
+    fn cursor_moved(&mut self, map: &mut Map, position: Coord) -> bool {
+        self.cursor_position = Some(position);
+
+        if self.dragging {
+            match self.drag_start {
+                Some((start_position, start_center)) => {
+                    if let Some(offset) = screen_offset_to_map(map, position - start_position) {
+                        map.set_center(start_center - offset);
+                        return true;
+                    }
+                }
+                None => {
+                    self.drag_start = map.center().map(|center| (position, center));
+                }
+            }
+        } else if self.rotating {
+            match self.rotate_start {
+                Some((start_position, start_pitch, start_yaw)) => {
+                    if let (Some(width), Some(height)) = (map.width(), map.height()) {
+                        let dx = position.x - start_position.x;
+                        let dy = position.y - start_position.y;
+
+                        map.set_pitch_yaw(
+                            start_pitch
+                                + dy / height as f64
+                                    * map.options().pitch_max
+                                    * self.options.pitch_speed,
+                            start_yaw - dx / width as f64 * self.options.rotate_speed,
+                        );
+                        return true;
+                    }
+                }
+                None => {
+                    self.rotate_start = Some((position, map.pitch(), map.yaw()));
+                }
+            }
+        }
+
+        false
+    }
+
+    fn key_input(&mut self, map: &mut Map, event: &KeyEvent) -> bool {
+        if !self.options.keyboard || event.state != ElementState::Pressed {
+            return false;
+        }
+
+        let step = self.options.keyboard_pan_step;
+        let pan = match event.physical_key {
+            PhysicalKey::Code(KeyCode::ArrowLeft) => Coord { x: -step, y: 0.0 },
+            PhysicalKey::Code(KeyCode::ArrowRight) => Coord { x: step, y: 0.0 },
+            PhysicalKey::Code(KeyCode::ArrowUp) => Coord { x: 0.0, y: -step },
+            PhysicalKey::Code(KeyCode::ArrowDown) => Coord { x: 0.0, y: step },
+            PhysicalKey::Code(KeyCode::Equal | KeyCode::NumpadAdd) => {
+                return self.zoom_around_center(map, self.options.keyboard_zoom_step);
+            }
+            PhysicalKey::Code(KeyCode::Minus | KeyCode::NumpadSubtract) => {
+                return self.zoom_around_center(map, -self.options.keyboard_zoom_step);
+            }
+            _ => return false,
+        };
+
+        match (map.center(), screen_offset_to_map(map, pan)) {
+            (Some(center), Some(offset)) => {
+                map.set_center(center + offset);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn mouse_input(&mut self, map: &mut Map, state: ElementState, button: MouseButton) -> bool {
+        match (state, button) {
+            (ElementState::Pressed, MouseButton::Left) => {
+                if self.options.double_click_zoom && self.double_clicked() {
+                    self.last_click = None;
+
+                    // Zoom out with shift
+                    let levels = if self.modifiers.shift_key() {
+                        -self.options.double_click_zoom_step
+                    } else {
+                        self.options.double_click_zoom_step
+                    };
+
+                    return self.zoom_around_cursor(map, 2.0_f64.powf(levels));
+                }
+
+                self.last_click = self
+                    .cursor_position
+                    .map(|position| (Instant::now(), position));
+
+                self.dragging = self.options.drag_pan;
+                self.drag_start = self.cursor_position.zip(map.center());
+            }
+            (ElementState::Pressed, MouseButton::Right) => {
+                self.rotating = self.options.drag_rotate;
+                self.rotate_start = self
+                    .cursor_position
+                    .map(|position| (position, map.pitch(), map.yaw()));
+            }
+            (ElementState::Released, MouseButton::Left) => self.dragging = false,
+            (ElementState::Released, MouseButton::Right) => self.rotating = false,
+            _ => (),
+        }
+
+        false
+    }
+
+    fn double_clicked(&self) -> bool {
+        match (self.last_click, self.cursor_position) {
+            (Some((time, click_position)), Some(position)) => {
+                let distance = position - click_position;
+
+                time.elapsed() <= self.options.double_click_interval
+                    && distance.x.hypot(distance.y) <= DOUBLE_CLICK_DISTANCE
+            }
+            _ => false,
+        }
+    }
+
+    fn zoom_around_center(&self, map: &mut Map, levels: f64) -> bool {
+        match map.center() {
+            Some(center) => {
+                map.zoom_around(&center, 2.0_f64.powf(levels));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn zoom_around_cursor(&self, map: &mut Map, scalar: f64) -> bool {
+        let coord = match self.cursor_position {
+            Some(cursor_position) => map.to_map(&cursor_position),
+            None => map.center(),
+        };
+
+        match coord {
+            Some(coord) => {
+                map.zoom_around(&coord, scalar);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct MapInteractionOptions {
+    pub drag_pan: bool,
+    /// Rotate and pitch by dragging with the right button.
+    pub drag_rotate: bool,
+    pub double_click_zoom: bool,
+    pub keyboard: bool,
+    pub pinch_zoom: bool,
+    pub scroll_zoom: bool,
+
+    pub double_click_interval: Duration,
+    /// Zoom levels per double click, shift zooms out.
+    pub double_click_zoom_step: f64,
+    /// Pixels per arrow key press.
+    pub keyboard_pan_step: f64,
+    /// Zoom levels per `+`/`-` key press.
+    pub keyboard_zoom_step: f64,
+    /// Fraction of `MapOptions::pitch_max` per dragged window height.
+    pub pitch_speed: f64,
+    /// Zoom levels per pinch magnification.
+    pub pinch_zoom_speed: f64,
+    /// Yaw degrees per dragged window width.
+    pub rotate_speed: f64,
+    /// Zoom levels per wheel line or pixel.
+    pub scroll_zoom_speed: f64,
+}
+
+impl Default for MapInteractionOptions {
+    fn default() -> Self {
+        Self {
+            drag_pan: true,
+            drag_rotate: true,
+            double_click_zoom: true,
+            keyboard: true,
+            pinch_zoom: true,
+            scroll_zoom: true,
+
+            double_click_interval: Duration::from_millis(300),
+            double_click_zoom_step: 1.0,
+            keyboard_pan_step: 100.0,
+            keyboard_zoom_step: 1.0,
+            pitch_speed: 1.0,
+            pinch_zoom_speed: 1.0,
+            rotate_speed: 90.0,
+            scroll_zoom_speed: 0.05,
+        }
+    }
+}
+
+impl MapInteractionOptions {
+    pub fn with_drag_pan(mut self, v: bool) -> Self {
+        self.drag_pan = v;
+        self
+    }
+
+    pub fn with_drag_rotate(mut self, v: bool) -> Self {
+        self.drag_rotate = v;
+        self
+    }
+
+    pub fn with_double_click_zoom(mut self, v: bool) -> Self {
+        self.double_click_zoom = v;
+        self
+    }
+
+    pub fn with_keyboard(mut self, v: bool) -> Self {
+        self.keyboard = v;
+        self
+    }
+
+    pub fn with_pinch_zoom(mut self, v: bool) -> Self {
+        self.pinch_zoom = v;
+        self
+    }
+
+    pub fn with_scroll_zoom(mut self, v: bool) -> Self {
+        self.scroll_zoom = v;
+        self
+    }
+
+    pub fn with_double_click_interval(mut self, v: Duration) -> Self {
+        self.double_click_interval = v;
+        self
+    }
+
+    pub fn with_double_click_zoom_step(mut self, v: f64) -> Self {
+        self.double_click_zoom_step = v;
+        self
+    }
+
+    pub fn with_keyboard_pan_step(mut self, v: f64) -> Self {
+        self.keyboard_pan_step = v;
+        self
+    }
+
+    pub fn with_keyboard_zoom_step(mut self, v: f64) -> Self {
+        self.keyboard_zoom_step = v;
+        self
+    }
+
+    pub fn with_pitch_speed(mut self, v: f64) -> Self {
+        self.pitch_speed = v;
+        self
+    }
+
+    pub fn with_pinch_zoom_speed(mut self, v: f64) -> Self {
+        self.pinch_zoom_speed = v;
+        self
+    }
+
+    pub fn with_rotate_speed(mut self, v: f64) -> Self {
+        self.rotate_speed = v;
+        self
+    }
+
+    pub fn with_scroll_zoom_speed(mut self, v: f64) -> Self {
+        self.scroll_zoom_speed = v;
+        self
+    }
+}
+
+/// Map offset of a screen offset in pixels, at the current resolution and yaw.
+fn screen_offset_to_map(map: &Map, offset: Coord) -> Option<Coord> {
+    let map_res = map.resolution()?;
+    let (sin, cos) = map.yaw().to_radians().sin_cos();
+
+    let dx = offset.x * map_res;
+    let dy = -offset.y * map_res;
+
+    Some(Coord {
+        x: dx * cos - dy * sin,
+        y: dy * cos + dx * sin,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::{dpi::PhysicalPosition, event::DeviceId};
+
+    use super::*;
+    use crate::map::{tests::test_map, MapOptions};
+
+    fn cursor_moved(x: f64, y: f64) -> WindowEvent {
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(x, y),
+        }
+    }
+
+    fn mouse_input(state: ElementState, button: MouseButton) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button,
+        }
+    }
+
+    #[test]
+    fn test_map_interaction() {
+        let Some(mut map) = test_map(&MapOptions::default().with_zoom(2)) else {
+            return;
+        };
+        let mut interaction = MapInteraction::new(
+            MapInteractionOptions::default().with_double_click_interval(Duration::from_secs(60)),
+        );
+
+        // Drag pan by 10 pixels to the right
+        let map_res = map.resolution().unwrap();
+        interaction.handle_window_event(&mut map, &cursor_moved(32.0, 32.0));
+        interaction.handle_window_event(
+            &mut map,
+            &mouse_input(ElementState::Pressed, MouseButton::Left),
+        );
+        assert!(interaction.handle_window_event(&mut map, &cursor_moved(42.0, 32.0)));
+        interaction.handle_window_event(
+            &mut map,
+            &mouse_input(ElementState::Released, MouseButton::Left),
+        );
+
+        let center = map.center().unwrap();
+        assert!((center.x + 10.0 * map_res).abs() < 1e-6);
+        assert!(center.y.abs() < 1e-6);
+
+        // Double click zooms in by one level
+        let zoom = map.zoom();
+        for state in [
+            ElementState::Pressed,
+            ElementState::Released,
+            ElementState::Pressed,
+        ] {
+            interaction.handle_window_event(&mut map, &mouse_input(state, MouseButton::Left));
+        }
+        assert!((map.zoom() - zoom - 1.0).abs() < 1e-9);
+
+        // Disabled handlers leave the map as is
+        interaction.options_mut().scroll_zoom = false;
+        assert!(!interaction.handle_window_event(
+            &mut map,
+            &WindowEvent::MouseWheel {
+                device_id: DeviceId::dummy(),
+                delta: MouseScrollDelta::LineDelta(0.0, 1.0),
+                phase: winit::event::TouchPhase::Moved,
+            },
+        ));
+        assert!((map.zoom() - zoom - 1.0).abs() < 1e-9);
+
+        // Right drag rotates
+        interaction.handle_window_event(
+            &mut map,
+            &mouse_input(ElementState::Pressed, MouseButton::Right),
+        );
+        interaction.handle_window_event(&mut map, &cursor_moved(10.0, 32.0));
+        assert!((map.yaw() - 45.0).abs() < 1e-9);
+    }
+}
@@ -1,17 +1,32 @@
-use std::time::Instant;
+use std::{collections::HashMap, sync::OnceLock, time::Instant};
 
+use ab_glyph::FontArc;
 use dashmap::DashMap;
-use geo::Coord;
-use glam::{Quat, Vec3};
+use geo::{BoundingRect, Coord};
+use glam::{Mat4, Quat, Vec3};
+use image::RgbaImage;
 use wgpu::*;
 
 use crate::{
-    feature::style::OutlineAlign,
-    map::{context::MapState, MapOptions},
+    feature::style::{expression::EvaluationContext, OutlineAlign, ShapeStyles},
+    map::{context::MapState, FrameAllocations, MapOptions},
     render::{
         camera::Camera,
-        draw::{vector_tile::VectorTileDrawable, DrawItem},
-        resources::{bind_group::*, pipeline::*, texture::create_depth_texture},
+        draw::{
+            fill_gradient_transform, fill_pattern_transform, vector_tile::VectorTileDrawable,
+            DrawItem,
+        },
+        resources::{
+            allocation::AllocationCounter,
+            bind_group::*,
+            buffer::create_uniform_buffer_from_bytes,
+            pipeline::*,
+            texture::{create_depth_texture, create_texture, read_texture},
+        },
+        sprite::{Icon, SpriteAtlas},
+        targets::RenderTarget,
+        tessellation::vector_tile::VectorTileShapeMeta,
+        text::{Label, LabelRenderer, LabelView},
     },
     utils::size::PixelSize,
     Canvas,
@@ -20,8 +35,10 @@ use crate::{
 pub mod camera;
 pub mod draw;
 pub mod resources;
+pub mod sprite;
 pub mod targets;
 pub mod tessellation;
+pub mod text;
 
 pub struct MapRenderer {
     renderer_options: MapRendererOptions,
@@ -31,111 +48,196 @@ pub struct MapRenderer {
 
     camera: Camera,
     layer_draw_items: DashMap<String, DashMap<String, DrawItem>>,
+    label_renderer: LabelRenderer,
+    sprite_atlas: SpriteAtlas,
 }
 
 impl MapRenderer {
     pub async fn new(canvas: Canvas, renderer_options: &MapRendererOptions) -> Self {
-        match canvas {
+        let instance = Instance::default();
+
+        let (surface, width, height, pixel_ratio) = match canvas {
             Canvas::Window(window) => {
                 let width = window.width();
                 let height = window.height();
                 let pixel_ratio = window.scale_factor();
 
-                let instance = Instance::default();
                 let surface = instance
                     .create_surface(window.handle())
                     .expect("Failed to create surface");
-                let adapter = instance
-                    .request_adapter(&RequestAdapterOptions {
-                        power_preference: PowerPreference::default(),
-                        force_fallback_adapter: false,
-                        compatible_surface: Some(&surface),
-                    })
-                    .await
-                    .expect("Failed to find adapter");
-                let (device, queue) = adapter
-                    .request_device(
-                        &DeviceDescriptor {
-                            required_features: Features::empty(),
-                            required_limits: if cfg!(target_arch = "wasm32") {
-                                Limits::downlevel_webgl2_defaults()
-                                    .using_resolution(adapter.limits())
-                            } else {
-                                Limits::default().using_resolution(adapter.limits())
-                            },
-                            memory_hints: Default::default(),
-                            label: None,
-                        },
-                        None,
-                    )
-                    .await
-                    .expect("Failed to find device");
+
+                (Some(surface), width, height, pixel_ratio)
+            }
+            Canvas::Offscreen {
+                width,
+                height,
+                pixel_ratio,
+            } => (None, width, height, pixel_ratio),
+        };
+
+        let adapter = request_adapter(&instance, surface.as_ref())
+            .await
+            .expect("Failed to find adapter");
+        let (device, queue) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    required_features: Features::empty(),
+                    required_limits: if cfg!(target_arch = "wasm32") {
+                        Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+                    } else {
+                        Limits::default().using_resolution(adapter.limits())
+                    },
+                    memory_hints: Default::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .expect("Failed to find device");
+
+        let (target, color_format) = match surface {
+            Some(surface) => {
                 if let Some(config) = surface.get_default_config(&adapter, width, height) {
                     surface.configure(&device, &config);
                 }
 
                 let surface_capabilities = surface.get_capabilities(&adapter);
-                let color_target_state = ColorTargetState {
-                    format: surface_capabilities.formats[0],
-                    blend: Some(BlendState::ALPHA_BLENDING),
-                    write_mask: ColorWrites::ALL,
-                };
+                let color_format = surface_capabilities.formats[0];
 
-                let color_sampler = device.create_sampler(&SamplerDescriptor {
-                    address_mode_u: AddressMode::ClampToEdge,
-                    address_mode_v: AddressMode::ClampToEdge,
-                    address_mode_w: AddressMode::ClampToEdge,
-                    mag_filter: FilterMode::Linear,
-                    min_filter: FilterMode::Nearest,
-                    mipmap_filter: FilterMode::Nearest,
-                    ..Default::default()
-                });
+                (RenderTarget::Surface(surface), color_format)
+            }
+            None => {
+                let color_format = TextureFormat::Rgba8UnormSrgb;
+                let texture = create_texture(&device, width, height, color_format);
 
-                let depth_texture = create_depth_texture(&device, width, height);
-                let depth_texture_view =
-                    depth_texture.create_view(&TextureViewDescriptor::default());
-
-                let image_pipeline = create_image_pipeline(&device, &color_target_state);
-                let shape_fill_pipeline = create_shape_fill_pipeline(&device, &color_target_state);
-                let shape_stroke_pipeline =
-                    create_shape_stroke_pipeline(&device, &color_target_state);
-                let symbol_circle_pipeline =
-                    create_symbol_circle_pipeline(&device, &color_target_state);
-
-                let rendering_context = MapRenderingContext {
-                    pixel_ratio,
-                    surface,
-                    adapter,
-                    device,
-                    queue,
-
-                    color_target_state,
-                    color_sampler,
-                    depth_texture_view,
-
-                    image_pipeline,
-                    shape_fill_pipeline,
-                    shape_stroke_pipeline,
-                    symbol_circle_pipeline,
-                };
+                (RenderTarget::Texture(texture), color_format)
+            }
+        };
+
+        let color_target_state = ColorTargetState {
+            format: color_format,
+            blend: Some(BlendState::ALPHA_BLENDING),
+            write_mask: ColorWrites::ALL,
+        };
+
+        let color_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
 
-                let mut camera = Camera::default();
-                camera.set_eye(Vec3::new(0.0, 0.0, height as f32 / 2.0));
-                camera.set_aspect(width as f32 / height as f32);
+        let depth_texture = create_depth_texture(&device, width, height);
+        let depth_texture_view = depth_texture.create_view(&TextureViewDescriptor::default());
+
+        // Batched features read their styles from storage buffers in vertex shaders
+        let supports_feature_batches = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::VERTEX_STORAGE)
+            && device.limits().max_storage_buffers_per_shader_stage > 0;
+        let bind_group_layouts = BindGroupLayouts::new(&device, supports_feature_batches);
+
+        let image_pipeline =
+            create_image_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let shape_fill_pipeline =
+            create_shape_fill_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let shape_stroke_pipeline =
+            create_shape_stroke_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let shape_fill_batch_pipeline =
+            create_shape_fill_batch_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let shape_stroke_batch_pipeline =
+            create_shape_stroke_batch_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let symbol_shape_pipeline =
+            create_symbol_shape_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let symbol_icon_pipeline =
+            create_symbol_icon_pipeline(&device, &color_target_state, &bind_group_layouts);
+        let text_pipeline = create_text_pipeline(&device, &color_target_state, &bind_group_layouts);
 
-                Self {
-                    renderer_options: renderer_options.clone(),
+        let mut camera = Camera::default();
+        camera.set_eye(Vec3::new(0.0, 0.0, height as f32 / 2.0));
+        camera.set_aspect(width as f32 / height as f32);
+
+        // Written once per world copy, see `render_to_view`
+        let map_view_buffer = create_uniform_buffer_from_bytes(
+            &device,
+            "Map View Buffer",
+            bytemuck::bytes_of(&map_view(&camera, &MapState::default())),
+        );
+        let map_view_bg =
+            create_map_view_bg(&device, &bind_group_layouts.map_view, &map_view_buffer);
 
-                    rendering_size: PixelSize::new(width, height),
-                    rendering_context,
+        let allocation_counter = AllocationCounter::default();
 
-                    camera,
-                    layer_draw_items: DashMap::new(),
-                }
-            }
+        let label_renderer = LabelRenderer::new(
+            &device,
+            &allocation_counter,
+            &bind_group_layouts.image_texture,
+        );
+
+        let mut sprite_atlas = SpriteAtlas::new(
+            &device,
+            &allocation_counter,
+            &bind_group_layouts.image_texture,
+        );
+        for (name, icon) in &renderer_options.icons {
+            sprite_atlas.add_icon(&queue, name, icon);
+        }
+
+        let rendering_context = MapRenderingContext {
+            pixel_ratio,
+            target,
+            adapter,
+            device,
+            queue,
+
+            color_target_state,
+            color_sampler,
+            depth_texture_view,
+
+            bind_group_layouts,
+            allocation_counter,
+            map_view_buffer,
+            map_view_bg,
+
+            image_pipeline,
+            shape_fill_pipeline,
+            shape_stroke_pipeline,
+            shape_fill_batch_pipeline,
+            shape_stroke_batch_pipeline,
+            symbol_shape_pipeline,
+            symbol_icon_pipeline,
+            text_pipeline,
+        };
+
+        Self {
+            renderer_options: renderer_options.clone(),
+
+            rendering_size: PixelSize::new(width, height),
+            rendering_context,
+
+            camera,
+            layer_draw_items: DashMap::new(),
+            label_renderer,
+            sprite_atlas,
         }
     }
 
+    /// Add a font used by labels, see [`crate::feature::style::LabelStyle::font`].
+    pub fn add_font(&mut self, name: &str, font: FontArc) {
+        self.renderer_options.fonts.insert(name.to_string(), font);
+    }
+
+    /// Add an icon used by icon styles, or replace the icon with the same name.
+    pub fn add_icon(&mut self, name: &str, icon: Icon) {
+        self.sprite_atlas
+            .add_icon(&self.rendering_context.queue, name, &icon);
+        self.renderer_options.icons.insert(name.to_string(), icon);
+    }
+
     pub fn add_layer_draw_item(
         &mut self,
         layer_name: &str,
@@ -151,10 +253,35 @@ impl MapRenderer {
         }
     }
 
+    /// Add labels of a draw item, they are removed together with the draw item.
+    pub fn add_layer_labels(
+        &mut self,
+        layer_name: &str,
+        item_id: &impl ToString,
+        labels: Vec<Label>,
+    ) {
+        if labels.is_empty() {
+            return;
+        }
+
+        self.label_renderer.add_labels(
+            &self.rendering_context.queue,
+            &self.renderer_options.fonts,
+            layer_name,
+            &item_id.to_string(),
+            labels,
+        );
+    }
+
     pub fn camera(&self) -> &Camera {
         &self.camera
     }
 
+    pub fn clear_layer_draw_items(&mut self, layer_name: &str) {
+        self.layer_draw_items.remove(layer_name);
+        self.label_renderer.clear_labels(layer_name);
+    }
+
     pub fn contains_layer_draw_item(&self, layer_name: &str, item_id: &impl ToString) -> bool {
         if let Some(layer) = self.layer_draw_items.get(layer_name) {
             layer.contains_key(&item_id.to_string())
@@ -167,14 +294,38 @@ impl MapRenderer {
         self.rendering_size.width
     }
 
+    /// Numbers of GPU resources created while rendering the last frame.
+    pub fn frame_allocations(&self) -> FrameAllocations {
+        self.rendering_context.allocation_counter.last_frame()
+    }
+
     pub fn height(&self) -> u32 {
         self.rendering_size.height
     }
 
+    pub fn pixel_ratio(&self) -> f64 {
+        self.rendering_context.pixel_ratio
+    }
+
+    /// Whether features can be drawn in batches, see
+    /// [`crate::layer::feature_layer::FeatureLayerOptions::with_batched`].
+    pub fn supports_feature_batches(&self) -> bool {
+        self.rendering_context.shape_fill_batch_pipeline.is_some()
+    }
+
     pub fn remove_layer_draw_item(&mut self, layer_name: &str, item_id: &impl ToString) {
         if let Some(layer) = self.layer_draw_items.get_mut(layer_name) {
             layer.remove(&item_id.to_string());
         }
+        self.label_renderer
+            .remove_labels(layer_name, &item_id.to_string());
+    }
+
+    pub fn retain_layer_draw_items(&mut self, layer_name: &str, mut f: impl FnMut(&str) -> bool) {
+        if let Some(layer) = self.layer_draw_items.get_mut(layer_name) {
+            layer.retain(|item_id, _| f(item_id));
+        }
+        self.label_renderer.retain_labels(layer_name, f);
     }
 
     pub fn render(
@@ -185,56 +336,81 @@ impl MapRenderer {
     ) {
         let instant = Instant::now();
 
+        match &self.rendering_context.target {
+            RenderTarget::Surface(surface) => {
+                if let Ok(surface_texture) = surface.get_current_texture() {
+                    let surface_view = surface_texture
+                        .texture
+                        .create_view(&TextureViewDescriptor::default());
+
+                    self.render_to_view(&surface_view, map_options, map_state, inter_renderers);
+
+                    surface_texture.present();
+                }
+            }
+            RenderTarget::Texture(texture) => {
+                let texture_view = texture.create_view(&TextureViewDescriptor::default());
+
+                self.render_to_view(&texture_view, map_options, map_state, inter_renderers);
+            }
+        }
+
+        log::info!("MapRenderer::render elapsed: {:?}", instant.elapsed());
+    }
+
+    /// Render a frame into a new texture and read it back, regardless of the canvas type.
+    pub fn render_to_image(
+        &self,
+        map_options: &MapOptions,
+        map_state: &MapState,
+        inter_renderers: &InterRenderers,
+    ) -> Option<RgbaImage> {
         let MapRenderingContext {
-            surface,
             device,
             queue,
+            color_target_state,
             ..
         } = &self.rendering_context;
 
-        if let Ok(surface_texture) = surface.get_current_texture() {
-            let surface_view = surface_texture
-                .texture
-                .create_view(&TextureViewDescriptor::default());
-            let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-                label: Some("Map CommandEncoder"),
-            });
-            {
-                let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
-                    label: Some("Map RenderPass"),
-                    color_attachments: &[Some(RenderPassColorAttachment {
-                        view: &surface_view,
-                        resolve_target: None,
-                        ops: Operations {
-                            load: LoadOp::Clear(self.renderer_options.background_color),
-                            store: StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    timestamp_writes: None,
-                    occlusion_query_set: None,
-                });
+        let texture = create_texture(
+            device,
+            self.rendering_size.width,
+            self.rendering_size.height,
+            color_target_state.format,
+        );
+        let texture_view = texture.create_view(&TextureViewDescriptor::default());
 
-                for layer_name in &map_state.layers_order {
-                    if let Some(layer_pair) = self.layer_draw_items.get_mut(layer_name) {
-                        layer_pair.value().iter_mut().for_each(|mut draw_item| {
-                            draw_item.draw(
-                                map_options,
-                                map_state,
-                                &self,
-                                inter_renderers,
-                                &mut render_pass,
-                            );
-                        });
-                    }
-                }
-            }
+        self.render_to_view(&texture_view, map_options, map_state, inter_renderers);
 
-            queue.submit(Some(command_encoder.finish()));
-            surface_texture.present();
-        }
+        read_texture(device, queue, &texture)
+    }
 
-        log::info!("MapRenderer::render elapsed: {:?}", instant.elapsed());
+    /// Render a frame at another size and pixel ratio into a new texture and read it back, on the
+    /// same device. The rendering size and pixel ratio are restored afterwards.
+    pub fn render_to_image_with_size(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixel_ratio: f64,
+        map_options: &MapOptions,
+        map_state: &MapState,
+        inter_renderers: &InterRenderers,
+    ) -> Option<RgbaImage> {
+        let rendering_size =
+            std::mem::replace(&mut self.rendering_size, PixelSize::new(width, height));
+        let rendering_pixel_ratio =
+            std::mem::replace(&mut self.rendering_context.pixel_ratio, pixel_ratio);
+        self.update_camera_size();
+        self.update_camera_position(map_state.pitch, map_state.yaw);
+
+        let image = self.render_to_image(map_options, map_state, inter_renderers);
+
+        self.rendering_size = rendering_size;
+        self.rendering_context.pixel_ratio = rendering_pixel_ratio;
+        self.update_camera_size();
+        self.update_camera_position(map_state.pitch, map_state.yaw);
+
+        image
     }
 
     pub fn resize(
@@ -252,23 +428,38 @@ impl MapRenderer {
         self.update_camera_position(map_state.pitch, map_state.yaw);
 
         let MapRenderingContext {
-            surface,
+            target,
             adapter,
             device,
+            color_target_state,
             ..
         } = &self.rendering_context;
 
-        if let Some(config) = surface.get_default_config(&adapter, width, height) {
-            surface.configure(&device, &config);
+        match target {
+            RenderTarget::Surface(surface) => {
+                if let Some(config) = surface.get_default_config(adapter, width, height) {
+                    surface.configure(device, &config);
+                }
+            }
+            RenderTarget::Texture(_) => {
+                let texture = create_texture(device, width, height, color_target_state.format);
+                self.rendering_context.target = RenderTarget::Texture(texture);
+            }
         }
 
-        let depth_texture = create_depth_texture(&device, width, height);
+        let MapRenderingContext { device, .. } = &self.rendering_context;
+
+        let depth_texture = create_depth_texture(device, width, height);
         self.rendering_context.depth_texture_view =
             depth_texture.create_view(&TextureViewDescriptor::default());
 
         self.render(map_options, map_state, inter_renderers);
     }
 
+    pub fn set_background_color(&mut self, color: Color) {
+        self.renderer_options.background_color = color;
+    }
+
     pub fn set_pitch_yaw(
         &mut self,
         pitch: f64,
@@ -281,6 +472,139 @@ impl MapRenderer {
         self.render(map_options, map_state, inter_renderers);
     }
 
+    fn render_to_view(
+        &self,
+        view: &TextureView,
+        map_options: &MapOptions,
+        map_state: &MapState,
+        inter_renderers: &InterRenderers,
+    ) {
+        let MapRenderingContext {
+            device,
+            queue,
+            pixel_ratio,
+            allocation_counter,
+            map_view_buffer,
+            ..
+        } = &self.rendering_context;
+
+        allocation_counter.begin_frame();
+
+        let copy_states = world_copy_states(map_options, map_state);
+
+        // Layers without draw items of their own in the view, e.g. feature layers, share the
+        // draw items of the live view
+        let layer_keys: Vec<String> = map_state
+            .layers_order
+            .iter()
+            .map(|layer_name| {
+                let layer_key = map_state.view.layer_key(layer_name);
+                if self.layer_draw_items.contains_key(&layer_key) {
+                    layer_key
+                } else {
+                    layer_name.clone()
+                }
+            })
+            .collect();
+
+        // Each world copy is drawn with its own submit, so the map view written for a copy is
+        // applied before the next copy overwrites it.
+        for (i, copy_state) in copy_states.iter().enumerate() {
+            queue.write_buffer(
+                map_view_buffer,
+                0,
+                bytemuck::bytes_of(&map_view(&self.camera, copy_state)),
+            );
+
+            let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Map CommandEncoder"),
+            });
+            {
+                let load = if i == 0 {
+                    LoadOp::Clear(self.renderer_options.background_color)
+                } else {
+                    LoadOp::Load
+                };
+
+                let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("Map RenderPass"),
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load,
+                            store: StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+
+                for layer_key in &layer_keys {
+                    if let Some(layer_pair) = self.layer_draw_items.get_mut(layer_key) {
+                        layer_pair.value().iter_mut().for_each(|mut draw_item| {
+                            draw_item.draw(
+                                map_options,
+                                copy_state,
+                                self,
+                                inter_renderers,
+                                &mut render_pass,
+                            );
+                        });
+                    }
+                }
+            }
+
+            queue.submit(Some(command_encoder.finish()));
+        }
+
+        // Labels are drawn above all layers of all world copies, placed in one pass so labels
+        // of neighboring copies don't overlap.
+        let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Label CommandEncoder"),
+        });
+        {
+            let mut render_pass = command_encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Label RenderPass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let label_view = LabelView {
+                view_proj: Mat4::from_cols_array_2d(&self.camera.view_proj()),
+                centers: copy_states
+                    .iter()
+                    .map(|copy_state| copy_state.center)
+                    .collect(),
+                map_res: map_state.zoom_res * map_state.map_res_ratio,
+                width: self.rendering_size.width as f32,
+                height: self.rendering_size.height as f32,
+                pixel_ratio: *pixel_ratio as f32,
+                zoom: map_options.tiling.zoom_for_resolution(map_state.zoom_res),
+            };
+            self.label_renderer.draw(
+                &self.rendering_context,
+                &mut render_pass,
+                &layer_keys,
+                &label_view,
+            );
+        }
+
+        queue.submit(Some(command_encoder.finish()));
+
+        allocation_counter.end_frame();
+    }
+
     fn update_camera_position(&mut self, pitch: f64, yaw: f64) {
         let pitch_rad = pitch.to_radians() as f32;
         let yaw_rad = yaw.to_radians() as f32;
@@ -309,6 +633,8 @@ impl MapRenderer {
 #[derive(Clone)]
 pub struct MapRendererOptions {
     background_color: Color,
+    fonts: HashMap<String, FontArc>,
+    icons: HashMap<String, Icon>,
 }
 
 impl Default for MapRendererOptions {
@@ -320,6 +646,8 @@ impl Default for MapRendererOptions {
                 b: 0.0,
                 a: 0.0,
             },
+            fonts: HashMap::new(),
+            icons: HashMap::new(),
         }
     }
 }
@@ -333,7 +661,7 @@ impl MapRendererOptions {
 
 pub struct MapRenderingContext {
     pixel_ratio: f64,
-    surface: Surface<'static>,
+    target: RenderTarget,
     adapter: Adapter,
     device: Device,
     queue: Queue,
@@ -342,10 +670,45 @@ pub struct MapRenderingContext {
     color_sampler: Sampler,
     depth_texture_view: TextureView,
 
+    bind_group_layouts: BindGroupLayouts,
+    allocation_counter: AllocationCounter,
+    /// The map view of all draw items, rewritten for each world copy.
+    map_view_buffer: Buffer,
+    map_view_bg: BindGroup,
+
     image_pipeline: RenderPipeline,
     shape_fill_pipeline: RenderPipeline,
     shape_stroke_pipeline: RenderPipeline,
-    symbol_circle_pipeline: RenderPipeline,
+    /// Not supported without storage buffers in vertex shaders, e.g. on WebGL2.
+    shape_fill_batch_pipeline: Option<RenderPipeline>,
+    shape_stroke_batch_pipeline: Option<RenderPipeline>,
+    symbol_shape_pipeline: RenderPipeline,
+    symbol_icon_pipeline: RenderPipeline,
+    text_pipeline: RenderPipeline,
+}
+
+async fn request_adapter(instance: &Instance, surface: Option<&Surface<'_>>) -> Option<Adapter> {
+    let adapter = instance
+        .request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: surface,
+        })
+        .await;
+
+    if adapter.is_some() {
+        return adapter;
+    }
+
+    log::warn!("No hardware adapter found, fall back to software adapter");
+
+    instance
+        .request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: true,
+            compatible_surface: surface,
+        })
+        .await
 }
 
 pub struct InterRenderers {
@@ -355,6 +718,8 @@ pub struct InterRenderers {
 pub struct VectorTileRenderer {
     camera: Camera,
     map_state: MapState,
+    /// The map view of tile textures, the same for all tiles.
+    map_view: OnceLock<UniformBindGroup>,
 }
 
 impl VectorTileRenderer {
@@ -367,7 +732,11 @@ impl VectorTileRenderer {
         let mut map_state = MapState::default();
         map_state.center = Coord { x: 0.0, y: 0.0 };
 
-        Self { camera, map_state }
+        Self {
+            camera,
+            map_state,
+            map_view: OnceLock::new(),
+        }
     }
 
     pub fn render(
@@ -379,15 +748,20 @@ impl VectorTileRenderer {
     ) {
         let instant = Instant::now();
 
+        let rendering_context = &map_renderer.rendering_context;
         let MapRenderingContext {
             device,
             queue,
+            bind_group_layouts,
 
             shape_fill_pipeline,
             shape_stroke_pipeline,
-            symbol_circle_pipeline,
+            symbol_shape_pipeline,
             ..
-        } = &map_renderer.rendering_context;
+        } = rendering_context;
+
+        // Bind groups are kept across renders, their buffers are rewritten when styles change
+        let mut bind_groups = std::mem::take(&mut vector_tile_drawable.tile_bind_groups);
 
         let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
             label: Some("Vector Tile CommandEncoder"),
@@ -415,174 +789,229 @@ impl VectorTileRenderer {
                 / map_options.tiling.tile_size() as f32
                 / map_renderer.rendering_context.pixel_ratio as f32;
 
-            let map_view_bgl = create_map_view_bgl(device);
-            let map_view_bg =
-                create_map_view_bg(device, &map_view_bgl, &self.camera, &self.map_state);
+            let map_view_bg = self
+                .map_view
+                .get_or_init(|| {
+                    UniformBindGroup::new(
+                        rendering_context,
+                        &bind_group_layouts.map_view,
+                        "Tile Map View",
+                        Uniforms::default().with(&[map_view(&self.camera, &self.map_state)]),
+                    )
+                })
+                .bind_group();
 
-            let symbol_circle_params_bgl = create_symbol_circle_params_bgl(device);
-            let shape_fill_params_bgl = create_shape_fill_params_bgl(device);
-            let shape_stroke_params_bgl = create_shape_stroke_params_bgl(device);
+            // Vertex coords are tile pixels
+            let origin = Coord { x: 0.0, y: 0.0 };
 
-            // draw fills
-            {
-                render_pass.set_vertex_buffer(0, vector_tile_drawable.fill_vertex_buffer.slice(..));
-                render_pass.set_index_buffer(
-                    vector_tile_drawable.fill_index_buffer.slice(..),
-                    IndexFormat::Uint16,
-                );
+            let tile_bbox = map_options
+                .tiling
+                .get_tile_bbox(&vector_tile_drawable.tile_id);
 
-                {
-                    render_pass.set_pipeline(&shape_fill_pipeline);
+            let zoom = map_options.tiling.zoom_for_resolution(map_state.zoom_res);
+            let shape_style_groups = group_shape_metas(vector_tile_drawable, zoom);
 
-                    for (shape_styles_index, (_, shape_styles)) in
-                        vector_tile_drawable.layers_shape_styles.iter().enumerate()
-                    {
-                        if shape_styles.fill_enabled {
-                            let shape_fill_params_bg = create_shape_fill_params_bg(
-                                device,
-                                &shape_fill_params_bgl,
-                                vector_tile_drawable.z,
-                                &shape_styles,
-                            );
+            let draw_buffer_index =
+                |render_pass: &mut RenderPass, buffer_index: &[(u32, u32, i32)]| {
+                    for (index_start, index_end, base_vertex) in buffer_index {
+                        render_pass.draw_indexed(*index_start..*index_end, *base_vertex, 0..1);
+                    }
+                };
+
+            // Draw each group completely before the next one to keep the painter's order of styles
+            for (group_index, (shape_styles, shape_metas)) in shape_style_groups.iter().enumerate()
+            {
+                if shape_styles.fill_enabled || shape_styles.stroke_enabled {
+                    render_pass
+                        .set_vertex_buffer(0, vector_tile_drawable.fill_vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(
+                        vector_tile_drawable.fill_index_buffer.slice(..),
+                        vector_tile_drawable.fill_index_format,
+                    );
+                }
 
-                            render_pass.set_bind_group(0, &map_view_bg, &[]);
-                            render_pass.set_bind_group(1, &shape_fill_params_bg, &[]);
-
-                            for feature_meta in &vector_tile_drawable.shape_metas {
-                                if !feature_meta.shape_is_points
-                                    && !feature_meta.shape_is_lines
-                                    && feature_meta.shape_styles_index == shape_styles_index
-                                {
-                                    for (index_start, index_end, base_vertex) in
-                                        &feature_meta.fill_buffer_index
-                                    {
-                                        render_pass.draw_indexed(
-                                            *index_start..*index_end,
-                                            *base_vertex,
-                                            0..1,
-                                        );
-                                    }
-                                }
+                // draw fills
+                if shape_styles.fill_enabled {
+                    render_pass.set_pipeline(shape_fill_pipeline);
+
+                    let pattern = shape_styles
+                        .fill_pattern
+                        .as_ref()
+                        .and_then(|name| map_renderer.sprite_atlas.icon(name));
+
+                    // Patterns are anchored to map coords to continue across tiles
+                    let pattern_transform = match (pattern, &tile_bbox) {
+                        (Some(icon), Some(tile_bbox)) => {
+                            let scale = (vt_pixel_ratio / icon.pixel_ratio) as f64;
+                            fill_pattern_transform(
+                                Coord {
+                                    x: tile_bbox.min().x,
+                                    y: tile_bbox.max().y,
+                                },
+                                [icon.width as f64 * scale, icon.height as f64 * scale],
+                                tile_bbox.width() / 4096.0,
+                                true,
+                            )
+                        }
+                        _ => [0.0; 4],
+                    };
+
+                    let fill_params = |gradient_transform| {
+                        shape_fill_params(
+                            vector_tile_drawable.z,
+                            &origin,
+                            shape_styles,
+                            pattern,
+                            pattern_transform,
+                            gradient_transform,
+                        )
+                    };
+                    let shape_fill_params_bg = UniformBindGroup::update_or_create(
+                        cache_slot(&mut bind_groups.fill_params, group_index),
+                        rendering_context,
+                        &bind_group_layouts.shape_fill_params,
+                        "Shape Fill Params",
+                        fill_params([0.0; 4]),
+                    );
+
+                    // Gradients are relative to each feature
+                    let gradient_per_feature =
+                        pattern.is_none() && shape_styles.fill_gradient.is_some();
+                    let gradient_fill_params =
+                        cache_slot(&mut bind_groups.gradient_fill_params, group_index)
+                            .get_or_insert_with(Vec::new);
+
+                    render_pass.set_bind_group(0, map_view_bg, &[]);
+                    render_pass.set_bind_group(1, shape_fill_params_bg, &[]);
+                    render_pass.set_bind_group(2, map_renderer.sprite_atlas.bind_group(), &[]);
+
+                    for (i, shape_meta) in shape_metas.iter().enumerate() {
+                        if !shape_meta.shape_is_points && !shape_meta.shape_is_lines {
+                            if let Some(fill_bbox) =
+                                shape_meta.fill_bbox.filter(|_| gradient_per_feature)
+                            {
+                                let shape_fill_params_bg = UniformBindGroup::update_or_create(
+                                    cache_slot(gradient_fill_params, i),
+                                    rendering_context,
+                                    &bind_group_layouts.shape_fill_params,
+                                    "Shape Fill Params",
+                                    fill_params(fill_gradient_transform(
+                                        [0.0, 0.0],
+                                        fill_bbox,
+                                        true,
+                                    )),
+                                );
+                                render_pass.set_bind_group(1, shape_fill_params_bg, &[]);
                             }
+
+                            draw_buffer_index(&mut render_pass, &shape_meta.fill_buffer_index);
                         }
                     }
                 }
 
-                {
-                    render_pass.set_pipeline(&symbol_circle_pipeline);
+                // draw symbols
+                if shape_styles.fill_enabled || shape_styles.stroke_enabled {
+                    render_pass.set_pipeline(symbol_shape_pipeline);
+
+                    let symbol_shape_params_bg = UniformBindGroup::update_or_create(
+                        cache_slot(&mut bind_groups.symbol_shape_params, group_index),
+                        rendering_context,
+                        &bind_group_layouts.symbol_shape_params,
+                        "Symbol Shape Params",
+                        symbol_shape_params(
+                            vt_pixel_ratio,
+                            vector_tile_drawable.z,
+                            &origin,
+                            shape_styles,
+                        ),
+                    );
+
+                    render_pass.set_bind_group(0, map_view_bg, &[]);
+                    render_pass.set_bind_group(1, symbol_shape_params_bg, &[]);
+
+                    for shape_meta in shape_metas {
+                        if shape_meta.shape_is_points {
+                            draw_buffer_index(&mut render_pass, &shape_meta.fill_buffer_index);
+                        }
+                    }
+                }
+
+                // draw strokes
+                if shape_styles.stroke_enabled {
+                    render_pass
+                        .set_vertex_buffer(0, vector_tile_drawable.stroke_vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(
+                        vector_tile_drawable.stroke_index_buffer.slice(..),
+                        vector_tile_drawable.stroke_index_format,
+                    );
+                    render_pass.set_pipeline(shape_stroke_pipeline);
+
+                    let pattern = shape_styles
+                        .stroke_pattern
+                        .as_ref()
+                        .and_then(|name| map_renderer.sprite_atlas.icon(name));
 
-                    for (shape_styles_index, (_, shape_styles)) in
-                        vector_tile_drawable.layers_shape_styles.iter().enumerate()
                     {
-                        if shape_styles.fill_enabled {
-                            let symbol_circle_params_bg = create_symbol_circle_params_bg(
-                                device,
+                        let shape_stroke_params_bg = UniformBindGroup::update_or_create(
+                            cache_slot(&mut bind_groups.line_stroke_params, group_index),
+                            rendering_context,
+                            &bind_group_layouts.shape_stroke_params,
+                            "Shape Stroke Params",
+                            shape_stroke_params(
                                 vt_pixel_ratio,
-                                &symbol_circle_params_bgl,
                                 vector_tile_drawable.z,
-                                &shape_styles,
-                            );
-
-                            render_pass.set_bind_group(0, &map_view_bg, &[]);
-                            render_pass.set_bind_group(1, &symbol_circle_params_bg, &[]);
-
-                            for feature_meta in &vector_tile_drawable.shape_metas {
-                                if feature_meta.shape_is_points
-                                    && feature_meta.shape_styles_index == shape_styles_index
-                                {
-                                    for (index_start, index_end, base_vertex) in
-                                        &feature_meta.fill_buffer_index
-                                    {
-                                        render_pass.draw_indexed(
-                                            *index_start..*index_end,
-                                            *base_vertex,
-                                            0..1,
-                                        );
-                                    }
-                                }
+                                &origin,
+                                0,
+                                shape_styles,
+                                pattern,
+                            ),
+                        );
+
+                        render_pass.set_bind_group(0, map_view_bg, &[]);
+                        render_pass.set_bind_group(1, shape_stroke_params_bg, &[]);
+                        render_pass.set_bind_group(2, map_renderer.sprite_atlas.bind_group(), &[]);
+
+                        for shape_meta in shape_metas {
+                            if shape_meta.shape_is_lines {
+                                draw_buffer_index(
+                                    &mut render_pass,
+                                    &shape_meta.stroke_buffer_index,
+                                );
                             }
                         }
                     }
-                }
-            }
 
-            // draw strokes
-            {
-                render_pass
-                    .set_vertex_buffer(0, vector_tile_drawable.stroke_vertex_buffer.slice(..));
-                render_pass.set_index_buffer(
-                    vector_tile_drawable.stroke_index_buffer.slice(..),
-                    IndexFormat::Uint16,
-                );
-
-                {
-                    render_pass.set_pipeline(&shape_stroke_pipeline);
-
-                    for (shape_styles_index, (_, shape_styles)) in
-                        vector_tile_drawable.layers_shape_styles.iter().enumerate()
                     {
-                        if shape_styles.stroke_enabled {
-                            {
-                                let shape_stroke_params_bg = create_shape_stroke_params_bg(
-                                    device,
-                                    vt_pixel_ratio,
-                                    &shape_stroke_params_bgl,
-                                    vector_tile_drawable.z,
-                                    0,
-                                    &shape_styles,
-                                );
-
-                                render_pass.set_bind_group(0, &map_view_bg, &[]);
-                                render_pass.set_bind_group(1, &shape_stroke_params_bg, &[]);
-
-                                for feature_meta in &vector_tile_drawable.shape_metas {
-                                    if feature_meta.shape_is_lines
-                                        && feature_meta.shape_styles_index == shape_styles_index
-                                    {
-                                        for (index_start, index_end, base_vertex) in
-                                            &feature_meta.stroke_buffer_index
-                                        {
-                                            render_pass.draw_indexed(
-                                                *index_start..*index_end,
-                                                *base_vertex,
-                                                0..1,
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-
-                            {
-                                let align = if shape_styles.outline_align == OutlineAlign::Center {
-                                    0
-                                } else {
-                                    1
-                                };
-                                let shape_stroke_params_bg = create_shape_stroke_params_bg(
-                                    device,
-                                    vt_pixel_ratio,
-                                    &shape_stroke_params_bgl,
-                                    vector_tile_drawable.z,
-                                    align,
-                                    &shape_styles,
+                        let align = if shape_styles.outline_align == OutlineAlign::Center {
+                            0
+                        } else {
+                            1
+                        };
+                        let shape_stroke_params_bg = UniformBindGroup::update_or_create(
+                            cache_slot(&mut bind_groups.outline_stroke_params, group_index),
+                            rendering_context,
+                            &bind_group_layouts.shape_stroke_params,
+                            "Shape Stroke Params",
+                            shape_stroke_params(
+                                vt_pixel_ratio,
+                                vector_tile_drawable.z,
+                                &origin,
+                                align,
+                                shape_styles,
+                                pattern,
+                            ),
+                        );
+
+                        render_pass.set_bind_group(0, map_view_bg, &[]);
+                        render_pass.set_bind_group(1, shape_stroke_params_bg, &[]);
+                        render_pass.set_bind_group(2, map_renderer.sprite_atlas.bind_group(), &[]);
+
+                        for shape_meta in shape_metas {
+                            if !shape_meta.shape_is_lines {
+                                draw_buffer_index(
+                                    &mut render_pass,
+                                    &shape_meta.stroke_buffer_index,
                                 );
-
-                                render_pass.set_bind_group(0, &map_view_bg, &[]);
-                                render_pass.set_bind_group(1, &shape_stroke_params_bg, &[]);
-
-                                for feature_meta in &vector_tile_drawable.shape_metas {
-                                    if feature_meta.shape_styles_index == shape_styles_index {
-                                        for (index_start, index_end, base_vertex) in
-                                            &feature_meta.stroke_buffer_index
-                                        {
-                                            render_pass.draw_indexed(
-                                                *index_start..*index_end,
-                                                *base_vertex,
-                                                0..1,
-                                            );
-                                        }
-                                    }
-                                }
                             }
                         }
                     }
@@ -592,9 +1021,84 @@ impl VectorTileRenderer {
 
         queue.submit(Some(command_encoder.finish()));
 
+        vector_tile_drawable.tile_bind_groups = bind_groups;
+
         log::info!(
             "VectorTileRenderer::render elapsed: {:?}",
             instant.elapsed()
         );
     }
 }
+
+/// Map states to draw the world copies in view with, a copy is drawn by shifting the center the
+/// opposite way.
+fn world_copy_states(map_options: &MapOptions, map_state: &MapState) -> Vec<MapState> {
+    let tiling = &map_options.tiling;
+
+    match map_state.view_bounds().bounding_rect() {
+        Some(view_rect) if map_options.world_copy => tiling
+            .world_copies(&view_rect)
+            .map(|copy| {
+                let mut copy_state = map_state.clone();
+                copy_state.center.x -= copy as f64 * tiling.map_size();
+                copy_state
+            })
+            .collect(),
+        _ => vec![map_state.clone()],
+    }
+}
+
+/// Slot of a cached item, growing the cache as needed.
+fn cache_slot<T>(cache: &mut Vec<Option<T>>, index: usize) -> &mut Option<T> {
+    if cache.len() <= index {
+        cache.resize_with(index + 1, || None);
+    }
+
+    &mut cache[index]
+}
+
+/// Group shapes of a vector tile by their styles, resolving data driven styles per feature.
+fn group_shape_metas(
+    vector_tile_drawable: &VectorTileDrawable,
+    zoom: f64,
+) -> Vec<(ShapeStyles, Vec<&VectorTileShapeMeta>)> {
+    let mut groups: Vec<(ShapeStyles, Vec<&VectorTileShapeMeta>)> = Vec::new();
+
+    for (shape_styles_index, (_, shape_styles)) in
+        vector_tile_drawable.layers_shape_styles.iter().enumerate()
+    {
+        let shape_metas = vector_tile_drawable
+            .shape_metas
+            .iter()
+            .filter(|shape_meta| shape_meta.shape_styles_index == shape_styles_index);
+
+        if !shape_styles.is_data_driven() {
+            if shape_styles.visible {
+                groups.push((shape_styles.clone(), shape_metas.collect()));
+            }
+            continue;
+        }
+
+        let groups_start = groups.len();
+        for shape_meta in shape_metas {
+            let resolved = shape_styles.resolve(
+                &EvaluationContext::new(&shape_meta.attrs)
+                    .with_geometry_type(shape_meta.geometry_type)
+                    .with_zoom(zoom),
+            );
+            if !resolved.visible {
+                continue;
+            }
+
+            match groups[groups_start..]
+                .iter_mut()
+                .find(|(group_styles, _)| *group_styles == resolved)
+            {
+                Some((_, group_metas)) => group_metas.push(shape_meta),
+                None => groups.push((resolved, vec![shape_meta])),
+            }
+        }
+    }
+
+    groups
+}
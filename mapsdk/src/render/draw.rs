@@ -1,11 +1,16 @@
+use geo::Coord;
 use wgpu::RenderPass;
 
 use crate::render::{
-    draw::{feature::FeatureDrawable, image::ImageDrawable, vector_tile::VectorTileDrawable},
+    draw::{
+        feature::FeatureDrawable, feature_batch::FeatureBatchDrawable, image::ImageDrawable,
+        vector_tile::VectorTileDrawable,
+    },
     InterRenderers, MapOptions, MapRenderer, MapState,
 };
 
 pub(crate) mod feature;
+pub(crate) mod feature_batch;
 pub(crate) mod image;
 pub(crate) mod vector_tile;
 
@@ -21,9 +26,10 @@ pub(crate) trait Drawable {
 }
 
 pub enum DrawItem {
-    Feature(FeatureDrawable),
-    Image(ImageDrawable),
-    VectorTile(VectorTileDrawable),
+    Feature(Box<FeatureDrawable>),
+    FeatureBatch(Box<FeatureBatchDrawable>),
+    Image(Box<ImageDrawable>),
+    VectorTile(Box<VectorTileDrawable>),
 }
 
 impl DrawItem {
@@ -43,6 +49,13 @@ impl DrawItem {
                 inter_renderers,
                 render_pass,
             ),
+            Self::FeatureBatch(drawable) => drawable.draw(
+                map_options,
+                map_state,
+                map_renderer,
+                inter_renderers,
+                render_pass,
+            ),
             Self::Image(drawable) => drawable.draw(
                 map_options,
                 map_state,
@@ -60,3 +73,106 @@ impl DrawItem {
         }
     }
 }
+
+/// Transform from vertex coords relative to the map center to pattern repeats of `period` vertex
+/// units, anchored to map coords so patterns don't move while panning. `center` is the map coord
+/// of the map center and `map_units` the map units per vertex unit.
+pub(crate) fn fill_pattern_transform(
+    center: Coord,
+    period: [f64; 2],
+    map_units: f64,
+    y_down: bool,
+) -> [f32; 4] {
+    // Pattern rows go down, the phase at the center is taken from map coords in f64
+    let phase_x = (center.x / (period[0] * map_units)).rem_euclid(1.0);
+    let phase_y = (-center.y / (period[1] * map_units)).rem_euclid(1.0);
+    let y_sign = if y_down { 1.0 } else { -1.0 };
+
+    [
+        phase_x as f32,
+        phase_y as f32,
+        (1.0 / period[0]) as f32,
+        (y_sign / period[1]) as f32,
+    ]
+}
+
+/// Transform from vertex coords relative to the map center to a bounding box, from `[0, 0]` at
+/// the top left to `[1, 1]` at the bottom right.
+pub(crate) fn fill_gradient_transform(center: [f32; 2], bbox: [f32; 4], y_down: bool) -> [f32; 4] {
+    let [xmin, ymin, xmax, ymax] = bbox.map(|v| v as f64);
+    let [x, y] = center.map(|v| v as f64);
+    let width = (xmax - xmin).max(f64::EPSILON);
+    let height = (ymax - ymin).max(f64::EPSILON);
+
+    if y_down {
+        [
+            ((x - xmin) / width) as f32,
+            ((y - ymin) / height) as f32,
+            (1.0 / width) as f32,
+            (1.0 / height) as f32,
+        ]
+    } else {
+        [
+            ((x - xmin) / width) as f32,
+            ((ymax - y) / height) as f32,
+            (1.0 / width) as f32,
+            (-1.0 / height) as f32,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_pattern_transform() {
+        let pattern_coord = |transform: [f32; 4], coord: [f32; 2]| {
+            [
+                coord[0] * transform[2] + transform[0],
+                coord[1] * transform[3] + transform[1],
+            ]
+        };
+        let same_phase = |a: f32, b: f32| ((a - b) - (a - b).round()).abs() < 1e-5;
+
+        // Panning keeps the pattern at the same map coords
+        let a = fill_pattern_transform(
+            Coord {
+                x: 1000.0,
+                y: 500.0,
+            },
+            [16.0, 8.0],
+            1.0,
+            false,
+        );
+        let b = fill_pattern_transform(
+            Coord {
+                x: 1012.0,
+                y: 498.0,
+            },
+            [16.0, 8.0],
+            1.0,
+            false,
+        );
+        let [ua, va] = pattern_coord(a, [20.0, 6.0]);
+        let [ub, vb] = pattern_coord(b, [8.0, 8.0]);
+        assert!(same_phase(ua, ub) && same_phase(va, vb));
+
+        // Tiles with y down continue the pattern of their neighbors
+        let left = fill_pattern_transform(Coord { x: 0.0, y: 64.0 }, [16.0, 16.0], 2.0, true);
+        let right = fill_pattern_transform(Coord { x: 48.0, y: 64.0 }, [16.0, 16.0], 2.0, true);
+        let [ul, vl] = pattern_coord(left, [30.0, 10.0]);
+        let [ur, vr] = pattern_coord(right, [6.0, 10.0]);
+        assert!(same_phase(ul, ur) && same_phase(vl, vr));
+    }
+
+    #[test]
+    fn test_fill_gradient_transform() {
+        let transform = fill_gradient_transform([5.0, 5.0], [0.0, 0.0, 10.0, 20.0], false);
+        // Top left and bottom right relative to the center
+        assert_eq!(-5.0 * transform[2] + transform[0], 0.0);
+        assert_eq!(15.0 * transform[3] + transform[1], 0.0);
+        assert_eq!(5.0 * transform[2] + transform[0], 1.0);
+        assert_eq!(-5.0 * transform[3] + transform[1], 1.0);
+    }
+}
@@ -1,15 +1,17 @@
+use geo::{Coord, MapCoords};
 use wgpu::*;
 
 use crate::{
-    feature::{style::ShapeStyles, Feature, Shape},
+    feature::{
+        style::{expression::EvaluationContext, RotationAlignment, ShapeStyles},
+        Feature, Shape,
+    },
     render::{
-        create_symbol_circle_params_bg, create_symbol_circle_params_bgl,
-        draw::Drawable,
+        draw::{fill_gradient_transform, fill_pattern_transform, Drawable},
         resources::{
             bind_group::{
-                create_map_view_bg, create_map_view_bgl, create_shape_fill_params_bg,
-                create_shape_fill_params_bgl, create_shape_stroke_params_bg,
-                create_shape_stroke_params_bgl,
+                shape_fill_params, shape_stroke_params, symbol_icon_params, symbol_shape_params,
+                UniformBindGroup,
             },
             buffer::VertexIndexBuffer,
         },
@@ -21,10 +23,21 @@ use crate::{
 pub struct FeatureDrawable {
     feature: Feature,
     z: f32,
-    shape_styles: ShapeStyles,
+    shape_styles: Box<ShapeStyles>,
+    resolved_shape_styles: Box<ShapeStyles>,
+    resolved_zoom: Option<f64>,
 
+    /// Origin of the vertex coords, see [`crate::render::resources::bind_group::map_view`].
+    origin: Coord,
+    fill_bbox: Option<[f32; 4]>,
     fill_buffers: Vec<VertexIndexBuffer>,
     stroke_buffers: Vec<VertexIndexBuffer>,
+
+    /// Created on first draw and rewritten when the view or styles change.
+    fill_params: Option<UniformBindGroup>,
+    stroke_params: Option<UniformBindGroup>,
+    symbol_shape_params: Option<UniformBindGroup>,
+    symbol_icon_params: Option<UniformBindGroup>,
 }
 
 impl FeatureDrawable {
@@ -36,9 +49,12 @@ impl FeatureDrawable {
     ) -> Self {
         let MapRenderingContext { device, .. } = &map_renderer.rendering_context;
 
+        let origin = feature.shape().bbox().center();
         let tessellations = match feature.shape() {
-            Shape::Circle { center, radius } => tessellate_circle(center, *radius as f32, 6),
-            Shape::Geometry(geom) => tessellate_geometry(&geom),
+            Shape::Circle { center, radius } => {
+                tessellate_circle(&(*center - origin), *radius as f32, 6)
+            }
+            Shape::Geometry(geom) => tessellate_geometry(&geom.map_coords(|coord| coord - origin)),
         };
 
         let mut fill_buffers: Vec<VertexIndexBuffer> = Vec::new();
@@ -58,10 +74,19 @@ impl FeatureDrawable {
         Self {
             feature: feature.clone(),
             z: z as f32,
-            shape_styles: shape_styles.clone(),
+            shape_styles: Box::new(shape_styles.clone()),
+            resolved_shape_styles: Box::new(shape_styles.clone()),
+            resolved_zoom: None,
 
+            origin,
+            fill_bbox: tessellations.fill_bbox(),
             fill_buffers,
             stroke_buffers,
+
+            fill_params: None,
+            stroke_params: None,
+            symbol_shape_params: None,
+            symbol_icon_params: None,
         }
     }
 }
@@ -69,91 +94,197 @@ impl FeatureDrawable {
 impl Drawable for FeatureDrawable {
     fn draw(
         &mut self,
-        _map_options: &MapOptions,
+        map_options: &MapOptions,
         map_state: &MapState,
         map_renderer: &MapRenderer,
         _inter_renderers: &InterRenderers,
         render_pass: &mut RenderPass,
     ) {
+        let zoom = map_options.tiling.zoom_for_resolution(map_state.zoom_res);
+        if self.resolved_zoom.is_none()
+            || (self.resolved_zoom != Some(zoom) && self.shape_styles.is_zoom_dependent())
+        {
+            *self.resolved_shape_styles = self.shape_styles.resolve(
+                &EvaluationContext::new(self.feature.attrs())
+                    .with_geometry_type(self.feature.shape().geometry_type())
+                    .with_zoom(zoom),
+            );
+            self.resolved_zoom = Some(zoom);
+        }
+
+        let shape_styles = &self.resolved_shape_styles;
+        if !shape_styles.visible {
+            return;
+        }
+
+        let rendering_context = &map_renderer.rendering_context;
         let MapRenderingContext {
-            device,
             pixel_ratio,
+            bind_group_layouts,
+            map_view_bg,
 
             shape_fill_pipeline,
             shape_stroke_pipeline,
-            symbol_circle_pipeline,
+            symbol_shape_pipeline,
+            symbol_icon_pipeline,
             ..
-        } = &map_renderer.rendering_context;
-
-        let map_view_bgl = create_map_view_bgl(device);
-        let map_view_bg =
-            create_map_view_bg(device, &map_view_bgl, &map_renderer.camera, &map_state);
-
-        if self.feature.shape().is_points() {
-            let symbol_circle_params_bgl = create_symbol_circle_params_bgl(device);
-            let symbol_circle_params_bg = create_symbol_circle_params_bg(
-                device,
-                *pixel_ratio as f32,
-                &symbol_circle_params_bgl,
-                self.z,
-                &self.shape_styles,
+        } = rendering_context;
+
+        // Points are drawn as icons if the icon is added, otherwise as marker shapes
+        let icon = match &shape_styles.icon {
+            Some(icon_style) if self.feature.shape().is_points() => map_renderer
+                .sprite_atlas
+                .icon(&icon_style.name)
+                .map(|sprite_icon| (icon_style, sprite_icon)),
+            _ => None,
+        };
+
+        if let Some((icon_style, sprite_icon)) = icon {
+            let rotation = match icon_style.rotation_alignment {
+                RotationAlignment::Map => -icon_style.rotation.to_radians(),
+                RotationAlignment::Screen => {
+                    (map_state.yaw as f32 - icon_style.rotation).to_radians()
+                }
+            };
+
+            let symbol_icon_params_bg = UniformBindGroup::update_or_create(
+                &mut self.symbol_icon_params,
+                rendering_context,
+                &bind_group_layouts.symbol_icon_params,
+                "Symbol Icon Params",
+                symbol_icon_params(
+                    *pixel_ratio as f32,
+                    self.z,
+                    &self.origin,
+                    rotation,
+                    icon_style,
+                    sprite_icon,
+                ),
+            );
+
+            for fill_buffer in &self.fill_buffers {
+                render_pass.set_pipeline(symbol_icon_pipeline);
+                render_pass.set_bind_group(0, map_view_bg, &[]);
+                render_pass.set_bind_group(1, map_renderer.sprite_atlas.bind_group(), &[]);
+                render_pass.set_bind_group(2, symbol_icon_params_bg, &[]);
+                render_pass.set_vertex_buffer(0, fill_buffer.vertex_buffer.slice(..));
+                render_pass
+                    .set_index_buffer(fill_buffer.index_buffer.slice(..), fill_buffer.index_format);
+                render_pass.draw_indexed(0..fill_buffer.index_count, 0, 0..1);
+            }
+        } else if self.feature.shape().is_points() {
+            if !shape_styles.fill_enabled && !shape_styles.stroke_enabled {
+                return;
+            }
+
+            let symbol_shape_params_bg = UniformBindGroup::update_or_create(
+                &mut self.symbol_shape_params,
+                rendering_context,
+                &bind_group_layouts.symbol_shape_params,
+                "Symbol Shape Params",
+                symbol_shape_params(*pixel_ratio as f32, self.z, &self.origin, shape_styles),
             );
 
             for fill_buffer in &self.fill_buffers {
-                render_pass.set_pipeline(&symbol_circle_pipeline);
-                render_pass.set_bind_group(0, &map_view_bg, &[]);
-                render_pass.set_bind_group(1, &symbol_circle_params_bg, &[]);
+                render_pass.set_pipeline(symbol_shape_pipeline);
+                render_pass.set_bind_group(0, map_view_bg, &[]);
+                render_pass.set_bind_group(1, symbol_shape_params_bg, &[]);
                 render_pass.set_vertex_buffer(0, fill_buffer.vertex_buffer.slice(..));
                 render_pass
-                    .set_index_buffer(fill_buffer.index_buffer.slice(..), IndexFormat::Uint16);
+                    .set_index_buffer(fill_buffer.index_buffer.slice(..), fill_buffer.index_format);
                 render_pass.draw_indexed(0..fill_buffer.index_count, 0, 0..1);
             }
         } else {
-            if self.shape_styles.fill_enabled {
-                let shape_fill_params_bgl = create_shape_fill_params_bgl(device);
-                let shape_fill_params_bg = create_shape_fill_params_bg(
-                    device,
-                    &shape_fill_params_bgl,
-                    self.z,
-                    &self.shape_styles,
+            if shape_styles.fill_enabled {
+                let pattern = shape_styles
+                    .fill_pattern
+                    .as_ref()
+                    .and_then(|name| map_renderer.sprite_atlas.icon(name));
+
+                let pattern_transform = pattern.map_or([0.0; 4], |icon| {
+                    let scale = pixel_ratio * map_state.zoom_res * map_state.map_res_ratio
+                        / icon.pixel_ratio as f64;
+                    fill_pattern_transform(
+                        map_state.center,
+                        [icon.width as f64 * scale, icon.height as f64 * scale],
+                        1.0,
+                        false,
+                    )
+                });
+
+                let map_center = [
+                    (map_state.center.x - self.origin.x) as f32,
+                    (map_state.center.y - self.origin.y) as f32,
+                ];
+                let gradient_transform = self.fill_bbox.map_or([0.0; 4], |bbox| {
+                    fill_gradient_transform(map_center, bbox, false)
+                });
+
+                let shape_fill_params_bg = UniformBindGroup::update_or_create(
+                    &mut self.fill_params,
+                    rendering_context,
+                    &bind_group_layouts.shape_fill_params,
+                    "Shape Fill Params",
+                    shape_fill_params(
+                        self.z,
+                        &self.origin,
+                        shape_styles,
+                        pattern,
+                        pattern_transform,
+                        gradient_transform,
+                    ),
                 );
 
                 for fill_buffer in &self.fill_buffers {
                     render_pass.set_pipeline(&shape_fill_pipeline);
-                    render_pass.set_bind_group(0, &map_view_bg, &[]);
-                    render_pass.set_bind_group(1, &shape_fill_params_bg, &[]);
+                    render_pass.set_bind_group(0, map_view_bg, &[]);
+                    render_pass.set_bind_group(1, shape_fill_params_bg, &[]);
+                    render_pass.set_bind_group(2, map_renderer.sprite_atlas.bind_group(), &[]);
                     render_pass.set_vertex_buffer(0, fill_buffer.vertex_buffer.slice(..));
-                    render_pass
-                        .set_index_buffer(fill_buffer.index_buffer.slice(..), IndexFormat::Uint16);
+                    render_pass.set_index_buffer(
+                        fill_buffer.index_buffer.slice(..),
+                        fill_buffer.index_format,
+                    );
                     render_pass.draw_indexed(0..fill_buffer.index_count, 0, 0..1);
                 }
             }
 
-            if self.shape_styles.stroke_enabled {
+            if shape_styles.stroke_enabled {
                 let align = if self.feature.shape().is_lines() {
                     0
                 } else {
                     1
                 };
 
-                let shape_stroke_params_bgl = create_shape_stroke_params_bgl(device);
-                let shape_stroke_params_bg = create_shape_stroke_params_bg(
-                    device,
-                    *pixel_ratio as f32,
-                    &shape_stroke_params_bgl,
-                    self.z,
-                    align,
-                    &self.shape_styles,
+                let pattern = shape_styles
+                    .stroke_pattern
+                    .as_ref()
+                    .and_then(|name| map_renderer.sprite_atlas.icon(name));
+
+                let shape_stroke_params_bg = UniformBindGroup::update_or_create(
+                    &mut self.stroke_params,
+                    rendering_context,
+                    &bind_group_layouts.shape_stroke_params,
+                    "Shape Stroke Params",
+                    shape_stroke_params(
+                        *pixel_ratio as f32,
+                        self.z,
+                        &self.origin,
+                        align,
+                        shape_styles,
+                        pattern,
+                    ),
                 );
 
                 for stroke_buffer in &self.stroke_buffers {
                     render_pass.set_pipeline(&shape_stroke_pipeline);
-                    render_pass.set_bind_group(0, &map_view_bg, &[]);
-                    render_pass.set_bind_group(1, &shape_stroke_params_bg, &[]);
+                    render_pass.set_bind_group(0, map_view_bg, &[]);
+                    render_pass.set_bind_group(1, shape_stroke_params_bg, &[]);
+                    render_pass.set_bind_group(2, map_renderer.sprite_atlas.bind_group(), &[]);
                     render_pass.set_vertex_buffer(0, stroke_buffer.vertex_buffer.slice(..));
                     render_pass.set_index_buffer(
                         stroke_buffer.index_buffer.slice(..),
-                        IndexFormat::Uint16,
+                        stroke_buffer.index_format,
                     );
                     render_pass.draw_indexed(0..stroke_buffer.index_count, 0, 0..1);
                 }
@@ -164,6 +295,6 @@ impl Drawable for FeatureDrawable {
 
 impl Into<DrawItem> for FeatureDrawable {
     fn into(self) -> DrawItem {
-        DrawItem::Feature(self)
+        DrawItem::Feature(Box::new(self))
     }
 }
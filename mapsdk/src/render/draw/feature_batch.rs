@@ -0,0 +1,245 @@
+use geo::Coord;
+use wgpu::*;
+
+use crate::{
+    feature::{
+        style::{expression::EvaluationContext, ShapeStyles},
+        Feature,
+    },
+    render::{
+        draw::Drawable,
+        resources::{
+            bind_group::{create_shape_batch_params_bg, shape_batch_feature_style},
+            buffer::{
+                create_index_buffer, create_storage_buffer_from_vec4_f32_slice,
+                create_vertex_buffer_from_u32_slice, create_vertex_buffer_from_vec2_f32_slice,
+                create_vertex_buffer_from_vec8_f32_slice,
+            },
+        },
+        tessellation::feature_batch::FeatureBatchTessellation,
+        DrawItem, InterRenderers, MapOptions, MapRenderer, MapRenderingContext, MapState,
+    },
+};
+
+/// Polygons and lines of features sharing styles, drawn with one draw call for all fills and one
+/// for all strokes. Data driven styles are resolved per feature into a storage buffer, which is
+/// only rewritten when the resolved styles may change.
+pub struct FeatureBatchDrawable {
+    features: Vec<Feature>,
+    shape_styles: Box<ShapeStyles>,
+    resolved_zoom: Option<f64>,
+    resolved_pixel_ratio: f64,
+
+    /// Vertex coords of each feature are relative to its origin, see [`FeatureBatchTessellation`].
+    origins: Vec<Coord>,
+    fill_vertex_buffer: Buffer,
+    fill_feature_index_buffer: Buffer,
+    fill_index_buffer: Buffer,
+    fill_index_format: IndexFormat,
+    fill_index_count: u32,
+    stroke_vertex_buffer: Buffer,
+    stroke_feature_index_buffer: Buffer,
+    stroke_index_buffer: Buffer,
+    stroke_index_format: IndexFormat,
+    stroke_index_count: u32,
+
+    feature_styles_buffer: Buffer,
+    /// None if the device does not support feature batches.
+    params_bg: Option<BindGroup>,
+}
+
+impl FeatureBatchDrawable {
+    /// Whether features can be batched with these styles, patterns, gradients, dashes and miter
+    /// limits varying by feature or zoom are only drawn by [`super::feature::FeatureDrawable`].
+    pub fn supports(shape_styles: &ShapeStyles) -> bool {
+        shape_styles.fill_pattern.is_none()
+            && shape_styles.fill_gradient.is_none()
+            && shape_styles.dash_array.is_none()
+            && shape_styles.stroke_pattern.is_none()
+            && shape_styles.expressions.miter_limit.is_none()
+    }
+
+    /// Features must not be points, see [`Self::supports`] for the styles.
+    pub fn new(
+        map_renderer: &MapRenderer,
+        features: Vec<Feature>,
+        z: f64,
+        shape_styles: &ShapeStyles,
+    ) -> Self {
+        let MapRenderingContext {
+            device,
+            bind_group_layouts,
+            allocation_counter,
+            ..
+        } = &map_renderer.rendering_context;
+
+        let FeatureBatchTessellation {
+            fill_vertices,
+            fill_feature_indices,
+            fill_indices,
+            stroke_vertices,
+            stroke_feature_indices,
+            stroke_indices,
+
+            origins,
+        } = FeatureBatchTessellation::new(&features);
+
+        let fill_vertex_buffer =
+            create_vertex_buffer_from_vec2_f32_slice(device, "Fill VertexBuffer", &fill_vertices);
+        let fill_feature_index_buffer = create_vertex_buffer_from_u32_slice(
+            device,
+            "Fill Feature Index VertexBuffer",
+            &fill_feature_indices,
+        );
+        let (fill_index_buffer, fill_index_format) =
+            create_index_buffer(device, "Fill IndexBuffer", &fill_indices);
+
+        let stroke_vertex_buffer = create_vertex_buffer_from_vec8_f32_slice(
+            device,
+            "Stroke VertexBuffer",
+            &stroke_vertices,
+        );
+        let stroke_feature_index_buffer = create_vertex_buffer_from_u32_slice(
+            device,
+            "Stroke Feature Index VertexBuffer",
+            &stroke_feature_indices,
+        );
+        let (stroke_index_buffer, stroke_index_format) =
+            create_index_buffer(device, "Stroke IndexBuffer", &stroke_indices);
+
+        // Written with the resolved styles on the first draw
+        let feature_styles_buffer = create_storage_buffer_from_vec4_f32_slice(
+            device,
+            "Feature Styles Buffer",
+            &vec![[0.0; 4]; features.len().max(1) * 4],
+        );
+
+        let params_bg = bind_group_layouts
+            .shape_batch_params
+            .as_ref()
+            .map(|params_bgl| {
+                create_shape_batch_params_bg(
+                    device,
+                    allocation_counter,
+                    params_bgl,
+                    z as f32,
+                    shape_styles,
+                    &feature_styles_buffer,
+                )
+            });
+
+        Self {
+            features,
+            shape_styles: Box::new(shape_styles.clone()),
+            resolved_zoom: None,
+            resolved_pixel_ratio: 0.0,
+
+            origins,
+            fill_vertex_buffer,
+            fill_feature_index_buffer,
+            fill_index_buffer,
+            fill_index_format,
+            fill_index_count: fill_indices.len() as u32,
+            stroke_vertex_buffer,
+            stroke_feature_index_buffer,
+            stroke_index_buffer,
+            stroke_index_format,
+            stroke_index_count: stroke_indices.len() as u32,
+
+            feature_styles_buffer,
+            params_bg,
+        }
+    }
+
+    fn update_feature_styles(&self, queue: &Queue, pixel_ratio: f32, zoom: f64) {
+        let feature_styles: Vec<[f32; 4]> = self
+            .features
+            .iter()
+            .zip(&self.origins)
+            .flat_map(|(feature, origin)| {
+                let shape_styles = self.shape_styles.resolve(
+                    &EvaluationContext::new(feature.attrs())
+                        .with_geometry_type(feature.shape().geometry_type())
+                        .with_zoom(zoom),
+                );
+                let align = if feature.shape().is_lines() { 0 } else { 1 };
+
+                shape_batch_feature_style(&shape_styles, pixel_ratio, align, origin)
+            })
+            .collect();
+
+        queue.write_buffer(
+            &self.feature_styles_buffer,
+            0,
+            bytemuck::cast_slice(&feature_styles),
+        );
+    }
+}
+
+impl Drawable for FeatureBatchDrawable {
+    fn draw(
+        &mut self,
+        map_options: &MapOptions,
+        map_state: &MapState,
+        map_renderer: &MapRenderer,
+        _inter_renderers: &InterRenderers,
+        render_pass: &mut RenderPass,
+    ) {
+        let rendering_context = &map_renderer.rendering_context;
+        let MapRenderingContext {
+            queue,
+            pixel_ratio,
+            map_view_bg,
+
+            shape_fill_batch_pipeline,
+            shape_stroke_batch_pipeline,
+            ..
+        } = rendering_context;
+
+        let (Some(shape_fill_batch_pipeline), Some(shape_stroke_batch_pipeline), Some(params_bg)) = (
+            shape_fill_batch_pipeline,
+            shape_stroke_batch_pipeline,
+            &self.params_bg,
+        ) else {
+            return;
+        };
+
+        let zoom = map_options.tiling.zoom_for_resolution(map_state.zoom_res);
+        // Pixel widths are rewritten when rendering at another pixel ratio, e.g. for an export
+        if self.resolved_zoom.is_none()
+            || (self.resolved_zoom != Some(zoom) && self.shape_styles.is_zoom_dependent())
+            || self.resolved_pixel_ratio != *pixel_ratio
+        {
+            self.update_feature_styles(queue, *pixel_ratio as f32, zoom);
+            self.resolved_zoom = Some(zoom);
+            self.resolved_pixel_ratio = *pixel_ratio;
+        }
+
+        if self.shape_styles.fill_enabled && self.fill_index_count > 0 {
+            render_pass.set_pipeline(shape_fill_batch_pipeline);
+            render_pass.set_bind_group(0, map_view_bg, &[]);
+            render_pass.set_bind_group(1, params_bg, &[]);
+            render_pass.set_vertex_buffer(0, self.fill_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.fill_feature_index_buffer.slice(..));
+            render_pass.set_index_buffer(self.fill_index_buffer.slice(..), self.fill_index_format);
+            render_pass.draw_indexed(0..self.fill_index_count, 0, 0..1);
+        }
+
+        if self.shape_styles.stroke_enabled && self.stroke_index_count > 0 {
+            render_pass.set_pipeline(shape_stroke_batch_pipeline);
+            render_pass.set_bind_group(0, map_view_bg, &[]);
+            render_pass.set_bind_group(1, params_bg, &[]);
+            render_pass.set_vertex_buffer(0, self.stroke_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.stroke_feature_index_buffer.slice(..));
+            render_pass
+                .set_index_buffer(self.stroke_index_buffer.slice(..), self.stroke_index_format);
+            render_pass.draw_indexed(0..self.stroke_index_count, 0, 0..1);
+        }
+    }
+}
+
+impl From<FeatureBatchDrawable> for DrawItem {
+    fn from(drawable: FeatureBatchDrawable) -> Self {
+        DrawItem::FeatureBatch(Box::new(drawable))
+    }
+}
@@ -5,10 +5,7 @@ use wgpu::*;
 use crate::render::{
     draw::Drawable,
     resources::{
-        bind_group::{
-            create_image_params_bg, create_image_params_bgl, create_image_texture_bg,
-            create_image_texture_bgl, create_map_view_bg, create_map_view_bgl,
-        },
+        bind_group::{create_image_texture_bg, image_params, UniformBindGroup},
         buffer::{create_index_buffer_from_u16_slice, create_vertex_buffer_from_vec2_f32_slice},
         texture::create_texture,
     },
@@ -16,16 +13,23 @@ use crate::render::{
 };
 
 pub struct ImageDrawable {
-    z: f32,
-
-    texture_view: TextureView,
     texture_vertex_buffer: Buffer,
     texture_index_buffer: Buffer,
+    texture_bg: BindGroup,
+    /// Vertex coords are relative to the center of the image, passed as origin with the params.
+    image_params: UniformBindGroup,
 }
 
 impl ImageDrawable {
     pub fn new(map_renderer: &MapRenderer, image: &RgbaImage, bbox: &Rect, z: f64) -> Self {
-        let MapRenderingContext { device, queue, .. } = &map_renderer.rendering_context;
+        let MapRenderingContext {
+            device,
+            queue,
+            color_sampler,
+            bind_group_layouts,
+            allocation_counter,
+            ..
+        } = &map_renderer.rendering_context;
 
         let width = image.width();
         let height = image.height();
@@ -47,12 +51,22 @@ impl ImageDrawable {
             texture.size(),
         );
         let texture_view = texture.create_view(&TextureViewDescriptor::default());
+        let texture_bg = create_image_texture_bg(
+            device,
+            allocation_counter,
+            &bind_group_layouts.image_texture,
+            &texture_view,
+            color_sampler,
+        );
 
+        let origin = bbox.center();
+        let half_width = (bbox.width() / 2.0) as f32;
+        let half_height = (bbox.height() / 2.0) as f32;
         let texture_vertices = [
-            [bbox.min().x as f32, bbox.max().y as f32],
-            [bbox.max().x as f32, bbox.max().y as f32],
-            [bbox.min().x as f32, bbox.min().y as f32],
-            [bbox.max().x as f32, bbox.min().y as f32],
+            [-half_width, half_height],
+            [half_width, half_height],
+            [-half_width, -half_height],
+            [half_width, -half_height],
         ];
         let texture_vertex_buffer = create_vertex_buffer_from_vec2_f32_slice(
             &device,
@@ -67,12 +81,18 @@ impl ImageDrawable {
             &texture_indices,
         );
 
-        Self {
-            z: z as f32,
+        let image_params = UniformBindGroup::new(
+            &map_renderer.rendering_context,
+            &bind_group_layouts.image_params,
+            "Image Params",
+            image_params(z as f32, &origin),
+        );
 
-            texture_view,
+        Self {
             texture_vertex_buffer,
             texture_index_buffer,
+            texture_bg,
+            image_params,
         }
     }
 }
@@ -81,37 +101,21 @@ impl Drawable for ImageDrawable {
     fn draw(
         &mut self,
         _map_options: &MapOptions,
-        map_state: &MapState,
+        _map_state: &MapState,
         map_renderer: &MapRenderer,
         _inter_renderers: &InterRenderers,
         render_pass: &mut RenderPass,
     ) {
         let MapRenderingContext {
-            device,
-            color_sampler,
+            map_view_bg,
             image_pipeline,
             ..
         } = &map_renderer.rendering_context;
 
-        let map_view_bgl = create_map_view_bgl(device);
-        let map_view_bg =
-            create_map_view_bg(device, &map_view_bgl, &map_renderer.camera, &map_state);
-
-        let image_texture_bgl = create_image_texture_bgl(device);
-        let image_texture_bg = create_image_texture_bg(
-            device,
-            &image_texture_bgl,
-            &self.texture_view,
-            &color_sampler,
-        );
-
-        let image_params_bgl = create_image_params_bgl(device);
-        let image_params_bg = create_image_params_bg(device, &image_params_bgl, self.z);
-
-        render_pass.set_pipeline(&image_pipeline);
-        render_pass.set_bind_group(0, &map_view_bg, &[]);
-        render_pass.set_bind_group(1, &image_texture_bg, &[]);
-        render_pass.set_bind_group(2, &image_params_bg, &[]);
+        render_pass.set_pipeline(image_pipeline);
+        render_pass.set_bind_group(0, map_view_bg, &[]);
+        render_pass.set_bind_group(1, &self.texture_bg, &[]);
+        render_pass.set_bind_group(2, self.image_params.bind_group(), &[]);
         render_pass.set_vertex_buffer(0, self.texture_vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.texture_index_buffer.slice(..), IndexFormat::Uint16);
         render_pass.draw_indexed(0..4, 0, 0..1);
@@ -120,6 +124,6 @@ impl Drawable for ImageDrawable {
 
 impl Into<DrawItem> for ImageDrawable {
     fn into(self) -> DrawItem {
-        DrawItem::Image(self)
+        DrawItem::Image(Box::new(self))
     }
 }
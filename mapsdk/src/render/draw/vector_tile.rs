@@ -1,15 +1,15 @@
+use geo::Coord;
 use wgpu::*;
 
 use crate::{
     feature::style::ShapeStyles,
     render::{
-        create_image_params_bg, create_image_params_bgl, create_image_texture_bg,
-        create_image_texture_bgl, create_map_view_bg, create_map_view_bgl,
         draw::Drawable,
         resources::{
+            bind_group::{create_image_texture_bg, image_params, UniformBindGroup},
             buffer::{
-                create_index_buffer_from_u16_slice, create_vertex_buffer_from_vec2_f32_slice,
-                create_vertex_buffer_from_vec7_f32_slice,
+                create_index_buffer, create_index_buffer_from_u16_slice,
+                create_vertex_buffer_from_vec2_f32_slice, create_vertex_buffer_from_vec8_f32_slice,
             },
             texture::create_texture,
         },
@@ -22,18 +22,38 @@ use crate::{
 pub struct VectorTileDrawable {
     pub tile_id: TileId,
     pub z: f32,
+    /// Center of the tile, texture vertex coords are relative to it.
+    pub origin: Coord,
     pub layers_shape_styles: Vec<(String, ShapeStyles)>,
 
     pub fill_vertex_buffer: Buffer,
     pub fill_index_buffer: Buffer,
+    pub fill_index_format: IndexFormat,
     pub stroke_vertex_buffer: Buffer,
     pub stroke_index_buffer: Buffer,
+    pub stroke_index_format: IndexFormat,
     pub shape_metas: Vec<VectorTileShapeMeta>,
 
     pub texture_view: TextureView,
     pub texture_updated_zoom_res: f64,
+    pub texture_updated_pixel_ratio: f64,
     pub texture_vertex_buffer: Buffer,
     pub texture_index_buffer: Buffer,
+    pub texture_bg: BindGroup,
+    pub image_params: UniformBindGroup,
+    pub tile_bind_groups: VectorTileBindGroups,
+}
+
+/// Bind groups of rendering the tile into its texture, kept across renders. Params are by style
+/// group of `group_shape_metas` in render.rs.
+#[derive(Default)]
+pub struct VectorTileBindGroups {
+    pub fill_params: Vec<Option<UniformBindGroup>>,
+    /// By shape of the group, for gradients relative to each feature.
+    pub gradient_fill_params: Vec<Option<Vec<Option<UniformBindGroup>>>>,
+    pub symbol_shape_params: Vec<Option<UniformBindGroup>>,
+    pub line_stroke_params: Vec<Option<UniformBindGroup>>,
+    pub outline_stroke_params: Vec<Option<UniformBindGroup>>,
 }
 
 impl VectorTileDrawable {
@@ -58,30 +78,43 @@ impl VectorTileDrawable {
         let MapRenderingContext {
             device,
             color_target_state,
+            color_sampler,
+            bind_group_layouts,
+            allocation_counter,
             ..
         } = &map_renderer.rendering_context;
 
         let fill_vertex_buffer =
-            create_vertex_buffer_from_vec2_f32_slice(device, "Fill Vertex Buffer", &fill_vertices);
-        let fill_index_buffer =
-            create_index_buffer_from_u16_slice(device, "Fill Index Buffer", &fill_indices);
+            create_vertex_buffer_from_vec2_f32_slice(device, "Fill Vertex Buffer", fill_vertices);
+        let (fill_index_buffer, fill_index_format) =
+            create_index_buffer(device, "Fill Index Buffer", fill_indices);
 
-        let stroke_vertex_buffer = create_vertex_buffer_from_vec7_f32_slice(
+        let stroke_vertex_buffer = create_vertex_buffer_from_vec8_f32_slice(
             device,
             "Stroke Vertex Buffer",
-            &stroke_vertices,
+            stroke_vertices,
         );
-        let stroke_index_buffer =
-            create_index_buffer_from_u16_slice(device, "Stroke Index Buffer", &stroke_indices);
+        let (stroke_index_buffer, stroke_index_format) =
+            create_index_buffer(device, "Stroke Index Buffer", stroke_indices);
 
         let texture = create_texture(device, 4096, 4096, color_target_state.format);
         let texture_view = texture.create_view(&TextureViewDescriptor::default());
+        let texture_bg = create_image_texture_bg(
+            device,
+            allocation_counter,
+            &bind_group_layouts.image_texture,
+            &texture_view,
+            color_sampler,
+        );
 
+        let origin = tile_bbox.center();
+        let half_width = (tile_bbox.width() / 2.0) as f32;
+        let half_height = (tile_bbox.height() / 2.0) as f32;
         let texture_vertices = [
-            [tile_bbox[0], tile_bbox[3]],
-            [tile_bbox[2], tile_bbox[3]],
-            [tile_bbox[0], tile_bbox[1]],
-            [tile_bbox[2], tile_bbox[1]],
+            [-half_width, half_height],
+            [half_width, half_height],
+            [-half_width, -half_height],
+            [half_width, -half_height],
         ];
         let texture_vertex_buffer = create_vertex_buffer_from_vec2_f32_slice(
             device,
@@ -96,21 +129,35 @@ impl VectorTileDrawable {
             &texture_indices,
         );
 
+        let image_params = UniformBindGroup::new(
+            &map_renderer.rendering_context,
+            &bind_group_layouts.image_params,
+            "Image Params",
+            image_params(z as f32, &origin),
+        );
+
         Self {
             tile_id: tile_id.clone(),
             z: z as f32,
+            origin,
             layers_shape_styles: layers_shape_styles.clone(),
 
             fill_vertex_buffer,
             fill_index_buffer,
+            fill_index_format,
             stroke_vertex_buffer,
             stroke_index_buffer,
+            stroke_index_format,
             shape_metas: shape_metas.clone(),
 
             texture_view,
             texture_updated_zoom_res: 0.0,
+            texture_updated_pixel_ratio: 0.0,
             texture_vertex_buffer,
             texture_index_buffer,
+            texture_bg,
+            image_params,
+            tile_bind_groups: VectorTileBindGroups::default(),
         }
     }
 }
@@ -125,39 +172,26 @@ impl Drawable for VectorTileDrawable {
         render_pass: &mut RenderPass,
     ) {
         // Update texture if needed
-        if self.texture_updated_zoom_res != map_state.zoom_res {
+        if self.texture_updated_zoom_res != map_state.zoom_res
+            || self.texture_updated_pixel_ratio != map_renderer.pixel_ratio()
+        {
             inter_renderers
                 .vector_tile_renderer
                 .render(map_options, map_state, map_renderer, self);
             self.texture_updated_zoom_res = map_state.zoom_res;
+            self.texture_updated_pixel_ratio = map_renderer.pixel_ratio();
         }
 
         let MapRenderingContext {
-            device,
-            color_sampler,
+            map_view_bg,
             image_pipeline,
             ..
         } = &map_renderer.rendering_context;
 
-        let map_view_bgl = create_map_view_bgl(device);
-        let map_view_bg =
-            create_map_view_bg(device, &map_view_bgl, &map_renderer.camera, &map_state);
-
-        let image_texture_bgl = create_image_texture_bgl(device);
-        let image_texture_bg = create_image_texture_bg(
-            device,
-            &image_texture_bgl,
-            &self.texture_view,
-            &color_sampler,
-        );
-
-        let image_params_bgl = create_image_params_bgl(device);
-        let image_params_bg = create_image_params_bg(device, &image_params_bgl, self.z);
-
-        render_pass.set_pipeline(&image_pipeline);
-        render_pass.set_bind_group(0, &map_view_bg, &[]);
-        render_pass.set_bind_group(1, &image_texture_bg, &[]);
-        render_pass.set_bind_group(2, &image_params_bg, &[]);
+        render_pass.set_pipeline(image_pipeline);
+        render_pass.set_bind_group(0, map_view_bg, &[]);
+        render_pass.set_bind_group(1, &self.texture_bg, &[]);
+        render_pass.set_bind_group(2, self.image_params.bind_group(), &[]);
         render_pass.set_vertex_buffer(0, self.texture_vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.texture_index_buffer.slice(..), IndexFormat::Uint16);
         render_pass.draw_indexed(0..4, 0, 0..1);
@@ -166,6 +200,6 @@ impl Drawable for VectorTileDrawable {
 
 impl Into<DrawItem> for VectorTileDrawable {
     fn into(self) -> DrawItem {
-        DrawItem::VectorTile(self)
+        DrawItem::VectorTile(Box::new(self))
     }
 }
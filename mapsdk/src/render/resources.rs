@@ -1,3 +1,5 @@
+pub mod allocation;
+pub mod atlas;
 pub mod bind_group;
 pub mod buffer;
 pub mod pipeline;
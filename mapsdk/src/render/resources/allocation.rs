@@ -0,0 +1,48 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use crate::map::FrameAllocations;
+
+/// Counts GPU resources created while rendering, see [`FrameAllocations`]. Uniform buffers and
+/// bind groups are counted where they are created, in `UniformBindGroup`, the image texture and
+/// shape batch params bind groups and the text vertex buffer.
+#[derive(Default)]
+pub struct AllocationCounter {
+    buffers: AtomicUsize,
+    bind_groups: AtomicUsize,
+    last_frame: Mutex<FrameAllocations>,
+}
+
+impl AllocationCounter {
+    pub fn add_buffers(&self, count: usize) {
+        self.buffers.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn add_bind_groups(&self, count: usize) {
+        self.bind_groups.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Reset the counts, resources created between frames, e.g. by layer updates, are not counted.
+    pub fn begin_frame(&self) {
+        self.buffers.store(0, Ordering::Relaxed);
+        self.bind_groups.store(0, Ordering::Relaxed);
+    }
+
+    pub fn end_frame(&self) {
+        if let Ok(mut last_frame) = self.last_frame.lock() {
+            *last_frame = FrameAllocations {
+                buffers: self.buffers.load(Ordering::Relaxed),
+                bind_groups: self.bind_groups.load(Ordering::Relaxed),
+            };
+        }
+    }
+
+    pub fn last_frame(&self) -> FrameAllocations {
+        self.last_frame
+            .lock()
+            .map(|last_frame| *last_frame)
+            .unwrap_or_default()
+    }
+}
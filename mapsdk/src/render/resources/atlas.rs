@@ -0,0 +1,40 @@
+/// Packs rects into rows of an atlas texture, with a pixel of padding between them.
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+        }
+    }
+
+    /// Position of a new rect, `None` if the atlas is full.
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.shelf_x + width + 1 > self.width {
+            self.shelf_x = 0;
+            self.shelf_y += self.shelf_height;
+            self.shelf_height = 0;
+        }
+        if self.shelf_x + width + 1 > self.width || self.shelf_y + height + 1 > self.height {
+            return None;
+        }
+
+        let position = (self.shelf_x, self.shelf_y);
+        self.shelf_x += width + 1;
+        self.shelf_height = self.shelf_height.max(height + 1);
+
+        Some(position)
+    }
+}
@@ -1,51 +1,176 @@
+use geo::Coord;
 use wgpu::*;
 
 use crate::{
-    feature::style::ShapeStyles,
+    feature::style::{GradientKind, IconStyle, LineCap, LineJoin, ShapeStyles, SymbolShape},
     render::{
-        resources::buffer::{
-            create_uniform_buffer_from_f32_slice, create_uniform_buffer_from_u32_slice,
-            create_uniform_buffer_from_vec4_f32_slice,
-        },
-        Camera, MapState,
+        resources::{allocation::AllocationCounter, buffer::create_uniform_buffer_from_bytes},
+        sprite::SpriteIcon,
+        Camera, MapRenderingContext, MapState,
     },
 };
 
-pub fn create_image_params_bg(device: &Device, layout: &BindGroupLayout, z: f32) -> BindGroup {
-    let z_buffer = create_uniform_buffer_from_f32_slice(device, "Z Buffer", &[z]);
+/// Bind group layouts shared by pipelines and bind groups, created once with the device.
+pub struct BindGroupLayouts {
+    pub map_view: BindGroupLayout,
+    pub image_texture: BindGroupLayout,
+    pub image_params: BindGroupLayout,
+    pub shape_fill_params: BindGroupLayout,
+    pub shape_stroke_params: BindGroupLayout,
+    /// Not supported without storage buffers in vertex shaders, e.g. on WebGL2.
+    pub shape_batch_params: Option<BindGroupLayout>,
+    pub symbol_shape_params: BindGroupLayout,
+    pub symbol_icon_params: BindGroupLayout,
+}
 
-    device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Image Params BindGroup"),
-        layout,
-        entries: &[BindGroupEntry {
-            binding: 0,
-            resource: z_buffer.as_entire_binding(),
-        }],
-    })
+impl BindGroupLayouts {
+    pub fn new(device: &Device, supports_feature_batches: bool) -> Self {
+        Self {
+            map_view: create_map_view_bgl(device),
+            image_texture: create_image_texture_bgl(device),
+            image_params: create_image_params_bgl(device),
+            shape_fill_params: create_shape_fill_params_bgl(device),
+            shape_stroke_params: create_shape_stroke_params_bgl(device),
+            shape_batch_params: supports_feature_batches
+                .then(|| create_shape_batch_params_bgl(device)),
+            symbol_shape_params: create_symbol_shape_params_bgl(device),
+            symbol_icon_params: create_symbol_icon_params_bgl(device),
+        }
+    }
+}
+
+/// Contents of uniform buffers, one per binding in order.
+#[derive(Default, PartialEq)]
+pub struct Uniforms(Vec<Vec<u8>>);
+
+impl Uniforms {
+    pub fn with<T: bytemuck::Pod>(mut self, values: &[T]) -> Self {
+        self.0.push(bytemuck::cast_slice(values).to_vec());
+        self
+    }
+}
+
+/// Bind group of uniform buffers, the buffers are created once and only rewritten when their
+/// contents change.
+pub struct UniformBindGroup {
+    buffers: Vec<Buffer>,
+    uniforms: Uniforms,
+    bind_group: BindGroup,
 }
 
-pub fn create_image_params_bgl(device: &Device) -> BindGroupLayout {
+impl UniformBindGroup {
+    pub fn new(
+        rendering_context: &MapRenderingContext,
+        layout: &BindGroupLayout,
+        label: &str,
+        uniforms: Uniforms,
+    ) -> Self {
+        let MapRenderingContext {
+            device,
+            allocation_counter,
+            ..
+        } = rendering_context;
+
+        let buffer_label = format!("{} Buffer", label);
+        let buffers: Vec<Buffer> = uniforms
+            .0
+            .iter()
+            .map(|contents| create_uniform_buffer_from_bytes(device, &buffer_label, contents))
+            .collect();
+
+        let entries: Vec<BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(&format!("{} BindGroup", label)),
+            layout,
+            entries: &entries,
+        });
+
+        allocation_counter.add_buffers(buffers.len());
+        allocation_counter.add_bind_groups(1);
+
+        Self {
+            buffers,
+            uniforms,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    /// Rewrite the buffers whose contents changed.
+    pub fn update(&mut self, queue: &Queue, uniforms: Uniforms) {
+        for ((buffer, contents), new_contents) in self
+            .buffers
+            .iter()
+            .zip(&mut self.uniforms.0)
+            .zip(uniforms.0)
+        {
+            if *contents != new_contents {
+                queue.write_buffer(buffer, 0, &new_contents);
+                *contents = new_contents;
+            }
+        }
+    }
+
+    /// Update the bind group in `slot`, creating it on first use.
+    pub fn update_or_create<'a>(
+        slot: &'a mut Option<Self>,
+        rendering_context: &MapRenderingContext,
+        layout: &BindGroupLayout,
+        label: &str,
+        uniforms: Uniforms,
+    ) -> &'a BindGroup {
+        match slot {
+            Some(uniform_bind_group) => {
+                uniform_bind_group.update(&rendering_context.queue, uniforms)
+            }
+            None => *slot = Some(Self::new(rendering_context, layout, label, uniforms)),
+        }
+
+        slot.as_ref().unwrap().bind_group()
+    }
+}
+
+pub fn image_params(z: f32, origin: &Coord) -> Uniforms {
+    Uniforms::default().with(&[z]).with(&split_coord(origin))
+}
+
+fn create_image_params_bgl(device: &Device) -> BindGroupLayout {
+    let entry = |binding| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: Some("Image Params BindGroupLayout"),
-        entries: &[BindGroupLayoutEntry {
-            binding: 0,
-            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-            ty: BindingType::Buffer {
-                ty: BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            count: None,
-        }],
+        entries: &[entry(0), entry(1)],
     })
 }
 
 pub fn create_image_texture_bg(
     device: &Device,
+    allocation_counter: &AllocationCounter,
     layout: &BindGroupLayout,
     texture_view: &TextureView,
     sampler: &Sampler,
 ) -> BindGroup {
+    allocation_counter.add_bind_groups(1);
+
     device.create_bind_group(&BindGroupDescriptor {
         label: Some("Image Texture BindGroup"),
         layout,
@@ -62,7 +187,7 @@ pub fn create_image_texture_bg(
     })
 }
 
-pub fn create_image_texture_bgl(device: &Device) -> BindGroupLayout {
+fn create_image_texture_bgl(device: &Device) -> BindGroupLayout {
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: Some("Image Texture BindGroupLayout"),
         entries: &[
@@ -86,116 +211,128 @@ pub fn create_image_texture_bgl(device: &Device) -> BindGroupLayout {
     })
 }
 
+/// The `MapView` of map_view.wgsl, one buffer shared by all draw items and written once per world
+/// copy.
+///
+/// Vertex coords are relative to an origin near them, e.g. the center of a feature, which keeps
+/// them precise in f32 at high zooms far from zero. Draw items pass their origin with their
+/// params, split like the map center by [`split_coord`].
+pub fn map_view(camera: &Camera, map_state: &MapState) -> MapView {
+    MapView {
+        view_proj: camera.view_proj(),
+        center: split_coord(&map_state.center),
+        res: (map_state.zoom_res * map_state.map_res_ratio) as f32,
+        _padding: [0.0; 3],
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MapView {
+    view_proj: [[f32; 4]; 4],
+    center: [f32; 4],
+    res: f32,
+    _padding: [f32; 3],
+}
+
 pub fn create_map_view_bg(
     device: &Device,
     layout: &BindGroupLayout,
-    camera: &Camera,
-    map_state: &MapState,
+    map_view_buffer: &Buffer,
 ) -> BindGroup {
-    let camera_buffer =
-        create_uniform_buffer_from_vec4_f32_slice(device, "Camera Buffer", &camera.view_proj());
-
-    let map_center_buffer = create_uniform_buffer_from_f32_slice(
-        device,
-        "MapCenter Buffer",
-        &[map_state.center.x as f32, map_state.center.y as f32],
-    );
-
-    let map_res_buffer = create_uniform_buffer_from_f32_slice(
-        device,
-        "MapRes Buffer",
-        &[(map_state.zoom_res * map_state.map_res_ratio) as f32],
-    );
-
     device.create_bind_group(&BindGroupDescriptor {
         label: Some("Map View BindGroup"),
         layout,
-        entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 1,
-                resource: map_center_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 2,
-                resource: map_res_buffer.as_entire_binding(),
-            },
-        ],
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: map_view_buffer.as_entire_binding(),
+        }],
     })
 }
 
-pub fn create_map_view_bgl(device: &Device) -> BindGroupLayout {
+/// High and low f32 parts of a map coord as `[x_high, y_high, x_low, y_low]`. The shaders subtract
+/// the parts separately, so offsets between coords far from zero stay precise.
+pub fn split_coord(coord: &Coord) -> [f32; 4] {
+    let x_high = coord.x as f32;
+    let y_high = coord.y as f32;
+
+    [
+        x_high,
+        y_high,
+        (coord.x - x_high as f64) as f32,
+        (coord.y - y_high as f64) as f32,
+    ]
+}
+
+fn create_map_view_bgl(device: &Device) -> BindGroupLayout {
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: Some("Map View BindGroupLayout"),
-        entries: &[
-            BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 1,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 2,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
             },
-        ],
+            count: None,
+        }],
     })
 }
 
-pub fn create_shape_fill_params_bg(
+/// Params of batched shapes, styles of each feature are in `feature_styles_buffer` as written by
+/// [`shape_batch_feature_style`].
+pub fn create_shape_batch_params_bg(
     device: &Device,
+    allocation_counter: &AllocationCounter,
     layout: &BindGroupLayout,
     z: f32,
     shape_styles: &ShapeStyles,
+    feature_styles_buffer: &Buffer,
 ) -> BindGroup {
-    let z_buffer = create_uniform_buffer_from_f32_slice(device, "Z Buffer", &[z]);
-
-    let fill_color: [f32; 4] = shape_styles.fill_color.clone().into();
-    let fill_color_buffer =
-        create_uniform_buffer_from_f32_slice(device, "Fill Color Buffer", &fill_color);
+    let params = ShapeBatchParams {
+        z,
+        line_cap: line_cap(shape_styles.line_cap),
+        line_join: line_join(shape_styles.line_join),
+        miter_limit: shape_styles.miter_limit,
+    };
+    let params_buffer = create_uniform_buffer_from_bytes(
+        device,
+        "Shape Batch Params Buffer",
+        bytemuck::bytes_of(&params),
+    );
+    allocation_counter.add_buffers(1);
+    allocation_counter.add_bind_groups(1);
 
     device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Shape Fill Params BindGroup"),
+        label: Some("Shape Batch Params BindGroup"),
         layout,
         entries: &[
             BindGroupEntry {
                 binding: 0,
-                resource: z_buffer.as_entire_binding(),
+                resource: params_buffer.as_entire_binding(),
             },
             BindGroupEntry {
                 binding: 1,
-                resource: fill_color_buffer.as_entire_binding(),
+                resource: feature_styles_buffer.as_entire_binding(),
             },
         ],
     })
 }
 
-pub fn create_shape_fill_params_bgl(device: &Device) -> BindGroupLayout {
+/// A `Params` in shape_fill_batch.wgsl and shape_stroke_batch.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeBatchParams {
+    z: f32,
+    line_cap: u32,
+    line_join: u32,
+    miter_limit: f32,
+}
+
+fn create_shape_batch_params_bgl(device: &Device) -> BindGroupLayout {
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        label: Some("Shape Fill Params BindGroupLayout"),
+        label: Some("Shape Batch Params BindGroupLayout"),
         entries: &[
             BindGroupLayoutEntry {
                 binding: 0,
@@ -209,9 +346,9 @@ pub fn create_shape_fill_params_bgl(device: &Device) -> BindGroupLayout {
             },
             BindGroupLayoutEntry {
                 binding: 1,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                visibility: ShaderStages::VERTEX,
                 ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
+                    ty: BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
@@ -221,169 +358,523 @@ pub fn create_shape_fill_params_bgl(device: &Device) -> BindGroupLayout {
     })
 }
 
-pub fn create_shape_stroke_params_bg(
-    device: &Device,
+/// Style of a batched feature, a `FeatureStyle` in shape_fill_batch.wgsl and
+/// shape_stroke_batch.wgsl, with the origin of the vertex coords of the feature.
+pub fn shape_batch_feature_style(
+    shape_styles: &ShapeStyles,
     pixel_ratio: f32,
-    layout: &BindGroupLayout,
-    z: f32,
     align: u32,
+    origin: &Coord,
+) -> [[f32; 4]; 4] {
+    [
+        split_coord(origin),
+        shape_styles.fill_color.clone().into(),
+        shape_styles.stroke_color.clone().into(),
+        [
+            shape_styles.stroke_width * pixel_ratio,
+            align as f32,
+            if shape_styles.visible { 1.0 } else { 0.0 },
+            0.0,
+        ],
+    ]
+}
+
+/// Max number of gradient stops, the size of the gradient arrays in shape_fill.wgsl.
+const MAX_GRADIENT_STOPS: usize = 8;
+
+/// Transforms map `[offset_x, offset_y, scale_x, scale_y]` from coords relative to the map center
+/// to pattern repeats and to the gradient bounding box.
+pub fn shape_fill_params(
+    z: f32,
+    origin: &Coord,
     shape_styles: &ShapeStyles,
-) -> BindGroup {
-    let z_buffer = create_uniform_buffer_from_f32_slice(device, "Z Buffer", &[z]);
-    let align_buffer = create_uniform_buffer_from_u32_slice(device, "Align Buffer", &[align]);
+    pattern: Option<&SpriteIcon>,
+    pattern_transform: [f32; 4],
+    gradient_transform: [f32; 4],
+) -> Uniforms {
+    let fill_color: [f32; 4] = shape_styles.fill_color.clone().into();
 
-    let stroke_width_buffer = create_uniform_buffer_from_f32_slice(
-        device,
-        "Stroke Width Buffer",
-        &[shape_styles.stroke_width * pixel_ratio],
-    );
+    let (fill_type, gradient_geometry) = match (pattern, &shape_styles.fill_gradient) {
+        (Some(_), _) => (1u32, [0.0; 4]),
+        (None, Some(gradient)) => match gradient.kind {
+            GradientKind::Linear { start, end } => (2, [start[0], start[1], end[0], end[1]]),
+            GradientKind::Radial { center, radius } => (3, [center[0], center[1], radius, 0.0]),
+        },
+        (None, None) => (0, [0.0; 4]),
+    };
 
-    let stroke_color: [f32; 4] = shape_styles.stroke_color.clone().into();
-    let stroke_color_buffer =
-        create_uniform_buffer_from_f32_slice(device, "Stroke Color Buffer", &stroke_color);
+    let pattern_tex_rect = pattern.map_or([0.0; 4], |icon| icon.tex_rect());
 
-    device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Shape Stroke Params BindGroup"),
-        layout,
-        entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: z_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 1,
-                resource: align_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 2,
-                resource: stroke_width_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 3,
-                resource: stroke_color_buffer.as_entire_binding(),
+    let stops = shape_styles
+        .fill_gradient
+        .as_ref()
+        .map_or(&[][..], |gradient| &gradient.stops[..]);
+    let stops = &stops[..stops.len().min(MAX_GRADIENT_STOPS)];
+
+    let mut gradient_offsets = [[0.0f32; 4]; MAX_GRADIENT_STOPS / 4];
+    let mut gradient_colors = [[0.0f32; 4]; MAX_GRADIENT_STOPS];
+    for (i, (offset, color)) in stops.iter().enumerate() {
+        gradient_offsets[i / 4][i % 4] = *offset;
+        gradient_colors[i] = color.clone().into();
+    }
+
+    Uniforms::default().with(&[ShapeFillParams {
+        origin: split_coord(origin),
+        fill_color,
+        pattern_transform,
+        pattern_tex_rect,
+        gradient_transform,
+        gradient_geometry,
+        gradient_offsets,
+        gradient_colors,
+        z,
+        fill_type,
+        gradient_stop_count: stops.len() as u32,
+        _padding: 0,
+    }])
+}
+
+/// A `Params` in shape_fill.wgsl, vectors first to match the alignment of uniform structs.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeFillParams {
+    origin: [f32; 4],
+    fill_color: [f32; 4],
+    pattern_transform: [f32; 4],
+    pattern_tex_rect: [f32; 4],
+    gradient_transform: [f32; 4],
+    gradient_geometry: [f32; 4],
+    gradient_offsets: [[f32; 4]; MAX_GRADIENT_STOPS / 4],
+    gradient_colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+    z: f32,
+    fill_type: u32,
+    gradient_stop_count: u32,
+    _padding: u32,
+}
+
+fn create_shape_fill_params_bgl(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Shape Fill Params BindGroupLayout"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
             },
-        ],
+            count: None,
+        }],
     })
 }
 
-pub fn create_shape_stroke_params_bgl(device: &Device) -> BindGroupLayout {
+/// Max number of dash array values, the size of the dash array in shape_stroke.wgsl.
+const MAX_DASH_COUNT: usize = 16;
+
+pub fn shape_stroke_params(
+    pixel_ratio: f32,
+    z: f32,
+    origin: &Coord,
+    align: u32,
+    shape_styles: &ShapeStyles,
+    pattern: Option<&SpriteIcon>,
+) -> Uniforms {
+    let stroke_color: [f32; 4] = shape_styles.stroke_color.clone().into();
+
+    // Odd dash arrays are repeated to alternate dashes and gaps
+    let mut dash_array: Vec<f32> = shape_styles
+        .dash_array
+        .iter()
+        .flatten()
+        .map(|v| v * pixel_ratio)
+        .collect();
+    if dash_array.len() % 2 == 1 {
+        dash_array.extend_from_within(..);
+    }
+    dash_array.truncate(MAX_DASH_COUNT);
+
+    let mut dash_values = [[0.0f32; 4]; MAX_DASH_COUNT / 4];
+    for (i, v) in dash_array.iter().enumerate() {
+        dash_values[i / 4][i % 4] = *v;
+    }
+
+    let (pattern_size, pattern_tex_rect) = match pattern {
+        Some(icon) => ([icon.width as f32, icon.height as f32], icon.tex_rect()),
+        None => ([0.0; 2], [0.0; 4]),
+    };
+
+    Uniforms::default().with(&[ShapeStrokeParams {
+        origin: split_coord(origin),
+        stroke_color,
+        dash_array: dash_values,
+        pattern_tex_rect,
+        pattern_size,
+        z,
+        stroke_width: shape_styles.stroke_width * pixel_ratio,
+        align,
+        line_cap: line_cap(shape_styles.line_cap),
+        line_join: line_join(shape_styles.line_join),
+        miter_limit: shape_styles.miter_limit,
+        dash_count: dash_array.len() as u32,
+        _padding: [0; 3],
+    }])
+}
+
+/// A `Params` in shape_stroke.wgsl, vectors first to match the alignment of uniform structs.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeStrokeParams {
+    origin: [f32; 4],
+    stroke_color: [f32; 4],
+    dash_array: [[f32; 4]; MAX_DASH_COUNT / 4],
+    pattern_tex_rect: [f32; 4],
+    pattern_size: [f32; 2],
+    z: f32,
+    stroke_width: f32,
+    align: u32,
+    line_cap: u32,
+    line_join: u32,
+    miter_limit: f32,
+    dash_count: u32,
+    _padding: [u32; 3],
+}
+
+fn create_shape_stroke_params_bgl(device: &Device) -> BindGroupLayout {
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: Some("Shape Stroke Params BindGroupLayout"),
-        entries: &[
-            BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 1,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 2,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 3,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
             },
-        ],
+            count: None,
+        }],
     })
 }
 
-pub fn create_symbol_circle_params_bg(
-    device: &Device,
+fn line_cap(line_cap: LineCap) -> u32 {
+    match line_cap {
+        LineCap::Butt => 0,
+        LineCap::Round => 1,
+        LineCap::Square => 2,
+    }
+}
+
+fn line_join(line_join: LineJoin) -> u32 {
+    match line_join {
+        LineJoin::Miter => 0,
+        LineJoin::Bevel => 1,
+        LineJoin::Round => 2,
+    }
+}
+
+pub fn symbol_shape_params(
     pixel_ratio: f32,
-    layout: &BindGroupLayout,
     z: f32,
+    origin: &Coord,
     shape_styles: &ShapeStyles,
-) -> BindGroup {
-    let z_buffer = create_uniform_buffer_from_f32_slice(device, "Z Buffer", &[z]);
-
+) -> Uniforms {
     let radius = shape_styles.symbol_size * pixel_ratio / 2.0;
-    let radius_buffer = create_uniform_buffer_from_f32_slice(device, "Radius Buffer", &[radius]);
 
-    let fill_color: [f32; 4] = shape_styles.fill_color.clone().into();
-    let fill_color_buffer =
-        create_uniform_buffer_from_f32_slice(device, "Fill Color Buffer", &fill_color);
+    // Disabled fills are transparent and disabled strokes have no width
+    let fill_color: [f32; 4] = if shape_styles.fill_enabled {
+        shape_styles.fill_color.clone().into()
+    } else {
+        [0.0; 4]
+    };
 
-    device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Symbol Rect Params BindGroup"),
-        layout,
+    let shape: u32 = match shape_styles.symbol_shape {
+        SymbolShape::Circle => 0,
+        SymbolShape::Square => 1,
+        SymbolShape::Triangle => 2,
+        SymbolShape::Diamond => 3,
+        SymbolShape::Star => 4,
+        SymbolShape::Cross => 5,
+        SymbolShape::Arrow => 6,
+    };
+
+    let stroke_color: [f32; 4] = shape_styles.stroke_color.clone().into();
+
+    let stroke_width = if shape_styles.stroke_enabled {
+        shape_styles.stroke_width * pixel_ratio
+    } else {
+        0.0
+    };
+
+    Uniforms::default()
+        .with(&[z])
+        .with(&[radius])
+        .with(&fill_color)
+        .with(&[shape])
+        .with(&stroke_color)
+        .with(&[stroke_width])
+        .with(&split_coord(origin))
+}
+
+fn create_symbol_shape_params_bgl(device: &Device) -> BindGroupLayout {
+    let entry = |binding| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Symbol Shape Params BindGroupLayout"),
         entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: z_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 1,
-                resource: radius_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 2,
-                resource: fill_color_buffer.as_entire_binding(),
-            },
+            entry(0),
+            entry(1),
+            entry(2),
+            entry(3),
+            entry(4),
+            entry(5),
+            entry(6),
         ],
     })
 }
 
-pub fn create_symbol_circle_params_bgl(device: &Device) -> BindGroupLayout {
+pub fn symbol_icon_params(
+    pixel_ratio: f32,
+    z: f32,
+    origin: &Coord,
+    rotation: f32,
+    icon_style: &IconStyle,
+    sprite_icon: &SpriteIcon,
+) -> Uniforms {
+    let scale = icon_style.size * pixel_ratio / sprite_icon.pixel_ratio;
+    let width = sprite_icon.width as f32 * scale;
+    let height = sprite_icon.height as f32 * scale;
+    let (ax, ay) = icon_style.anchor.position();
+    let left = icon_style.offset[0] * pixel_ratio - width * ax;
+    let top = height * ay - icon_style.offset[1] * pixel_ratio;
+
+    let tint: [f32; 4] = match &icon_style.tint {
+        Some(tint) => tint.clone().into(),
+        None => [1.0, 1.0, 1.0, 1.0],
+    };
+
+    Uniforms::default()
+        .with(&[z])
+        .with(&[left, top, left + width, top - height])
+        .with(&[rotation])
+        .with(&tint)
+        .with(&sprite_icon.tex_rect())
+        .with(&split_coord(origin))
+}
+
+fn create_symbol_icon_params_bgl(device: &Device) -> BindGroupLayout {
+    let entry = |binding| BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-        label: Some("Symbol Rect Params BindGroupLayout"),
-        entries: &[
-            BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 1,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-            BindGroupLayoutEntry {
-                binding: 2,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            },
-        ],
+        label: Some("Symbol Icon Params BindGroupLayout"),
+        entries: &[entry(0), entry(1), entry(2), entry(3), entry(4), entry(5)],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::{polygon, BoundingRect, MapCoords};
+
+    use super::*;
+    use crate::{
+        feature::{Feature, Shape},
+        render::tessellation::{
+            feature_batch::FeatureBatchTessellation, geometry::tessellate_geometry,
+        },
+    };
+
+    fn stroke_params(pixel_ratio: f32, shape_styles: &ShapeStyles) -> ShapeStrokeParams {
+        let uniforms = shape_stroke_params(
+            pixel_ratio,
+            0.0,
+            &Coord { x: 0.0, y: 0.0 },
+            0,
+            shape_styles,
+            None,
+        );
+        assert_eq!(uniforms.0.len(), 1);
+
+        bytemuck::pod_read_unaligned(&uniforms.0[0])
+    }
+
+    #[test]
+    fn test_shape_stroke_params() {
+        let shape_styles = ShapeStyles {
+            stroke_width: 2.0,
+            dash_array: Some(vec![4.0, 2.0, 1.0]),
+            ..Default::default()
+        };
+
+        // Odd dash arrays are repeated to alternate dashes and gaps
+        let params = stroke_params(1.0, &shape_styles);
+        assert_eq!(params.stroke_width, 2.0);
+        assert_eq!(params.dash_count, 6);
+        assert_eq!(params.dash_array[0], [4.0, 2.0, 1.0, 4.0]);
+        assert_eq!(params.dash_array[1], [2.0, 1.0, 0.0, 0.0]);
+
+        // Widths and dashes are in pixels of the pixel ratio
+        let params = stroke_params(2.0, &shape_styles);
+        assert_eq!(params.stroke_width, 4.0);
+        assert_eq!(params.dash_array[0], [8.0, 4.0, 2.0, 8.0]);
+        assert_eq!(params.dash_array[1], [4.0, 2.0, 0.0, 0.0]);
+
+        let shape_styles = ShapeStyles {
+            dash_array: Some(vec![1.0; 9]),
+            ..Default::default()
+        };
+        assert_eq!(
+            stroke_params(1.0, &shape_styles).dash_count,
+            MAX_DASH_COUNT as u32
+        );
+    }
+
+    #[test]
+    fn test_map_view_precision() {
+        // Street level at about 2e7 m, where f32 steps are 2 m
+        let square = polygon![
+            (x: 2e7 + 0.12, y: 2e7 + 0.34),
+            (x: 2e7 + 0.56, y: 2e7 + 0.34),
+            (x: 2e7 + 0.56, y: 2e7 + 0.78),
+            (x: 2e7 + 0.12, y: 2e7 + 0.78),
+        ];
+        let mut map_state = MapState::default();
+        map_state.center = Coord {
+            x: 2e7 + 0.91,
+            y: 2e7 + 0.23,
+        };
+
+        // Vertices relative to the drawable origin, like in `FeatureDrawable`
+        let origin = square.bounding_rect().unwrap().center();
+        let tessellations = tessellate_geometry(&square.map_coords(|coord| coord - origin).into());
+        let center = map_view(&Camera::default(), &map_state).center;
+        let origin = split_coord(&origin);
+        // Map center relative to the origin as in map_view.wgsl
+        let map_center = [
+            (center[0] - origin[0]) + (center[2] - origin[2]),
+            (center[1] - origin[1]) + (center[3] - origin[3]),
+        ];
+
+        let vertices = &tessellations.fills[0].vertices;
+        assert_eq!(vertices.len(), 4);
+        for vertex in vertices {
+            // Offset from the map center as in the shaders
+            let offset = [vertex[0] - map_center[0], vertex[1] - map_center[1]];
+            let error = square
+                .exterior()
+                .coords()
+                .map(|coord| {
+                    let exact = *coord - map_state.center;
+                    (offset[0] as f64 - exact.x).hypot(offset[1] as f64 - exact.y)
+                })
+                .fold(f64::INFINITY, f64::min);
+            assert!(error < 0.01, "{}", error);
+        }
+
+        // Absolute f32 coords are off by meters
+        let max_error = square
+            .exterior()
+            .coords()
+            .map(|coord| {
+                let offset = coord.x as f32 - map_state.center.x as f32;
+                (offset as f64 - (coord.x - map_state.center.x)).abs()
+            })
+            .fold(0.0, f64::max);
+        assert!(max_error > 0.1, "{}", max_error);
+    }
+
+    #[test]
+    fn test_feature_batch_precision() {
+        // Street level at about 2e7 m, in a batch spanning 1e6 m
+        let square = |x: f64, y: f64| {
+            polygon![
+                (x: x, y: y),
+                (x: x + 0.44, y: y),
+                (x: x + 0.44, y: y + 0.44),
+                (x: x, y: y + 0.44),
+            ]
+        };
+        let near = square(2e7 + 0.12, 2e7 + 0.34);
+        let far = square(2e7 - 1e6 + 0.12, 2e7 - 1e6 + 0.34);
+        let features = vec![
+            Feature::new("near", Shape::Geometry(near.clone().into()), None),
+            Feature::new("far", Shape::Geometry(far.into()), None),
+        ];
+        let mut map_state = MapState::default();
+        map_state.center = Coord {
+            x: 2e7 + 0.91,
+            y: 2e7 + 0.23,
+        };
+
+        let tessellation = FeatureBatchTessellation::new(&features);
+        let center = map_view(&Camera::default(), &map_state).center;
+        // The origin of the near feature as written to the feature styles buffer
+        let feature_style =
+            shape_batch_feature_style(&ShapeStyles::default(), 1.0, 1, &tessellation.origins[0]);
+        let origin = feature_style[0];
+        // Map center relative to the origin as in map_view.wgsl
+        let map_center = [
+            (center[0] - origin[0]) + (center[2] - origin[2]),
+            (center[1] - origin[1]) + (center[3] - origin[3]),
+        ];
+
+        let offset_error = |offset: [f32; 2]| {
+            near.exterior()
+                .coords()
+                .map(|coord| {
+                    let exact = *coord - map_state.center;
+                    (offset[0] as f64 - exact.x).hypot(offset[1] as f64 - exact.y)
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+
+        let vertices: Vec<[f32; 2]> = tessellation
+            .fill_vertices
+            .iter()
+            .zip(&tessellation.fill_feature_indices)
+            .filter(|(_, feature_index)| **feature_index == 0)
+            .map(|(vertex, _)| *vertex)
+            .collect();
+        assert_eq!(vertices.len(), 4);
+        for vertex in &vertices {
+            // Offset from the map center as in the shaders
+            let error = offset_error([vertex[0] - map_center[0], vertex[1] - map_center[1]]);
+            assert!(error < 0.001, "{}", error);
+        }
+
+        // Relative to the center of the whole batch, vertices are 5e5 m away and off by
+        // centimeters in f32
+        let batch_origin = Coord {
+            x: 2e7 - 5e5 + 0.34,
+            y: 2e7 - 5e5 + 0.56,
+        };
+        let batch_center = map_state.center - batch_origin;
+        let max_error = near
+            .exterior()
+            .coords()
+            .map(|coord| {
+                let vertex = *coord - batch_origin;
+                offset_error([
+                    vertex.x as f32 - batch_center.x as f32,
+                    vertex.y as f32 - batch_center.y as f32,
+                ])
+            })
+            .fold(0.0, f64::max);
+        assert!(max_error > 0.001, "{}", max_error);
+    }
+}
@@ -9,6 +9,7 @@ pub struct VertexIndexBuffer {
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
     pub index_count: u32,
+    pub index_format: IndexFormat,
 }
 
 impl VertexIndexBuffer {
@@ -19,42 +20,54 @@ impl VertexIndexBuffer {
             &fill_vertex_index.vertices,
         );
 
-        let index_buffer = create_index_buffer_from_u16_slice(
-            device,
-            "Fill IndexBuffer",
-            &fill_vertex_index.indices,
-        );
+        let (index_buffer, index_format) =
+            create_index_buffer(device, "Fill IndexBuffer", &fill_vertex_index.indices);
 
         Self {
             vertex_buffer,
             index_buffer,
             index_count: fill_vertex_index.indices.len() as u32,
+            index_format,
         }
     }
     pub fn from_stroke_vertex_index(
         device: &Device,
         stroke_vertex_index: &StrokeVertexIndex,
     ) -> Self {
-        let vertex_buffer = create_vertex_buffer_from_vec7_f32_slice(
+        let vertex_buffer = create_vertex_buffer_from_vec8_f32_slice(
             device,
             "Stroke VertexBuffer",
             &stroke_vertex_index.vertices,
         );
 
-        let index_buffer = create_index_buffer_from_u16_slice(
-            device,
-            "Stroke IndexBuffer",
-            &stroke_vertex_index.indices,
-        );
+        let (index_buffer, index_format) =
+            create_index_buffer(device, "Stroke IndexBuffer", &stroke_vertex_index.indices);
 
         Self {
             vertex_buffer,
             index_buffer,
             index_count: stroke_vertex_index.indices.len() as u32,
+            index_format,
         }
     }
 }
 
+/// Create an index buffer with u16 indices if all indices fit, with u32 indices otherwise.
+pub fn create_index_buffer(device: &Device, label: &str, indices: &[u32]) -> (Buffer, IndexFormat) {
+    if indices.iter().all(|index| *index <= u16::MAX as u32) {
+        let indices: Vec<u16> = indices.iter().map(|index| *index as u16).collect();
+        (
+            create_index_buffer_from_u16_slice(device, label, &indices),
+            IndexFormat::Uint16,
+        )
+    } else {
+        (
+            create_index_buffer_from_u32_slice(device, label, indices),
+            IndexFormat::Uint32,
+        )
+    }
+}
+
 pub fn create_index_buffer_from_u16_slice(device: &Device, label: &str, slice: &[u16]) -> Buffer {
     device.create_buffer_init(&BufferInitDescriptor {
         label: Some(label),
@@ -63,34 +76,52 @@ pub fn create_index_buffer_from_u16_slice(device: &Device, label: &str, slice: &
     })
 }
 
-pub fn create_uniform_buffer_from_f32_slice(device: &Device, label: &str, slice: &[f32]) -> Buffer {
+pub fn create_index_buffer_from_u32_slice(device: &Device, label: &str, slice: &[u32]) -> Buffer {
     device.create_buffer_init(&BufferInitDescriptor {
         label: Some(label),
-        contents: bytemuck::cast_slice(&slice),
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        contents: bytemuck::cast_slice(slice),
+        usage: BufferUsages::INDEX,
     })
 }
 
-pub fn create_uniform_buffer_from_u32_slice(device: &Device, label: &str, slice: &[u32]) -> Buffer {
+pub fn create_storage_buffer_from_vec4_f32_slice(
+    device: &Device,
+    label: &str,
+    slice: &[[f32; 4]],
+) -> Buffer {
     device.create_buffer_init(&BufferInitDescriptor {
         label: Some(label),
-        contents: bytemuck::cast_slice(&slice),
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        contents: bytemuck::cast_slice(slice),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
     })
 }
 
-pub fn create_uniform_buffer_from_vec4_f32_slice(
-    device: &Device,
-    label: &str,
-    slice: &[[f32; 4]],
-) -> Buffer {
+pub fn create_uniform_buffer_from_bytes(device: &Device, label: &str, contents: &[u8]) -> Buffer {
     device.create_buffer_init(&BufferInitDescriptor {
         label: Some(label),
-        contents: bytemuck::cast_slice(&slice),
+        contents,
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     })
 }
 
+/// Create an empty vertex buffer, written with `Queue::write_buffer`.
+pub fn create_vertex_buffer(device: &Device, label: &str, size: BufferAddress) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_vertex_buffer_from_u32_slice(device: &Device, label: &str, slice: &[u32]) -> Buffer {
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(slice),
+        usage: BufferUsages::VERTEX,
+    })
+}
+
 pub fn create_vertex_buffer_from_vec2_f32_slice(
     device: &Device,
     label: &str,
@@ -103,10 +134,10 @@ pub fn create_vertex_buffer_from_vec2_f32_slice(
     })
 }
 
-pub fn create_vertex_buffer_from_vec7_f32_slice(
+pub fn create_vertex_buffer_from_vec8_f32_slice(
     device: &Device,
     label: &str,
-    slice: &[[f32; 7]],
+    slice: &[[f32; 8]],
 ) -> Buffer {
     device.create_buffer_init(&BufferInitDescriptor {
         label: Some(label),
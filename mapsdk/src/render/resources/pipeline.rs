@@ -2,30 +2,28 @@ use std::borrow::Cow;
 
 use wgpu::*;
 
-use crate::render::{
-    create_image_texture_bgl,
-    resources::bind_group::{
-        create_image_params_bgl, create_map_view_bgl, create_shape_fill_params_bgl,
-        create_shape_stroke_params_bgl, create_symbol_circle_params_bgl,
-    },
-};
+use crate::render::resources::bind_group::BindGroupLayouts;
 
 pub fn create_image_pipeline(
     device: &Device,
     color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
 ) -> RenderPipeline {
     let shader = device.create_shader_module(ShaderModuleDescriptor {
         label: Some("Image Shader"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wgsl/image.wgsl"))),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/image.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
     });
 
-    let map_view_bgl = create_map_view_bgl(device);
-    let image_texture_bgl = create_image_texture_bgl(device);
-    let image_params_bgl = create_image_params_bgl(device);
-
     let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Image PipelineLayout"),
-        bind_group_layouts: &[&map_view_bgl, &image_texture_bgl, &image_params_bgl],
+        bind_group_layouts: &[
+            &bind_group_layouts.map_view,
+            &bind_group_layouts.image_texture,
+            &bind_group_layouts.image_params,
+        ],
         push_constant_ranges: &[],
     });
 
@@ -74,18 +72,23 @@ pub fn create_image_pipeline(
 pub fn create_shape_fill_pipeline(
     device: &Device,
     color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
 ) -> RenderPipeline {
     let shader = device.create_shader_module(ShaderModuleDescriptor {
         label: Some("Shape Fill Shader"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wgsl/shape_fill.wgsl"))),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/shape_fill.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
     });
 
-    let map_view_bgl = create_map_view_bgl(device);
-    let shape_fill_params_bgl = create_shape_fill_params_bgl(device);
-
     let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Shape Fill PipelineLayout"),
-        bind_group_layouts: &[&map_view_bgl, &shape_fill_params_bgl],
+        bind_group_layouts: &[
+            &bind_group_layouts.map_view,
+            &bind_group_layouts.shape_fill_params,
+            &bind_group_layouts.image_texture,
+        ],
         push_constant_ranges: &[],
     });
 
@@ -131,25 +134,31 @@ pub fn create_shape_fill_pipeline(
 pub fn create_shape_stroke_pipeline(
     device: &Device,
     color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
 ) -> RenderPipeline {
     let shader = device.create_shader_module(ShaderModuleDescriptor {
         label: Some("Shape Stroke Shader"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wgsl/shape_stroke.wgsl"))),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/shape_stroke.wgsl"),
+            include_str!("../wgsl/stroke.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
     });
 
-    let map_view_bgl = create_map_view_bgl(device);
-    let shape_stroke_params_bgl = create_shape_stroke_params_bgl(device);
-
     let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
         label: Some("Shape Stroke PipelineLayout"),
-        bind_group_layouts: &[&map_view_bgl, &shape_stroke_params_bgl],
+        bind_group_layouts: &[
+            &bind_group_layouts.map_view,
+            &bind_group_layouts.shape_stroke_params,
+            &bind_group_layouts.image_texture,
+        ],
         push_constant_ranges: &[],
     });
 
     let vertex_buffer_layout = VertexBufferLayout {
-        array_stride: std::mem::size_of::<[f32; 7]>() as BufferAddress,
+        array_stride: std::mem::size_of::<[f32; 8]>() as BufferAddress,
         step_mode: VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x2, 3 => Float32],
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x2, 3 => Float32, 4 => Float32],
     };
 
     device.create_render_pipeline(&RenderPipelineDescriptor {
@@ -185,21 +194,146 @@ pub fn create_shape_stroke_pipeline(
     })
 }
 
-pub fn create_symbol_circle_pipeline(
+/// Fill pipeline of batched features, the feature index of each vertex is in a second vertex
+/// buffer. None if the device does not support feature batches.
+pub fn create_shape_fill_batch_pipeline(
     device: &Device,
     color_target_state: &ColorTargetState,
-) -> RenderPipeline {
+    bind_group_layouts: &BindGroupLayouts,
+) -> Option<RenderPipeline> {
+    let shape_batch_params_bgl = bind_group_layouts.shape_batch_params.as_ref()?;
+
     let shader = device.create_shader_module(ShaderModuleDescriptor {
-        label: Some("Symbol Circle Shader"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wgsl/symbol_circle.wgsl"))),
+        label: Some("Shape Fill Batch Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/shape_fill_batch.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
     });
 
-    let map_view_bgl = create_map_view_bgl(device);
-    let symbol_circle_params_bgl = create_symbol_circle_params_bgl(device);
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Shape Fill Batch PipelineLayout"),
+        bind_group_layouts: &[&bind_group_layouts.map_view, shape_batch_params_bgl],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+    };
+    let feature_index_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<u32>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![1 => Uint32],
+    };
+
+    Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Shape Fill Batch Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[vertex_buffer_layout, feature_index_buffer_layout],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            compilation_options: Default::default(),
+            targets: &[Some(color_target_state.clone())],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: 1,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    }))
+}
+
+/// Stroke pipeline of batched features, the feature index of each vertex is in a second vertex
+/// buffer. None if the device does not support feature batches.
+pub fn create_shape_stroke_batch_pipeline(
+    device: &Device,
+    color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
+) -> Option<RenderPipeline> {
+    let shape_batch_params_bgl = bind_group_layouts.shape_batch_params.as_ref()?;
+
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shape Stroke Batch Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/shape_stroke_batch.wgsl"),
+            include_str!("../wgsl/stroke.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
+    });
 
     let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-        label: Some("Symbol Circle PipelineLayout"),
-        bind_group_layouts: &[&map_view_bgl, &symbol_circle_params_bgl],
+        label: Some("Shape Stroke Batch PipelineLayout"),
+        bind_group_layouts: &[&bind_group_layouts.map_view, shape_batch_params_bgl],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 8]>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x2, 3 => Float32, 4 => Float32],
+    };
+    let feature_index_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<u32>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![5 => Uint32],
+    };
+
+    Some(device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Shape Stroke Batch Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[vertex_buffer_layout, feature_index_buffer_layout],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            compilation_options: Default::default(),
+            targets: &[Some(color_target_state.clone())],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: 1,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    }))
+}
+
+pub fn create_symbol_shape_pipeline(
+    device: &Device,
+    color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Symbol Shape Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/symbol_shape.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Symbol Shape PipelineLayout"),
+        bind_group_layouts: &[
+            &bind_group_layouts.map_view,
+            &bind_group_layouts.symbol_shape_params,
+        ],
         push_constant_ranges: &[],
     });
 
@@ -210,7 +344,7 @@ pub fn create_symbol_circle_pipeline(
     };
 
     device.create_render_pipeline(&RenderPipelineDescriptor {
-        label: Some("Symbol Circle Pipeline"),
+        label: Some("Symbol Shape Pipeline"),
         layout: Some(&pipeline_layout),
         vertex: VertexState {
             module: &shader,
@@ -244,3 +378,109 @@ pub fn create_symbol_circle_pipeline(
         cache: None,
     })
 }
+
+pub fn create_symbol_icon_pipeline(
+    device: &Device,
+    color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Symbol Icon Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+            include_str!("../wgsl/symbol_icon.wgsl"),
+            include_str!("../wgsl/map_view.wgsl")
+        ))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Symbol Icon PipelineLayout"),
+        bind_group_layouts: &[
+            &bind_group_layouts.map_view,
+            &bind_group_layouts.image_texture,
+            &bind_group_layouts.symbol_icon_params,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 2]>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+    };
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Symbol Icon Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[vertex_buffer_layout],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            compilation_options: Default::default(),
+            targets: &[Some(color_target_state.clone())],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: 1,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+pub fn create_text_pipeline(
+    device: &Device,
+    color_target_state: &ColorTargetState,
+    bind_group_layouts: &BindGroupLayouts,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Text Shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("../wgsl/text.wgsl"))),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Text PipelineLayout"),
+        bind_group_layouts: &[&bind_group_layouts.image_texture],
+        push_constant_ranges: &[],
+    });
+
+    let vertex_buffer_layout = VertexBufferLayout {
+        array_stride: std::mem::size_of::<[f32; 14]>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4, 3 => Float32x4, 4 => Float32x2],
+    };
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Text Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[vertex_buffer_layout],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            compilation_options: Default::default(),
+            targets: &[Some(color_target_state.clone())],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: 1,
+            ..Default::default()
+        },
+        multiview: None,
+        cache: None,
+    })
+}
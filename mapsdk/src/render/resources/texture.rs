@@ -1,3 +1,6 @@
+use std::sync::mpsc;
+
+use image::RgbaImage;
 use wgpu::*;
 
 pub fn create_depth_texture(device: &Device, width: u32, height: u32) -> Texture {
@@ -46,3 +49,66 @@ pub fn create_texture(device: &Device, width: u32, height: u32, format: TextureF
 
     device.create_texture(&texture_desc)
 }
+
+pub fn read_texture(device: &Device, queue: &Queue, texture: &Texture) -> Option<RgbaImage> {
+    let width = texture.width();
+    let height = texture.height();
+
+    let unpadded_bytes_per_row = 4 * width;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+        * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Texture Readback Buffer"),
+        size: (padded_bytes_per_row * height) as BufferAddress,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut command_encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Texture Readback CommandEncoder"),
+    });
+    command_encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(command_encoder.finish()));
+
+    let buffer_slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    buffer_slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+
+    if let Err(err) = receiver.recv().ok()? {
+        log::error!("Read texture error: {}", err);
+        return None;
+    }
+
+    let mut pixels: Vec<u8> = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let data = buffer_slice.get_mapped_range();
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    match texture.format() {
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            pixels.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        _ => (),
+    }
+
+    RgbaImage::from_raw(width, height, pixels)
+}
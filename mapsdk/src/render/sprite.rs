@@ -0,0 +1,291 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use image::{imageops, RgbaImage};
+use wgpu::*;
+
+use crate::render::resources::{
+    allocation::AllocationCounter, atlas::ShelfPacker, bind_group::create_image_texture_bg,
+    texture::create_texture,
+};
+
+const ATLAS_SIZE: u32 = 1024;
+
+/// Image of an icon, with the number of image pixels per logical pixel.
+#[derive(Clone)]
+pub struct Icon {
+    pub image: Arc<RgbaImage>,
+    pub pixel_ratio: f32,
+}
+
+impl Icon {
+    /// Decode a raster image, e.g. PNG, or rasterize an SVG image at the pixel ratio.
+    pub fn from_bytes(data: &[u8], pixel_ratio: f32) -> Result<Self, Box<dyn Error>> {
+        if pixel_ratio <= 0.0 {
+            return Err("Invalid icon pixel ratio".into());
+        }
+
+        let image = if is_svg(data) {
+            rasterize_svg(data, pixel_ratio)?
+        } else {
+            image::load_from_memory(data)?.to_rgba8()
+        };
+
+        Ok(Self {
+            image: Arc::new(image),
+            pixel_ratio,
+        })
+    }
+}
+
+fn is_svg(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+}
+
+#[cfg(feature = "svg")]
+fn rasterize_svg(data: &[u8], pixel_ratio: f32) -> Result<RgbaImage, Box<dyn Error>> {
+    use resvg::{tiny_skia, usvg};
+
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+    let width = (tree.size().width() * pixel_ratio).ceil() as u32;
+    let height = (tree.size().height() * pixel_ratio).ceil() as u32;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or("Invalid SVG icon size")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(pixel_ratio, pixel_ratio),
+        &mut pixmap.as_mut(),
+    );
+
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "Invalid SVG icon image".into())
+}
+
+#[cfg(not(feature = "svg"))]
+fn rasterize_svg(_data: &[u8], _pixel_ratio: f32) -> Result<RgbaImage, Box<dyn Error>> {
+    Err("SVG icons require the svg feature".into())
+}
+
+/// Rect of an icon in the sprite atlas.
+#[derive(Clone, Debug)]
+pub struct SpriteIcon {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_ratio: f32,
+}
+
+impl SpriteIcon {
+    /// Rect of the icon in texture coords as `[xmin, ymin, xmax, ymax]`.
+    pub fn tex_rect(&self) -> [f32; 4] {
+        let size = ATLAS_SIZE as f32;
+
+        [
+            self.x as f32 / size,
+            self.y as f32 / size,
+            (self.x + self.width) as f32 / size,
+            (self.y + self.height) as f32 / size,
+        ]
+    }
+}
+
+/// Icons packed into a texture, referenced by name from icon styles.
+pub struct SpriteAtlas {
+    texture: Texture,
+    bind_group: BindGroup,
+    slots: SpriteSlots,
+    icons: HashMap<String, SpriteIcon>,
+}
+
+/// Packed rects of icons by name, as `[x, y, width, height]`.
+struct SpriteSlots {
+    packer: ShelfPacker,
+    slots: HashMap<String, [u32; 4]>,
+}
+
+impl SpriteSlots {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            packer: ShelfPacker::new(width, height),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Rect of an icon, a replacement fitting into the rect of the icon reuses it as packed rects
+    /// are never freed. `None` if the atlas is full.
+    fn allocate(&mut self, name: &str, width: u32, height: u32) -> Option<[u32; 4]> {
+        if let Some(slot) = self.slots.get(name) {
+            if width <= slot[2] && height <= slot[3] {
+                return Some(*slot);
+            }
+        }
+
+        let (x, y) = self.packer.pack(width, height)?;
+        let slot = [x, y, width, height];
+        self.slots.insert(name.to_string(), slot);
+
+        Some(slot)
+    }
+}
+
+impl SpriteAtlas {
+    pub fn new(
+        device: &Device,
+        allocation_counter: &AllocationCounter,
+        bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let texture = create_texture(
+            device,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        let texture_view = texture.create_view(&TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = create_image_texture_bg(
+            device,
+            allocation_counter,
+            bind_group_layout,
+            &texture_view,
+            &sampler,
+        );
+
+        Self {
+            texture,
+            bind_group,
+            slots: SpriteSlots::new(ATLAS_SIZE, ATLAS_SIZE),
+            icons: HashMap::new(),
+        }
+    }
+
+    /// Add an icon, or replace the icon with the same name.
+    pub fn add_icon(&mut self, queue: &Queue, name: &str, icon: &Icon) {
+        let width = icon.image.width();
+        let height = icon.image.height();
+
+        let Some([x, y, slot_width, slot_height]) = self.slots.allocate(name, width, height) else {
+            log::warn!("Sprite atlas is full, skip icon {}", name);
+            return;
+        };
+
+        // Clear the rest of a reused slot, it is sampled by linear filtering at the icon edges
+        let image = if (width, height) == (slot_width, slot_height) {
+            icon.image.clone()
+        } else {
+            let mut image = RgbaImage::new(slot_width, slot_height);
+            imageops::replace(&mut image, icon.image.as_ref(), 0, 0);
+            Arc::new(image)
+        };
+
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            &image,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * slot_width),
+                rows_per_image: Some(slot_height),
+            },
+            Extent3d {
+                width: slot_width,
+                height: slot_height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.icons.insert(
+            name.to_string(),
+            SpriteIcon {
+                x,
+                y,
+                width,
+                height,
+                pixel_ratio: icon.pixel_ratio,
+            },
+        );
+    }
+
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    pub fn icon(&self, name: &str) -> Option<&SpriteIcon> {
+        self.icons.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba};
+
+    use super::*;
+
+    #[test]
+    fn test_icon_from_bytes() {
+        let mut bytes = Vec::new();
+        RgbaImage::from_pixel(8, 4, Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        let icon = Icon::from_bytes(&bytes, 2.0).unwrap();
+        assert_eq!((icon.image.width(), icon.image.height()), (8, 4));
+        assert_eq!(icon.pixel_ratio, 2.0);
+
+        assert!(Icon::from_bytes(&bytes, 0.0).is_err());
+        assert!(Icon::from_bytes(b"not an image", 1.0).is_err());
+        assert!(is_svg(b"\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"));
+    }
+
+    #[test]
+    fn test_replace_icon() {
+        let mut slots = SpriteSlots::new(64, 64);
+        assert_eq!(slots.allocate("a", 16, 16), Some([0, 0, 16, 16]));
+        assert_eq!(slots.allocate("b", 16, 16), Some([17, 0, 16, 16]));
+
+        // Replacements fitting into the slot reuse it
+        assert_eq!(slots.allocate("a", 8, 16), Some([0, 0, 16, 16]));
+        assert_eq!(slots.allocate("a", 16, 16), Some([0, 0, 16, 16]));
+
+        // Larger replacements are packed anew
+        assert_eq!(slots.allocate("a", 20, 8), Some([34, 0, 20, 8]));
+        assert_eq!(slots.allocate("a", 16, 8), Some([34, 0, 20, 8]));
+    }
+
+    #[test]
+    fn test_full_atlas() {
+        let mut slots = SpriteSlots::new(64, 64);
+        assert!(slots.allocate("a", 40, 40).is_some());
+        assert!(slots.allocate("b", 40, 20).is_some());
+        assert_eq!(slots.allocate("c", 30, 8), None);
+
+        // Icons of the full atlas can still be replaced in their slots
+        assert_eq!(slots.allocate("a", 32, 32), Some([0, 0, 40, 40]));
+        assert_eq!(slots.allocate("b", 48, 20), None);
+        assert_eq!(slots.allocate("b", 40, 20), Some([0, 41, 40, 20]));
+    }
+}
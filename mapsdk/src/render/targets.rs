@@ -36,3 +36,8 @@ impl Window {
         self.scale_factor
     }
 }
+
+pub enum RenderTarget {
+    Surface(wgpu::Surface<'static>),
+    Texture(wgpu::Texture),
+}
@@ -1,4 +1,5 @@
 pub mod circle;
+pub mod feature_batch;
 pub mod geometry;
 pub mod vector_tile;
 
@@ -14,14 +15,33 @@ impl Tessellations {
             strokes: Vec::new(),
         }
     }
+
+    /// Bounding box of the fill vertices as `[xmin, ymin, xmax, ymax]`.
+    pub fn fill_bbox(&self) -> Option<[f32; 4]> {
+        self.fills
+            .iter()
+            .flat_map(|fill| fill.vertices.iter())
+            .fold(None, |bbox, v| {
+                Some(match bbox {
+                    Some([xmin, ymin, xmax, ymax]) => [
+                        xmin.min(v[0]),
+                        ymin.min(v[1]),
+                        xmax.max(v[0]),
+                        ymax.max(v[1]),
+                    ],
+                    None => [v[0], v[1], v[0], v[1]],
+                })
+            })
+    }
 }
 
+/// Indices are narrowed to u16 when uploaded if they fit.
 pub struct FillVertexIndex {
     pub vertices: Vec<[f32; 2]>,
-    pub indices: Vec<u16>,
+    pub indices: Vec<u32>,
 }
 
 pub struct StrokeVertexIndex {
-    pub vertices: Vec<[f32; 7]>,
-    pub indices: Vec<u16>,
+    pub vertices: Vec<[f32; 8]>,
+    pub indices: Vec<u32>,
 }
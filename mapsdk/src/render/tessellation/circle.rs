@@ -21,7 +21,7 @@ pub fn tessellate_circle<T: CoordType>(
 ) -> Tessellations {
     let mut output: Tessellations = Tessellations::new();
 
-    let mut fill_tessellation: VertexBuffers<[f32; 2], u16> = VertexBuffers::new();
+    let mut fill_tessellation: VertexBuffers<[f32; 2], u32> = VertexBuffers::new();
     {
         let mut buffers_builder =
             BuffersBuilder::new(&mut fill_tessellation, |vertex: FillVertex| {
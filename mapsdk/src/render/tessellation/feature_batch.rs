@@ -0,0 +1,178 @@
+use geo::{Coord, MapCoords};
+
+use crate::{
+    feature::{Feature, Shape},
+    render::tessellation::{circle::tessellate_circle, geometry::tessellate_geometry},
+};
+
+/// Shapes of features packed into shared buffers, with the index of the feature of each vertex.
+/// Indices already include the vertex offsets, so all fills or strokes are drawn at once.
+///
+/// The vertex coords of each feature are relative to its own origin, the center of its bounding
+/// box, see [`crate::render::resources::bind_group::map_view`].
+pub struct FeatureBatchTessellation {
+    pub fill_vertices: Vec<[f32; 2]>,
+    pub fill_feature_indices: Vec<u32>,
+    pub fill_indices: Vec<u32>,
+    pub stroke_vertices: Vec<[f32; 8]>,
+    pub stroke_feature_indices: Vec<u32>,
+    pub stroke_indices: Vec<u32>,
+
+    /// By feature index.
+    pub origins: Vec<Coord>,
+}
+
+impl FeatureBatchTessellation {
+    pub fn new(features: &[Feature]) -> Self {
+        let mut fill_vertices = Vec::new();
+        let mut fill_feature_indices = Vec::new();
+        let mut fill_indices = Vec::new();
+        let mut stroke_vertices = Vec::new();
+        let mut stroke_feature_indices = Vec::new();
+        let mut stroke_indices = Vec::new();
+        let mut origins = Vec::with_capacity(features.len());
+
+        for (feature_index, feature) in features.iter().enumerate() {
+            let origin = feature.shape().bbox().center();
+            origins.push(origin);
+
+            let tessellations = match feature.shape() {
+                Shape::Circle { center, radius } => {
+                    tessellate_circle(&(*center - origin), *radius as f32, 6)
+                }
+                Shape::Geometry(geom) => {
+                    tessellate_geometry(&geom.map_coords(|coord| coord - origin))
+                }
+            };
+
+            for fill_vertex_index in &tessellations.fills {
+                let vertex_start = fill_vertices.len() as u32;
+
+                fill_vertices.extend_from_slice(&fill_vertex_index.vertices);
+                fill_feature_indices.resize(fill_vertices.len(), feature_index as u32);
+                fill_indices.extend(
+                    fill_vertex_index
+                        .indices
+                        .iter()
+                        .map(|index| index + vertex_start),
+                );
+            }
+
+            for stroke_vertex_index in &tessellations.strokes {
+                let vertex_start = stroke_vertices.len() as u32;
+
+                stroke_vertices.extend_from_slice(&stroke_vertex_index.vertices);
+                stroke_feature_indices.resize(stroke_vertices.len(), feature_index as u32);
+                stroke_indices.extend(
+                    stroke_vertex_index
+                        .indices
+                        .iter()
+                        .map(|index| index + vertex_start),
+                );
+            }
+        }
+
+        Self {
+            fill_vertices,
+            fill_feature_indices,
+            fill_indices,
+            stroke_vertices,
+            stroke_feature_indices,
+            stroke_indices,
+
+            origins,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{LineString, Polygon};
+
+    use super::*;
+
+    #[test]
+    fn test_feature_batch_tessellation() {
+        let square = |x: f64, y: f64| {
+            Polygon::new(
+                LineString::from(vec![
+                    (x, y),
+                    (x + 10.0, y),
+                    (x + 10.0, y + 10.0),
+                    (x, y + 10.0),
+                    (x, y),
+                ]),
+                vec![],
+            )
+        };
+        let features = vec![
+            Feature::new("a", Shape::Geometry(square(1000.0, 1000.0).into()), None),
+            Feature::new(
+                "b",
+                Shape::Geometry(LineString::from(vec![(1000.0, 1020.0), (1010.0, 1020.0)]).into()),
+                None,
+            ),
+            Feature::new("c", Shape::Geometry(square(1010.0, 1010.0).into()), None),
+        ];
+
+        let tessellation = FeatureBatchTessellation::new(&features);
+        assert_eq!(
+            tessellation.origins,
+            vec![
+                Coord {
+                    x: 1005.0,
+                    y: 1005.0
+                },
+                Coord {
+                    x: 1005.0,
+                    y: 1020.0
+                },
+                Coord {
+                    x: 1015.0,
+                    y: 1015.0
+                },
+            ]
+        );
+
+        assert_eq!(
+            tessellation.fill_vertices.len(),
+            tessellation.fill_feature_indices.len()
+        );
+        assert_eq!(
+            tessellation.stroke_vertices.len(),
+            tessellation.stroke_feature_indices.len()
+        );
+
+        // Lines have no fill, the fills of both squares have the same number of vertices
+        let fill_vertex_count = tessellation.fill_vertices.len();
+        assert_eq!(tessellation.fill_feature_indices[0], 0);
+        assert_eq!(tessellation.fill_feature_indices[fill_vertex_count - 1], 2);
+        assert_eq!(
+            tessellation
+                .fill_feature_indices
+                .iter()
+                .filter(|index| **index == 2)
+                .count(),
+            fill_vertex_count / 2
+        );
+        assert!(tessellation.stroke_feature_indices.contains(&1));
+
+        // Indices point to the vertices of their own feature
+        for triangle in tessellation.fill_indices.chunks(3) {
+            let feature_index = tessellation.fill_feature_indices[triangle[0] as usize];
+            assert!(triangle
+                .iter()
+                .all(|index| tessellation.fill_feature_indices[*index as usize] == feature_index));
+        }
+        assert!(tessellation
+            .stroke_indices
+            .iter()
+            .all(|index| (*index as usize) < tessellation.stroke_vertices.len()));
+
+        // Vertex coords are relative to the origin of their feature
+        assert!(tessellation
+            .fill_vertices
+            .iter()
+            .all(|v| v[0].abs() <= 5.0 && v[1].abs() <= 5.0));
+    }
+}
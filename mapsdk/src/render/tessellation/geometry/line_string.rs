@@ -3,6 +3,21 @@ use crate::{
     CoordType,
 };
 
+// Stroke vertex types, expanded by the stroke width, caps and joins in shape_stroke.wgsl
+const SEGMENT_START_LOW: f32 = 0.0;
+const SEGMENT_START_HIGH: f32 = 1.0;
+const SEGMENT_END_LOW: f32 = 2.0;
+const SEGMENT_END_HIGH: f32 = 3.0;
+const JOIN_CENTER: f32 = 4.0;
+const JOIN_PREV: f32 = 5.0;
+const JOIN_PREV_MITER: f32 = 6.0;
+const JOIN_NEXT_MITER: f32 = 7.0;
+const JOIN_NEXT: f32 = 8.0;
+const CAP_START_LOW: f32 = 9.0;
+const CAP_START_HIGH: f32 = 10.0;
+const CAP_END_LOW: f32 = 11.0;
+const CAP_END_HIGH: f32 = 12.0;
+
 pub fn tessellate_line_string<T: CoordType>(line_string: &geo::LineString<T>) -> Tessellations {
     let mut output: Tessellations = Tessellations::new();
 
@@ -11,138 +26,151 @@ pub fn tessellate_line_string<T: CoordType>(line_string: &geo::LineString<T>) ->
         .map(|v| [CoordType::to_f32(v.x), CoordType::to_f32(v.y)])
         .collect();
 
+    if vertices.len() < 2 {
+        return output;
+    }
+
     let is_closed = line_string.is_closed();
+    let seg_count = vertices.len() - 1;
 
-    {
-        let mut stroke_vertices: Vec<[f32; 7]> = Vec::new();
-        let mut stroke_indices: Vec<u16> = Vec::new();
-        let mut stroke_index_offset: u16 = 0;
-
-        let seg_count = vertices.len() - 1;
-        for i in 0..seg_count {
-            // Line start join
-            {
-                if i > 0 || (i == 0 && is_closed) {
-                    let v = vertices[i];
-                    let v_prev = if i == 0 {
-                        vertices[seg_count - 1]
-                    } else {
-                        vertices[i - 1]
-                    };
-                    let v_next = vertices[i + 1];
-
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 0.3]);
-
-                    stroke_indices.extend_from_slice(&[
-                        stroke_index_offset,
-                        stroke_index_offset + 2,
-                        stroke_index_offset + 1,
-                    ]);
-
-                    stroke_index_offset += 1;
-                }
-            }
-
-            // Straight line segment
-            {
-                // Line start
-                {
-                    let v = vertices[i];
-                    let v_prev = if i == 0 {
-                        if is_closed {
-                            vertices[seg_count - 1]
-                        } else {
-                            vertices[0]
-                        }
-                    } else {
-                        vertices[i - 1]
-                    };
-                    let v_next = vertices[i + 1];
-
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 0.1]);
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 0.2]);
-                }
-
-                // Line end
-                {
-                    let v = vertices[i + 1];
-                    let v_prev = vertices[i];
-                    let v_next = if i == seg_count - 1 {
-                        if is_closed {
-                            vertices[1]
-                        } else {
-                            vertices[i + 1]
-                        }
-                    } else {
-                        vertices[i + 2]
-                    };
-
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 1.1]);
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 1.2]);
-                }
-
-                stroke_indices.extend_from_slice(&[
-                    stroke_index_offset,
-                    stroke_index_offset + 1,
-                    stroke_index_offset + 2,
-                    stroke_index_offset + 2,
-                    stroke_index_offset + 1,
-                    stroke_index_offset + 3,
-                ]);
-
-                stroke_index_offset += 4;
-            }
-
-            // Line end join
-            {
-                if i < seg_count - 1 || (i == seg_count - 1 && is_closed) {
-                    let v = vertices[i + 1];
-                    let v_prev = vertices[i];
-                    let v_next = if i == seg_count - 1 {
-                        vertices[1]
-                    } else {
-                        vertices[i + 2]
-                    };
-
-                    stroke_vertices
-                        .push([v[0], v[1], v_prev[0], v_prev[1], v_next[0], v_next[1], 1.3]);
-
-                    stroke_indices.extend_from_slice(&[
-                        stroke_index_offset - 2,
-                        stroke_index_offset - 1,
-                        stroke_index_offset,
-                    ]);
-
-                    if i == seg_count - 1 {
-                        stroke_indices.extend_from_slice(&[
-                            stroke_index_offset - 2,
-                            stroke_index_offset,
-                            0,
-                        ]);
-                    } else {
-                        stroke_indices.extend_from_slice(&[
-                            stroke_index_offset - 2,
-                            stroke_index_offset,
-                            stroke_index_offset + 1,
-                        ]);
-                    }
-
-                    stroke_index_offset += 1;
-                }
-            }
-        }
+    // Distances along the line, keeping dash patterns continuous across segments
+    let mut distances: Vec<f32> = Vec::with_capacity(vertices.len());
+    let mut distance = 0.0;
+    distances.push(distance);
+    for pair in vertices.windows(2) {
+        distance += (pair[1][0] - pair[0][0]).hypot(pair[1][1] - pair[0][1]);
+        distances.push(distance);
+    }
+
+    let stroke_vertex = |i: usize, v_prev: [f32; 2], v_next: [f32; 2], vertex_type: f32| {
+        let v = vertices[i];
+        [
+            v[0],
+            v[1],
+            v_prev[0],
+            v_prev[1],
+            v_next[0],
+            v_next[1],
+            vertex_type,
+            distances[i],
+        ]
+    };
+
+    let mut stroke_vertices: Vec<[f32; 8]> = Vec::new();
+    let mut stroke_indices: Vec<u32> = Vec::new();
+
+    // Straight line segments, their ends are pulled back on the inner side of turns
+    for i in 0..seg_count {
+        let v_start = vertices[i];
+        let v_end = vertices[i + 1];
+
+        let v_before = if i > 0 {
+            vertices[i - 1]
+        } else if is_closed {
+            vertices[seg_count - 1]
+        } else {
+            v_start
+        };
+        let v_after = if i < seg_count - 1 {
+            vertices[i + 2]
+        } else if is_closed {
+            vertices[1]
+        } else {
+            v_end
+        };
+
+        stroke_vertices.extend_from_slice(&[
+            stroke_vertex(i, v_before, v_end, SEGMENT_START_LOW),
+            stroke_vertex(i, v_before, v_end, SEGMENT_START_HIGH),
+            stroke_vertex(i + 1, v_start, v_after, SEGMENT_END_LOW),
+            stroke_vertex(i + 1, v_start, v_after, SEGMENT_END_HIGH),
+        ]);
+
+        let seg_offset = (i * 4) as u32;
+        stroke_indices.extend_from_slice(&[
+            seg_offset,
+            seg_offset + 1,
+            seg_offset + 2,
+            seg_offset + 2,
+            seg_offset + 1,
+            seg_offset + 3,
+        ]);
+    }
+
+    // Joins between segments, filling the space between the segment ends
+    let join_count = if is_closed { seg_count } else { seg_count - 1 };
+    for i in 0..join_count {
+        let next = (i + 1) % seg_count;
+
+        let v_prev = vertices[i];
+        let v_next = vertices[next + 1];
 
-        output.strokes.push(StrokeVertexIndex {
-            vertices: stroke_vertices,
-            indices: stroke_indices,
-        });
+        let join_offset = stroke_vertices.len() as u32;
+        stroke_vertices.extend_from_slice(&[
+            stroke_vertex(i + 1, v_prev, v_next, JOIN_CENTER),
+            stroke_vertex(i + 1, v_prev, v_next, JOIN_PREV),
+            stroke_vertex(i + 1, v_prev, v_next, JOIN_PREV_MITER),
+            stroke_vertex(i + 1, v_prev, v_next, JOIN_NEXT_MITER),
+            stroke_vertex(i + 1, v_prev, v_next, JOIN_NEXT),
+        ]);
+
+        let prev_seg_offset = (i * 4) as u32;
+        let next_seg_offset = (next * 4) as u32;
+        stroke_indices.extend_from_slice(&[
+            prev_seg_offset + 2,
+            prev_seg_offset + 3,
+            join_offset,
+            next_seg_offset,
+            next_seg_offset + 1,
+            join_offset,
+            join_offset,
+            join_offset + 1,
+            join_offset + 2,
+            join_offset,
+            join_offset + 2,
+            join_offset + 3,
+            join_offset,
+            join_offset + 3,
+            join_offset + 4,
+        ]);
     }
 
+    // Caps at the ends of open lines
+    if !is_closed {
+        let v_first = vertices[0];
+        let v_last = vertices[seg_count];
+        let v_before_last = vertices[seg_count - 1];
+
+        let cap_offset = stroke_vertices.len() as u32;
+        stroke_vertices.extend_from_slice(&[
+            stroke_vertex(0, v_first, vertices[1], CAP_START_LOW),
+            stroke_vertex(0, v_first, vertices[1], CAP_START_HIGH),
+            stroke_vertex(seg_count, v_before_last, v_last, CAP_END_LOW),
+            stroke_vertex(seg_count, v_before_last, v_last, CAP_END_HIGH),
+        ]);
+
+        let last_seg_offset = ((seg_count - 1) * 4) as u32;
+        stroke_indices.extend_from_slice(&[
+            0,
+            1,
+            cap_offset,
+            cap_offset,
+            1,
+            cap_offset + 1,
+            last_seg_offset + 2,
+            last_seg_offset + 3,
+            cap_offset + 2,
+            cap_offset + 2,
+            last_seg_offset + 3,
+            cap_offset + 3,
+        ]);
+    }
+
+    output.strokes.push(StrokeVertexIndex {
+        vertices: stroke_vertices,
+        indices: stroke_indices,
+    });
+
     output
 }
 
@@ -158,3 +186,96 @@ pub fn tessellate_multi_line_string<T: CoordType>(
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::LineString;
+
+    use super::*;
+
+    fn tessellate(coords: Vec<(f64, f64)>) -> StrokeVertexIndex {
+        let mut tessellations = tessellate_line_string(&LineString::from(coords));
+        assert_eq!(tessellations.strokes.len(), 1);
+        tessellations.strokes.remove(0)
+    }
+
+    fn cap_count(stroke: &StrokeVertexIndex) -> usize {
+        stroke
+            .vertices
+            .iter()
+            .filter(|vertex| vertex[6] >= CAP_START_LOW)
+            .count()
+    }
+
+    fn assert_indices_in_bounds(stroke: &StrokeVertexIndex) {
+        assert_eq!(stroke.indices.len() % 3, 0);
+        assert!(stroke
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < stroke.vertices.len()));
+    }
+
+    #[test]
+    fn test_open_line_string() {
+        let stroke = tessellate(vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+
+        // 4 vertices per segment, 5 per join and 4 for the caps
+        assert_eq!(stroke.vertices.len(), 2 * 4 + 5 + 4);
+        assert_eq!(stroke.indices.len(), 2 * 6 + 15 + 12);
+        assert_eq!(cap_count(&stroke), 4);
+        assert_indices_in_bounds(&stroke);
+    }
+
+    #[test]
+    fn test_closed_line_string() {
+        let stroke = tessellate(vec![
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ]);
+
+        // Joined at every vertex including the closing one, without caps
+        assert_eq!(stroke.vertices.len(), 4 * 4 + 4 * 5);
+        assert_eq!(stroke.indices.len(), 4 * 6 + 4 * 15);
+        assert_eq!(cap_count(&stroke), 0);
+        assert_indices_in_bounds(&stroke);
+    }
+
+    #[test]
+    fn test_distances() {
+        let stroke = tessellate(vec![(0.0, 0.0), (3.0, 4.0), (3.0, 10.0)]);
+
+        // Segment, join and cap vertices carry the distance along the line of their vertex
+        for vertex in &stroke.vertices {
+            let distance = match (vertex[0], vertex[1]) {
+                (0.0, 0.0) => 0.0,
+                (3.0, 4.0) => 5.0,
+                (3.0, 10.0) => 11.0,
+                _ => panic!("Unexpected vertex {:?}", vertex),
+            };
+            assert_eq!(vertex[7], distance);
+        }
+
+        let segment_distances: Vec<f32> = stroke.vertices[..8].iter().map(|v| v[7]).collect();
+        assert_eq!(
+            segment_distances,
+            [0.0, 0.0, 5.0, 5.0, 5.0, 5.0, 11.0, 11.0]
+        );
+    }
+
+    #[test]
+    fn test_two_point_line_string() {
+        let stroke = tessellate(vec![(0.0, 0.0), (10.0, 0.0)]);
+
+        assert_eq!(stroke.vertices.len(), 4 + 4);
+        assert_eq!(stroke.indices.len(), 6 + 12);
+        assert_eq!(cap_count(&stroke), 4);
+        assert_indices_in_bounds(&stroke);
+
+        // Degenerate lines are not drawn
+        let tessellations = tessellate_line_string(&LineString::from(vec![(0.0, 0.0)]));
+        assert!(tessellations.strokes.is_empty());
+    }
+}
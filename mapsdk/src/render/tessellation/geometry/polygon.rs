@@ -16,7 +16,7 @@ pub fn tessellate_polygon<T: CoordType>(polygon: &geo::Polygon<T>) -> Tessellati
     let mut output: Tessellations = Tessellations::new();
 
     {
-        let mut fill_tessellation: VertexBuffers<[f32; 2], u16> = VertexBuffers::new();
+        let mut fill_tessellation: VertexBuffers<[f32; 2], u32> = VertexBuffers::new();
 
         let mut buffers_builder =
             BuffersBuilder::new(&mut fill_tessellation, |vertex: FillVertex| {
@@ -95,3 +95,45 @@ pub fn tessellate_multi_polygon<T: CoordType>(
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn test_tessellate_large_polygon() {
+        // More vertices than u16 indices can address
+        let vertex_count = 70_000;
+        let exterior: LineString = (0..=vertex_count)
+            .map(|i| {
+                let angle = (i % vertex_count) as f64 / vertex_count as f64 * PI * 2.0;
+                Coord {
+                    x: angle.cos() * 1000.0,
+                    y: angle.sin() * 1000.0,
+                }
+            })
+            .collect();
+        let tessellations = tessellate_polygon(&Polygon::new(exterior, vec![]));
+
+        let fill = &tessellations.fills[0];
+        assert!(fill.vertices.len() > u16::MAX as usize);
+        assert_eq!(fill.indices.len(), (vertex_count - 2) * 3);
+        assert!(fill.indices.iter().any(|i| *i > u16::MAX as u32));
+        assert!(fill
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < fill.vertices.len()));
+
+        let stroke = &tessellations.strokes[0];
+        assert!(stroke
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < stroke.vertices.len()));
+        assert_eq!(
+            *stroke.indices.iter().max().unwrap() as usize,
+            stroke.vertices.len() - 1
+        );
+    }
+}
@@ -1,17 +1,25 @@
+use std::collections::HashMap;
+
+use geo::Rect;
+
 use crate::{
-    feature::{style::ShapeStyles, Shape},
+    feature::{
+        style::{expression::EvaluationContext, ShapeStyles},
+        Shape,
+    },
     render::tessellation::{circle::tessellate_circle, geometry::tessellate_geometry},
     vector_tile::VectorTile,
+    JsonValue,
 };
 
 #[derive(Clone)]
 pub struct VectorTileTessellation {
     pub fill_vertices: Vec<[f32; 2]>,
-    pub fill_indices: Vec<u16>,
-    pub stroke_vertices: Vec<[f32; 7]>,
-    pub stroke_indices: Vec<u16>,
+    pub fill_indices: Vec<u32>,
+    pub stroke_vertices: Vec<[f32; 8]>,
+    pub stroke_indices: Vec<u32>,
 
-    pub tile_bbox: [f32; 4],
+    pub tile_bbox: Rect,
     pub shape_metas: Vec<VectorTileShapeMeta>,
 }
 
@@ -28,11 +36,29 @@ impl VectorTileTessellation {
         let mut stroke_vertex_start: i32 = 0;
         let mut stroke_index_start: u32 = 0;
 
-        for (shape_styles_index, (layer_name, _)) in layers_shape_styles.iter().enumerate() {
+        for (shape_styles_index, (layer_name, shape_styles)) in
+            layers_shape_styles.iter().enumerate()
+        {
+            // Filters depending on zoom are evaluated when rendering
+            let filter_on_tessellation = shape_styles
+                .expressions
+                .filter
+                .as_ref()
+                .is_some_and(|filter| !filter.is_zoom_dependent());
+
             if let Some(layer) = &vector_tile.layers().get(layer_name) {
                 for feature in &layer.features {
                     let shape = feature.shape();
 
+                    if filter_on_tessellation
+                        && !shape_styles.matches_filter(
+                            &EvaluationContext::new(feature.attrs())
+                                .with_geometry_type(shape.geometry_type()),
+                        )
+                    {
+                        continue;
+                    }
+
                     let tessellations = match shape {
                         Shape::Circle { center, radius } => tessellate_circle(center, *radius, 6),
                         Shape::Geometry(geom) => tessellate_geometry(geom),
@@ -74,8 +100,16 @@ impl VectorTileTessellation {
                     shape_metas.push(VectorTileShapeMeta {
                         shape_is_points: shape.is_points(),
                         shape_is_lines: shape.is_lines(),
+                        geometry_type: shape.geometry_type(),
                         shape_styles_index,
-
+                        // Only kept for evaluating style expressions
+                        attrs: if shape_styles.is_data_driven() {
+                            feature.attrs().clone()
+                        } else {
+                            None
+                        },
+
+                        fill_bbox: tessellations.fill_bbox(),
                         fill_buffer_index,
                         stroke_buffer_index,
                     });
@@ -83,13 +117,7 @@ impl VectorTileTessellation {
             }
         }
 
-        let bbox = vector_tile.bbox();
-        let tile_bbox = [
-            bbox.min().x as f32,
-            bbox.min().y as f32,
-            bbox.max().x as f32,
-            bbox.max().y as f32,
-        ];
+        let tile_bbox = vector_tile.bbox();
 
         Self {
             fill_vertices,
@@ -107,8 +135,77 @@ impl VectorTileTessellation {
 pub struct VectorTileShapeMeta {
     pub shape_is_points: bool,
     pub shape_is_lines: bool,
+    pub geometry_type: &'static str,
     pub shape_styles_index: usize,
+    pub attrs: Option<HashMap<String, JsonValue>>,
 
+    /// Bounding box of the fill in tile units, gradients are positioned relative to it.
+    pub fill_bbox: Option<[f32; 4]>,
     pub fill_buffer_index: Vec<(u32, u32, i32)>,
     pub stroke_buffer_index: Vec<(u32, u32, i32)>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use geo::{Coord, Rect};
+
+    use super::*;
+    use crate::feature::style::expression::Expression;
+
+    #[test]
+    fn test_filters() {
+        let mut data = Vec::new();
+        File::open("../assets/mvt.pbf")
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        let vector_tile = VectorTile::from_data(
+            data,
+            Rect::new(
+                Coord {
+                    x: -20037508.34278924,
+                    y: -20037508.34278924,
+                },
+                Coord {
+                    x: 20037508.34278924,
+                    y: 20037508.34278924,
+                },
+            ),
+        )
+        .unwrap();
+
+        let shape_styles_with_filter = |filter: &str| {
+            let mut shape_styles = ShapeStyles::default();
+            shape_styles.expressions.filter = Some(filter.parse::<Expression>().unwrap());
+            shape_styles
+        };
+        let layers_shape_styles = vec![
+            (
+                "geolines".to_string(),
+                shape_styles_with_filter(r#"["==", ["get", "name"], "Equator"]"#),
+            ),
+            (
+                "geolines".to_string(),
+                shape_styles_with_filter(r#"["!=", ["get", "name"], "Equator"]"#),
+            ),
+            (
+                "geolines".to_string(),
+                shape_styles_with_filter(r#"["==", ["geometry-type"], "Polygon"]"#),
+            ),
+        ];
+
+        let tessellation = VectorTileTessellation::new(&vector_tile, &layers_shape_styles);
+        let counts: Vec<usize> = (0..layers_shape_styles.len())
+            .map(|index| {
+                tessellation
+                    .shape_metas
+                    .iter()
+                    .filter(|shape_meta| shape_meta.shape_styles_index == index)
+                    .count()
+            })
+            .collect();
+        assert_eq!(counts, vec![1, 5, 0]);
+    }
+}
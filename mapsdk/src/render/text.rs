@@ -0,0 +1,601 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use ab_glyph::{Font, FontArc, ScaleFont};
+use geo::{Coord, Geometry, InteriorPoint, LineInterpolatePoint, LineString};
+use glam::{Mat4, Vec4};
+use wgpu::*;
+
+use crate::{
+    feature::{
+        style::{
+            expression::{EvaluationContext, Value},
+            LabelPlacement, LabelStyle, ShapeStyles,
+        },
+        Feature, Shape,
+    },
+    render::{
+        resources::{
+            allocation::AllocationCounter, bind_group::create_image_texture_bg,
+            buffer::create_vertex_buffer, texture::create_texture,
+        },
+        text::{
+            atlas::{GlyphAtlas, SDF_EDGE, SDF_FONT_SIZE, SDF_RADIUS},
+            placement::{place_line_label, place_point_label, CollisionIndex, PlacedLabel},
+        },
+        MapRenderingContext,
+    },
+    JsonValue,
+};
+
+mod atlas;
+mod placement;
+
+const ATLAS_SIZE: u32 = 1024;
+const LINE_HEIGHT: f32 = 1.2;
+/// Edge sharpness of glyphs at the SDF font size.
+const SDF_GAMMA: f32 = 0.105;
+
+#[derive(Clone, Debug)]
+pub enum LabelGeometry {
+    Point(Coord),
+    Line(Vec<Coord>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub geometry: LabelGeometry,
+    pub text: String,
+    /// Style resolved for the feature when the label was created.
+    pub style: LabelStyle,
+    /// Set if the visibility or label style depends on zoom, resolved again when drawn.
+    pub zoom_styles: Option<Arc<LabelZoomStyles>>,
+}
+
+/// Styles of the feature of labels, shared by the labels of its geometries.
+#[derive(Debug)]
+pub struct LabelZoomStyles {
+    pub shape_styles: ShapeStyles,
+    pub attrs: Option<HashMap<String, JsonValue>>,
+    /// Geometry type of the feature, e.g. `Polygon` for labels placed inside polygons.
+    pub geometry_type: &'static str,
+}
+
+impl Label {
+    /// Style of the label at a zoom, `None` if it is not visible.
+    fn style_at(&self, zoom: f64) -> Option<Cow<'_, LabelStyle>> {
+        match &self.zoom_styles {
+            Some(zoom_styles) => zoom_styles.shape_styles.resolve_label(
+                &EvaluationContext::new(&zoom_styles.attrs)
+                    .with_geometry_type(zoom_styles.geometry_type)
+                    .with_zoom(zoom),
+            ),
+            None => Some(Cow::Borrowed(&self.style)),
+        }
+    }
+}
+
+/// Labels of a feature, empty if the styles have no label or the feature is not visible.
+pub fn feature_labels(feature: &Feature, shape_styles: &ShapeStyles, zoom: f64) -> Vec<Label> {
+    let Some(style) = &shape_styles.label else {
+        return Vec::new();
+    };
+
+    let context = EvaluationContext::new(feature.attrs())
+        .with_geometry_type(feature.shape().geometry_type())
+        .with_zoom(zoom);
+
+    let zoom_dependent = shape_styles.is_zoom_dependent();
+    let style = match shape_styles.resolve_label(&context) {
+        Some(style) => style,
+        // Labels of zoom dependent styles are resolved again when drawn
+        None if zoom_dependent => Cow::Borrowed(style),
+        None => return Vec::new(),
+    };
+
+    let text = match style.text.evaluate(&context) {
+        Value::String(v) => v,
+        Value::Number(v) if v.fract() == 0.0 && v.abs() < 1e15 => format!("{}", v as i64),
+        Value::Number(v) => v.to_string(),
+        _ => return Vec::new(),
+    };
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let line_placement = style.placement == LabelPlacement::Line;
+    let mut geometries = Vec::new();
+    let mut add_line = |line: &LineString| {
+        if line_placement {
+            geometries.push(LabelGeometry::Line(line.0.clone()));
+        } else if let Some(point) = line.line_interpolate_point(0.5) {
+            geometries.push(LabelGeometry::Point(point.0));
+        }
+    };
+
+    match feature.shape() {
+        Shape::Geometry(geom) => match geom {
+            Geometry::Point(point) => geometries.push(LabelGeometry::Point(point.0)),
+            Geometry::MultiPoint(points) => points
+                .iter()
+                .for_each(|point| geometries.push(LabelGeometry::Point(point.0))),
+            Geometry::Line(line) => add_line(&LineString::from(*line)),
+            Geometry::LineString(line) => add_line(line),
+            Geometry::MultiLineString(lines) => lines.iter().for_each(add_line),
+            _ => {
+                if let Some(point) = geom.interior_point() {
+                    geometries.push(LabelGeometry::Point(point.0));
+                }
+            }
+        },
+        Shape::Circle { center, .. } => geometries.push(LabelGeometry::Point(*center)),
+    }
+
+    let zoom_styles = zoom_dependent.then(|| {
+        Arc::new(LabelZoomStyles {
+            shape_styles: shape_styles.clone(),
+            attrs: feature.attrs().clone(),
+            geometry_type: feature.shape().geometry_type(),
+        })
+    });
+
+    geometries
+        .into_iter()
+        .map(|geometry| Label {
+            geometry,
+            text: text.clone(),
+            style: (*style).clone(),
+            zoom_styles: zoom_styles.clone(),
+        })
+        .collect()
+}
+
+/// Glyph of shaped text, positioned in pixels of the SDF font size from the top left of the text.
+#[derive(Clone, Debug)]
+pub struct ShapedGlyph {
+    pub x: f32,
+    pub y: f32,
+    /// Center of the glyph advance, used to follow lines.
+    pub center: f32,
+    /// Rect of the glyph in the atlas as `[x, y, width, height]`.
+    pub tex_rect: [f32; 4],
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Lay out text in lines at the SDF font size, with kerning and centered lines.
+fn shape_text(atlas: &mut GlyphAtlas, font_name: &str, font: &FontArc, text: &str) -> ShapedText {
+    let scaled_font = font.as_scaled(SDF_FONT_SIZE);
+    let line_height = SDF_FONT_SIZE * LINE_HEIGHT;
+    // Center the glyphs vertically in their line
+    let baseline = (line_height + scaled_font.ascent() + scaled_font.descent()) / 2.0;
+
+    let mut shaped_text = ShapedText::default();
+    let mut lines = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_start = shaped_text.glyphs.len();
+        let y = i as f32 * line_height + baseline;
+
+        let mut x = 0.0;
+        let mut last_id = None;
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(last_id) = last_id {
+                x += scaled_font.kern(last_id, id);
+            }
+            last_id = Some(id);
+
+            let advance = scaled_font.h_advance(id);
+            if let Some(glyph) = atlas.glyph(font_name, font, id) {
+                shaped_text.glyphs.push(ShapedGlyph {
+                    x: x + glyph.left,
+                    y: y + glyph.top,
+                    center: x + advance / 2.0,
+                    tex_rect: [
+                        glyph.x as f32,
+                        glyph.y as f32,
+                        glyph.width as f32,
+                        glyph.height as f32,
+                    ],
+                });
+            }
+            x += advance;
+        }
+
+        lines.push((line_start, x));
+        shaped_text.width = shaped_text.width.max(x);
+        shaped_text.height += line_height;
+    }
+
+    for (i, (line_start, line_width)) in lines.iter().enumerate() {
+        let line_end = lines
+            .get(i + 1)
+            .map_or(shaped_text.glyphs.len(), |line| line.0);
+        let dx = (shaped_text.width - line_width) / 2.0;
+
+        for glyph in &mut shaped_text.glyphs[*line_start..line_end] {
+            glyph.x += dx;
+            glyph.center += dx;
+        }
+    }
+
+    shaped_text
+}
+
+/// Projection of map coords to physical screen pixels, with y down.
+pub struct LabelView {
+    pub view_proj: Mat4,
+    /// Map centers of the world copies, labels are placed in each of them.
+    pub centers: Vec<Coord>,
+    pub map_res: f64,
+    pub width: f32,
+    pub height: f32,
+    pub pixel_ratio: f32,
+    pub zoom: f64,
+}
+
+impl LabelView {
+    fn project(&self, coord: &Coord, center: &Coord) -> Option<[f32; 2]> {
+        let clip = self.view_proj
+            * Vec4::new(
+                ((coord.x - center.x) / self.map_res) as f32,
+                ((coord.y - center.y) / self.map_res) as f32,
+                0.0,
+                1.0,
+            );
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        Some([
+            (clip.x / clip.w + 1.0) / 2.0 * self.width,
+            (1.0 - clip.y / clip.w) / 2.0 * self.height,
+        ])
+    }
+
+    fn to_ndc(&self, point: &[f32; 2]) -> [f32; 2] {
+        [
+            point[0] / self.width * 2.0 - 1.0,
+            1.0 - point[1] / self.height * 2.0,
+        ]
+    }
+}
+
+struct ShapedLabel {
+    label: Label,
+    text: ShapedText,
+}
+
+/// Shapes labels of layers, places them on screen without collisions and draws them.
+pub struct LabelRenderer {
+    atlas: GlyphAtlas,
+    atlas_texture: Texture,
+    atlas_bg: BindGroup,
+    /// Rewritten for every frame, only recreated when the placed labels outgrow it.
+    vertex_buffer: Mutex<Option<Buffer>>,
+
+    layer_labels: HashMap<String, BTreeMap<String, Vec<ShapedLabel>>>,
+}
+
+impl LabelRenderer {
+    pub fn new(
+        device: &Device,
+        allocation_counter: &AllocationCounter,
+        atlas_bgl: &BindGroupLayout,
+    ) -> Self {
+        let atlas = GlyphAtlas::new(ATLAS_SIZE, ATLAS_SIZE);
+        let atlas_texture = create_texture(device, ATLAS_SIZE, ATLAS_SIZE, TextureFormat::R8Unorm);
+        let atlas_texture_view = atlas_texture.create_view(&TextureViewDescriptor::default());
+
+        let atlas_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let atlas_bg = create_image_texture_bg(
+            device,
+            allocation_counter,
+            atlas_bgl,
+            &atlas_texture_view,
+            &atlas_sampler,
+        );
+
+        Self {
+            atlas,
+            atlas_texture,
+            atlas_bg,
+            vertex_buffer: Mutex::new(None),
+
+            layer_labels: HashMap::new(),
+        }
+    }
+
+    /// Shape labels of a draw item, labels with fonts not added are skipped.
+    pub fn add_labels(
+        &mut self,
+        queue: &Queue,
+        fonts: &HashMap<String, FontArc>,
+        layer_name: &str,
+        item_id: &str,
+        labels: Vec<Label>,
+    ) {
+        let mut shaped_labels = Vec::with_capacity(labels.len());
+        for label in labels {
+            let Some(font) = fonts.get(&label.style.font) else {
+                log::warn!(
+                    "Font {} not found, skip label {}",
+                    label.style.font,
+                    label.text
+                );
+                continue;
+            };
+
+            let text = shape_text(&mut self.atlas, &label.style.font, font, &label.text);
+            shaped_labels.push(ShapedLabel { label, text });
+        }
+
+        if self.atlas.take_dirty() {
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &self.atlas_texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                self.atlas.data(),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.atlas.width()),
+                    rows_per_image: Some(self.atlas.height()),
+                },
+                self.atlas_texture.size(),
+            );
+        }
+
+        self.layer_labels
+            .entry(layer_name.to_string())
+            .or_default()
+            .insert(item_id.to_string(), shaped_labels);
+    }
+
+    pub fn clear_labels(&mut self, layer_name: &str) {
+        self.layer_labels.remove(layer_name);
+    }
+
+    /// Place labels of the layers in all world copies, top layers first, and draw the ones not
+    /// colliding.
+    pub fn draw(
+        &self,
+        rendering_context: &MapRenderingContext,
+        render_pass: &mut RenderPass,
+        layers_order: &[String],
+        view: &LabelView,
+    ) {
+        let mut collision_index = CollisionIndex::new(64.0 * view.pixel_ratio);
+        let mut vertices: Vec<[f32; 14]> = Vec::new();
+
+        for layer_name in layers_order.iter().rev() {
+            let Some(item_labels) = self.layer_labels.get(layer_name) else {
+                continue;
+            };
+
+            for shaped_label in item_labels.values().flatten() {
+                let ShapedLabel { label, text } = shaped_label;
+                if text.glyphs.is_empty() {
+                    continue;
+                }
+                let Some(style) = label.style_at(view.zoom) else {
+                    continue;
+                };
+
+                let scale = style.size * view.pixel_ratio / SDF_FONT_SIZE;
+                for center in &view.centers {
+                    let placed = match &label.geometry {
+                        LabelGeometry::Point(coord) => view.project(coord, center).map(|point| {
+                            place_point_label(text, &style, point, scale, view.pixel_ratio)
+                        }),
+                        LabelGeometry::Line(coords) => coords
+                            .iter()
+                            .map(|coord| view.project(coord, center))
+                            .collect::<Option<Vec<_>>>()
+                            .and_then(|points| {
+                                place_line_label(text, &style, &points, scale, view.pixel_ratio)
+                            }),
+                    };
+
+                    let Some(placed) = placed else {
+                        continue;
+                    };
+                    let on_screen = placed.boxes.iter().any(|bbox| {
+                        bbox[2] > 0.0
+                            && bbox[0] < view.width
+                            && bbox[3] > 0.0
+                            && bbox[1] < view.height
+                    });
+                    if !on_screen || !collision_index.try_insert(&placed.boxes) {
+                        continue;
+                    }
+
+                    push_label_vertices(&mut vertices, &placed, &style, scale, view);
+                }
+            }
+        }
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        let MapRenderingContext {
+            device,
+            queue,
+            allocation_counter,
+            text_pipeline,
+            ..
+        } = rendering_context;
+
+        let Ok(mut cached_vertex_buffer) = self.vertex_buffer.lock() else {
+            return;
+        };
+
+        let contents: &[u8] = bytemuck::cast_slice(&vertices);
+        let size = contents.len() as BufferAddress;
+        let vertex_buffer = match cached_vertex_buffer.take() {
+            Some(vertex_buffer) if vertex_buffer.size() >= size => vertex_buffer,
+            _ => {
+                allocation_counter.add_buffers(1);
+                create_vertex_buffer(device, "Text VertexBuffer", size.next_power_of_two())
+            }
+        };
+        queue.write_buffer(&vertex_buffer, 0, contents);
+
+        render_pass.set_pipeline(text_pipeline);
+        render_pass.set_bind_group(0, &self.atlas_bg, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..size));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+
+        *cached_vertex_buffer = Some(vertex_buffer);
+    }
+
+    pub fn remove_labels(&mut self, layer_name: &str, item_id: &str) {
+        if let Some(item_labels) = self.layer_labels.get_mut(layer_name) {
+            item_labels.remove(item_id);
+        }
+    }
+
+    pub fn retain_labels(&mut self, layer_name: &str, mut f: impl FnMut(&str) -> bool) {
+        if let Some(item_labels) = self.layer_labels.get_mut(layer_name) {
+            item_labels.retain(|item_id, _| f(item_id));
+        }
+    }
+}
+
+/// Two triangles per glyph quad, with position, texture coord, color, halo color, edge gamma and
+/// halo edge per vertex.
+fn push_label_vertices(
+    vertices: &mut Vec<[f32; 14]>,
+    placed: &PlacedLabel,
+    style: &LabelStyle,
+    scale: f32,
+    view: &LabelView,
+) {
+    let color: [f32; 4] = style.color.clone().into();
+    let mut halo_color: [f32; 4] = style.halo_color.clone().into();
+    if style.halo_width <= 0.0 {
+        halo_color[3] = 0.0;
+    }
+
+    let gamma = SDF_GAMMA / scale;
+    let halo_edge =
+        (SDF_EDGE - style.halo_width * view.pixel_ratio / (SDF_RADIUS * scale)).max(gamma);
+
+    for quad in &placed.quads {
+        let [x, y, w, h] = quad.tex_rect;
+        let tex_coords = [[x, y], [x + w, y], [x, y + h], [x + w, y + h]]
+            .map(|[u, v]| [u / ATLAS_SIZE as f32, v / ATLAS_SIZE as f32]);
+
+        for i in [0, 1, 2, 1, 3, 2] {
+            let position = view.to_ndc(&quad.corners[i]);
+            let tex_coord = tex_coords[i];
+
+            vertices.push([
+                position[0],
+                position[1],
+                tex_coord[0],
+                tex_coord[1],
+                color[0],
+                color[1],
+                color[2],
+                color[3],
+                halo_color[0],
+                halo_color[1],
+                halo_color[2],
+                halo_color[3],
+                gamma,
+                halo_edge,
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature::style::expression::Expression;
+
+    #[test]
+    fn test_shape_text() {
+        let font = FontArc::try_from_vec(std::fs::read("../assets/Cantarell-Regular.ttf").unwrap())
+            .unwrap();
+        let mut atlas = GlyphAtlas::new(256, 256);
+
+        let shaped_text = shape_text(&mut atlas, "sans", &font, "AV A\nA");
+        // Spaces have no glyph
+        assert_eq!(shaped_text.glyphs.len(), 4);
+        assert_eq!(shaped_text.height, SDF_FONT_SIZE * LINE_HEIGHT * 2.0);
+
+        // The second line is centered
+        let first = &shaped_text.glyphs[0];
+        let last = &shaped_text.glyphs[3];
+        assert!(last.x > first.x);
+        assert!(last.y > first.y);
+        assert!((last.center - shaped_text.width / 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_feature_labels() {
+        let attrs = Some(HashMap::from([("ref".to_string(), JsonValue::from(66))]));
+        let feature = Feature::new(
+            "0",
+            Shape::Geometry(Geometry::LineString(LineString::from(vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+            ]))),
+            attrs,
+        );
+
+        let mut shape_styles = ShapeStyles::default();
+        assert!(feature_labels(&feature, &shape_styles, 0.0).is_empty());
+
+        shape_styles.label = Some(LabelStyle {
+            text: Expression::Get("ref".to_string()),
+            ..Default::default()
+        });
+        let labels = feature_labels(&feature, &shape_styles, 0.0);
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].text, "66");
+        assert!(matches!(labels[0].geometry, LabelGeometry::Point(Coord { x, y: _ }) if x == 5.0));
+
+        shape_styles.label.as_mut().unwrap().placement = LabelPlacement::Line;
+        let labels = feature_labels(&feature, &shape_styles, 0.0);
+        assert!(matches!(&labels[0].geometry, LabelGeometry::Line(coords) if coords.len() == 2));
+
+        shape_styles.expressions.visible = Some(r#"[">=", ["zoom"], 10]"#.parse().unwrap());
+        shape_styles.expressions.label_size = Some(Expression::zoom_step(14.0, vec![(14.0, 20.0)]));
+        let labels = feature_labels(&feature, &shape_styles, 0.0);
+        assert!(labels[0].style_at(5.0).is_none());
+        assert_eq!(labels[0].style_at(12.0).unwrap().size, 14.0);
+        assert_eq!(labels[0].style_at(16.0).unwrap().size, 20.0);
+
+        // Filtered by the geometry type of the feature, not of the label placed at a point
+        shape_styles.label.as_mut().unwrap().placement = LabelPlacement::Point;
+        shape_styles.expressions.filter =
+            Some(r#"["==", ["geometry-type"], "LineString"]"#.parse().unwrap());
+        let labels = feature_labels(&feature, &shape_styles, 0.0);
+        assert!(matches!(labels[0].geometry, LabelGeometry::Point(_)));
+        assert!(labels[0].style_at(12.0).is_some());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use ab_glyph::{point, Font, FontArc, GlyphId};
+
+use crate::render::resources::atlas::ShelfPacker;
+
+/// Font size the glyphs are rasterized with, labels of other sizes scale the distance field.
+pub const SDF_FONT_SIZE: f32 = 24.0;
+/// Pixels around a glyph, the distance field reaches into it for halos.
+pub const SDF_BUFFER: u32 = 3;
+/// Distance field value of glyph edges.
+pub const SDF_EDGE: f32 = 0.75;
+/// Distance in pixels covered by the distance field on each side of an edge.
+pub const SDF_RADIUS: f32 = 8.0;
+
+const INF: f64 = 1e20;
+
+#[derive(Clone, Debug)]
+pub struct AtlasGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Offset of the bitmap from the pen position on the baseline, y is down.
+    pub left: f32,
+    pub top: f32,
+}
+
+/// Signed distance fields of glyphs packed into a single channel texture.
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    glyphs: HashMap<(String, GlyphId), Option<AtlasGlyph>>,
+    packer: ShelfPacker,
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; (width * height) as usize],
+            glyphs: HashMap::new(),
+            packer: ShelfPacker::new(width, height),
+            dirty: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether glyphs were added since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Get a glyph, rasterizing it on first use. Glyphs without outlines, e.g. spaces, and glyphs
+    /// not fitting into the atlas anymore return `None`.
+    pub fn glyph(&mut self, font_name: &str, font: &FontArc, id: GlyphId) -> Option<AtlasGlyph> {
+        let key = (font_name.to_string(), id);
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return glyph.clone();
+        }
+
+        let glyph = self.rasterize(font, id);
+        self.glyphs.insert(key, glyph.clone());
+
+        glyph
+    }
+
+    fn rasterize(&mut self, font: &FontArc, id: GlyphId) -> Option<AtlasGlyph> {
+        let outlined =
+            font.outline_glyph(id.with_scale_and_position(SDF_FONT_SIZE, point(0.0, 0.0)))?;
+        let bounds = outlined.px_bounds();
+
+        let glyph_width = bounds.width() as u32;
+        let glyph_height = bounds.height() as u32;
+        let mut coverage = vec![0.0; (glyph_width * glyph_height) as usize];
+        outlined.draw(|x, y, c| {
+            if x < glyph_width && y < glyph_height {
+                coverage[(y * glyph_width + x) as usize] = c;
+            }
+        });
+
+        let (sdf, width, height) = glyph_sdf(&coverage, glyph_width, glyph_height);
+
+        let Some((x, y)) = self.packer.pack(width, height) else {
+            log::warn!("Glyph atlas is full, skip glyph {:?}", id);
+            return None;
+        };
+
+        for row in 0..height {
+            let start = ((y + row) * self.width + x) as usize;
+            let src_start = (row * width) as usize;
+            self.data[start..start + width as usize]
+                .copy_from_slice(&sdf[src_start..src_start + width as usize]);
+        }
+
+        self.dirty = true;
+
+        Some(AtlasGlyph {
+            x,
+            y,
+            width,
+            height,
+            left: bounds.min.x - SDF_BUFFER as f32,
+            top: bounds.min.y - SDF_BUFFER as f32,
+        })
+    }
+}
+
+/// Compute the signed distance field of a glyph coverage bitmap, with the buffer added around.
+///
+/// Reference: https://github.com/mapbox/tiny-sdf
+fn glyph_sdf(coverage: &[f32], glyph_width: u32, glyph_height: u32) -> (Vec<u8>, u32, u32) {
+    let width = (glyph_width + SDF_BUFFER * 2) as usize;
+    let height = (glyph_height + SDF_BUFFER * 2) as usize;
+
+    let mut grid_outer = vec![INF; width * height];
+    let mut grid_inner = vec![0.0; width * height];
+
+    for y in 0..glyph_height as usize {
+        for x in 0..glyph_width as usize {
+            let a = coverage[y * glyph_width as usize + x] as f64;
+            if a == 0.0 {
+                continue;
+            }
+
+            let i = (y + SDF_BUFFER as usize) * width + x + SDF_BUFFER as usize;
+            if a >= 1.0 {
+                grid_outer[i] = 0.0;
+                grid_inner[i] = INF;
+            } else {
+                let d = 0.5 - a;
+                grid_outer[i] = if d > 0.0 { d * d } else { 0.0 };
+                grid_inner[i] = if d < 0.0 { d * d } else { 0.0 };
+            }
+        }
+    }
+
+    edt(&mut grid_outer, width, height);
+    edt(&mut grid_inner, width, height);
+
+    let cutoff = 1.0 - SDF_EDGE as f64;
+    let sdf = grid_outer
+        .iter()
+        .zip(grid_inner.iter())
+        .map(|(outer, inner)| {
+            let d = outer.sqrt() - inner.sqrt();
+            (255.0 - 255.0 * (d / SDF_RADIUS as f64 + cutoff))
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+        .collect();
+
+    (sdf, width as u32, height as u32)
+}
+
+/// 2D squared euclidean distance transform.
+fn edt(grid: &mut [f64], width: usize, height: usize) {
+    let size = width.max(height);
+    let mut f = vec![0.0; size];
+    let mut v = vec![0; size];
+    let mut z = vec![0.0; size + 1];
+
+    for x in 0..width {
+        edt_1d(grid, x, width, height, &mut f, &mut v, &mut z);
+    }
+    for y in 0..height {
+        edt_1d(grid, y * width, 1, width, &mut f, &mut v, &mut z);
+    }
+}
+
+fn edt_1d(
+    grid: &mut [f64],
+    offset: usize,
+    stride: usize,
+    length: usize,
+    f: &mut [f64],
+    v: &mut [usize],
+    z: &mut [f64],
+) {
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+    f[0] = grid[offset];
+
+    let mut k: usize = 0;
+    for q in 1..length {
+        f[q] = grid[offset + q * stride];
+        let q2 = (q * q) as f64;
+
+        let mut s;
+        loop {
+            let r = v[k];
+            s = (f[q] - f[r] + q2 - (r * r) as f64) / (q - r) as f64 / 2.0;
+
+            if s <= z[k] && k > 0 {
+                k -= 1;
+            } else {
+                break;
+            }
+        }
+
+        if s > z[k] {
+            k += 1;
+        }
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+
+    let mut k = 0;
+    for q in 0..length {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let r = v[k];
+        let qr = q as f64 - r as f64;
+        grid[offset + q * stride] = f[r] + qr * qr;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_sdf() {
+        // A filled 4x4 square
+        let (sdf, width, height) = glyph_sdf(&[1.0; 16], 4, 4);
+        assert_eq!((width, height), (10, 10));
+
+        let value = |x: usize, y: usize| sdf[y * width as usize + x] as f32 / 255.0;
+        assert!(value(5, 5) > SDF_EDGE);
+        assert!(value(0, 0) < SDF_EDGE);
+        // Values decrease away from the glyph
+        assert!(value(3, 5) > value(2, 5) && value(2, 5) > value(1, 5));
+    }
+
+    #[test]
+    fn test_glyph_atlas() {
+        let font = FontArc::try_from_vec(std::fs::read("../assets/Cantarell-Regular.ttf").unwrap())
+            .unwrap();
+        let mut atlas = GlyphAtlas::new(256, 256);
+
+        let a = atlas.glyph("sans", &font, font.glyph_id('A')).unwrap();
+        assert!(a.width > SDF_BUFFER * 2 && a.height > SDF_BUFFER * 2);
+        assert!(a.top < 0.0);
+        assert!(atlas.take_dirty());
+        assert!(!atlas.take_dirty());
+
+        let b = atlas.glyph("sans", &font, font.glyph_id('B')).unwrap();
+        assert!(b.x > a.x);
+        assert!(atlas.glyph("sans", &font, font.glyph_id(' ')).is_none());
+        assert_eq!(
+            atlas.glyph("sans", &font, font.glyph_id('A')).unwrap().x,
+            a.x
+        );
+    }
+}
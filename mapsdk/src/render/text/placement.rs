@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use crate::{feature::style::LabelStyle, render::text::ShapedText};
+
+/// Max angle in degrees between neighboring glyphs of line labels.
+const LINE_LABEL_MAX_ANGLE: f32 = 45.0;
+
+/// Screen space boxes as `[xmin, ymin, xmax, ymax]`, indexed in a grid for collision detection.
+pub struct CollisionIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    boxes: Vec<[f32; 4]>,
+}
+
+impl CollisionIndex {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            boxes: Vec::new(),
+        }
+    }
+
+    pub fn collides(&self, bbox: &[f32; 4]) -> bool {
+        self.cell_keys(bbox).any(|key| {
+            self.cells.get(&key).is_some_and(|indices| {
+                indices.iter().any(|i| {
+                    let other = &self.boxes[*i];
+                    bbox[0] < other[2]
+                        && bbox[2] > other[0]
+                        && bbox[1] < other[3]
+                        && bbox[3] > other[1]
+                })
+            })
+        })
+    }
+
+    /// Insert all boxes if none of them collides, e.g. the glyphs of a label.
+    pub fn try_insert(&mut self, boxes: &[[f32; 4]]) -> bool {
+        if boxes.iter().any(|bbox| self.collides(bbox)) {
+            return false;
+        }
+
+        for bbox in boxes {
+            let index = self.boxes.len();
+            self.boxes.push(*bbox);
+
+            let keys: Vec<(i32, i32)> = self.cell_keys(bbox).collect();
+            for key in keys {
+                self.cells.entry(key).or_default().push(index);
+            }
+        }
+
+        true
+    }
+
+    fn cell_keys(&self, bbox: &[f32; 4]) -> impl Iterator<Item = (i32, i32)> {
+        let x0 = (bbox[0] / self.cell_size).floor() as i32;
+        let y0 = (bbox[1] / self.cell_size).floor() as i32;
+        let x1 = (bbox[2] / self.cell_size).floor() as i32;
+        let y1 = (bbox[3] / self.cell_size).floor() as i32;
+
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+}
+
+/// Corners of a glyph quad in screen pixels, ordered top left, top right, bottom left and bottom
+/// right, with its texture rect in atlas pixels.
+pub struct GlyphQuad {
+    pub corners: [[f32; 2]; 4],
+    pub tex_rect: [f32; 4],
+}
+
+pub struct PlacedLabel {
+    pub boxes: Vec<[f32; 4]>,
+    pub quads: Vec<GlyphQuad>,
+}
+
+/// Place a label horizontally at a screen point, `scale` maps shaped units to screen pixels.
+pub fn place_point_label(
+    text: &ShapedText,
+    style: &LabelStyle,
+    anchor: [f32; 2],
+    scale: f32,
+    pixel_ratio: f32,
+) -> PlacedLabel {
+    let width = text.width * scale;
+    let height = text.height * scale;
+
+    let (ax, ay) = style.anchor.position();
+
+    let x = anchor[0] - width * ax + style.offset[0] * pixel_ratio;
+    let y = anchor[1] - height * ay + style.offset[1] * pixel_ratio;
+    let padding = style.padding * pixel_ratio;
+
+    let quads = text
+        .glyphs
+        .iter()
+        .map(|glyph| {
+            let x0 = x + glyph.x * scale;
+            let y0 = y + glyph.y * scale;
+            let x1 = x0 + glyph.tex_rect[2] * scale;
+            let y1 = y0 + glyph.tex_rect[3] * scale;
+
+            GlyphQuad {
+                corners: [[x0, y0], [x1, y0], [x0, y1], [x1, y1]],
+                tex_rect: glyph.tex_rect,
+            }
+        })
+        .collect();
+
+    PlacedLabel {
+        boxes: vec![[
+            x - padding,
+            y - padding,
+            x + width + padding,
+            y + height + padding,
+        ]],
+        quads,
+    }
+}
+
+/// Place a single line label centered along a screen line, rotating each glyph with the line and
+/// keeping the text upright. Returns `None` if the line is too short or too curved.
+pub fn place_line_label(
+    text: &ShapedText,
+    style: &LabelStyle,
+    points: &[[f32; 2]],
+    scale: f32,
+    pixel_ratio: f32,
+) -> Option<PlacedLabel> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mut points = points.to_vec();
+    let width = text.width * scale;
+    let height = text.height * scale;
+
+    let mut distances = cumulative_distances(&points);
+    let length = *distances.last()?;
+    if width > length {
+        return None;
+    }
+
+    // Keep the text upright by reading lines from left to right
+    let center_start = (length - width) / 2.0;
+    let (first, _) = point_along(&points, &distances, center_start);
+    let (last, _) = point_along(&points, &distances, center_start + width);
+    if last[0] < first[0] {
+        points.reverse();
+        distances = cumulative_distances(&points);
+    }
+
+    // The offset follows the reading direction
+    let start = center_start + style.offset[0] * pixel_ratio;
+    if start < 0.0 || start + width > length {
+        return None;
+    }
+
+    let padding = style.padding * pixel_ratio;
+    let dy = style.offset[1] * pixel_ratio - height / 2.0;
+
+    let mut boxes = Vec::new();
+    let mut quads = Vec::new();
+    let mut last_angle: Option<f32> = None;
+
+    for glyph in &text.glyphs {
+        let (center, angle) = point_along(&points, &distances, start + glyph.center * scale);
+
+        if let Some(last_angle) = last_angle {
+            let mut diff = (angle - last_angle).abs();
+            if diff > std::f32::consts::PI {
+                diff = std::f32::consts::PI * 2.0 - diff;
+            }
+            if diff > LINE_LABEL_MAX_ANGLE.to_radians() {
+                return None;
+            }
+        }
+        last_angle = Some(angle);
+
+        let (sin, cos) = angle.sin_cos();
+        let rotate =
+            |x: f32, y: f32| [center[0] + x * cos - y * sin, center[1] + x * sin + y * cos];
+
+        let x0 = (glyph.x - glyph.center) * scale;
+        let y0 = glyph.y * scale + dy;
+        let x1 = x0 + glyph.tex_rect[2] * scale;
+        let y1 = y0 + glyph.tex_rect[3] * scale;
+
+        let corners = [
+            rotate(x0, y0),
+            rotate(x1, y0),
+            rotate(x0, y1),
+            rotate(x1, y1),
+        ];
+
+        let mut bbox = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+        for corner in &corners {
+            bbox[0] = bbox[0].min(corner[0] - padding);
+            bbox[1] = bbox[1].min(corner[1] - padding);
+            bbox[2] = bbox[2].max(corner[0] + padding);
+            bbox[3] = bbox[3].max(corner[1] + padding);
+        }
+
+        boxes.push(bbox);
+        quads.push(GlyphQuad {
+            corners,
+            tex_rect: glyph.tex_rect,
+        });
+    }
+
+    Some(PlacedLabel { boxes, quads })
+}
+
+fn cumulative_distances(points: &[[f32; 2]]) -> Vec<f32> {
+    let mut distances = Vec::with_capacity(points.len());
+    let mut distance = 0.0;
+    distances.push(distance);
+
+    for pair in points.windows(2) {
+        distance += (pair[1][0] - pair[0][0]).hypot(pair[1][1] - pair[0][1]);
+        distances.push(distance);
+    }
+
+    distances
+}
+
+/// Point and direction angle at a distance along a line.
+fn point_along(points: &[[f32; 2]], distances: &[f32], distance: f32) -> ([f32; 2], f32) {
+    let i = distances
+        .iter()
+        .position(|d| *d >= distance)
+        .unwrap_or(distances.len() - 1)
+        .max(1);
+
+    let a = points[i - 1];
+    let b = points[i];
+    let segment = distances[i] - distances[i - 1];
+    let t = if segment > 0.0 {
+        (distance - distances[i - 1]) / segment
+    } else {
+        0.0
+    };
+
+    (
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t],
+        (b[1] - a[1]).atan2(b[0] - a[0]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{feature::style::Anchor, render::text::ShapedGlyph};
+
+    fn shaped_text() -> ShapedText {
+        ShapedText {
+            glyphs: (0..4)
+                .map(|i| ShapedGlyph {
+                    x: i as f32 * 10.0,
+                    y: 0.0,
+                    center: i as f32 * 10.0 + 5.0,
+                    tex_rect: [0.0, 0.0, 10.0, 20.0],
+                })
+                .collect(),
+            width: 40.0,
+            height: 20.0,
+        }
+    }
+
+    #[test]
+    fn test_collision_index() {
+        let mut index = CollisionIndex::new(64.0);
+
+        assert!(index.try_insert(&[[0.0, 0.0, 100.0, 20.0]]));
+        assert!(index.collides(&[90.0, 10.0, 200.0, 30.0]));
+        assert!(!index.collides(&[100.0, 0.0, 200.0, 20.0]));
+        assert!(!index.try_insert(&[[300.0, 0.0, 310.0, 10.0], [50.0, 5.0, 60.0, 15.0]]));
+        assert!(!index.collides(&[300.0, 0.0, 310.0, 10.0]));
+    }
+
+    #[test]
+    fn test_place_point_label() {
+        let style = LabelStyle {
+            anchor: Anchor::Left,
+            padding: 0.0,
+            ..Default::default()
+        };
+
+        let placed = place_point_label(&shaped_text(), &style, [100.0, 100.0], 0.5, 1.0);
+        assert_eq!(placed.boxes, vec![[100.0, 95.0, 120.0, 105.0]]);
+        assert_eq!(placed.quads[1].corners[0], [105.0, 95.0]);
+    }
+
+    #[test]
+    fn test_place_line_label() {
+        let style = LabelStyle {
+            padding: 0.0,
+            ..Default::default()
+        };
+
+        // Lines from right to left are reversed to keep labels upright
+        let placed = place_line_label(
+            &shaped_text(),
+            &style,
+            &[[100.0, 50.0], [0.0, 50.0]],
+            1.0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(placed.quads.len(), 4);
+        assert_eq!(placed.quads[0].corners[0], [30.0, 40.0]);
+        assert_eq!(placed.boxes[3], [60.0, 40.0, 70.0, 60.0]);
+
+        // Offsets move labels along the reading direction of reversed lines
+        let offset_style = LabelStyle {
+            offset: [10.0, 0.0],
+            ..style.clone()
+        };
+        let placed = place_line_label(
+            &shaped_text(),
+            &offset_style,
+            &[[100.0, 50.0], [0.0, 50.0]],
+            1.0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(placed.quads[0].corners[0], [40.0, 40.0]);
+        assert_eq!(placed.boxes[3], [70.0, 40.0, 80.0, 60.0]);
+        assert!(place_line_label(
+            &shaped_text(),
+            &LabelStyle {
+                offset: [40.0, 0.0],
+                ..style.clone()
+            },
+            &[[100.0, 50.0], [0.0, 50.0]],
+            1.0,
+            1.0,
+        )
+        .is_none());
+
+        assert!(
+            place_line_label(&shaped_text(), &style, &[[0.0, 0.0], [30.0, 0.0]], 1.0, 1.0)
+                .is_none()
+        );
+        assert!(place_line_label(
+            &shaped_text(),
+            &style,
+            &[[0.0, 0.0], [50.0, 0.0], [50.0, 50.0]],
+            1.0,
+            1.0
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,19 @@
+
+// Map view shared by all shaders, appended to them when they are created. It is written once per
+// world copy, vertex coords are relative to an origin in the params of each draw item.
+
+struct MapView {
+    view_proj: mat4x4<f32>,
+    // High and low parts of the map center, see map_center
+    center: vec4<f32>,
+    res: f32,
+};
+
+// Map View BindGroup
+@group(0) @binding(0) var<uniform> map_view: MapView;
+
+// Map center relative to the origin of vertex coords, given as high and low parts like the
+// center. The parts are subtracted separately to keep the offset precise in f32 far from zero.
+fn map_center(origin: vec4<f32>) -> vec2<f32> {
+    return (map_view.center.xy - origin.xy) + (map_view.center.zw - origin.zw);
+}
@@ -1,28 +1,99 @@
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    // Position in pattern repeats and in the gradient bounding box
+    @location(1) pattern_coord: vec2<f32>,
+    @location(2) gradient_coord: vec2<f32>,
 };
 
-// Map View BindGroup
-@group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
-@group(0) @binding(1) var<uniform> map_center: vec2<f32>;
-@group(0) @binding(2) var<uniform> map_res: f32;
+struct Params {
+    // High and low parts of the vertex coords origin
+    origin: vec4<f32>,
+    fill_color: vec4<f32>,
+    // Offset and scale of coords relative to the map center
+    pattern_transform: vec4<f32>,
+    pattern_tex_rect: vec4<f32>,
+    gradient_transform: vec4<f32>,
+    // Linear: start and end, radial: center and radius
+    gradient_geometry: vec4<f32>,
+    gradient_offsets: array<vec4<f32>, 2>,
+    gradient_colors: array<vec4<f32>, 8>,
+    z: f32,
+    fill_type: u32, // 0: color 1: pattern 2: linear gradient 3: radial gradient
+    gradient_stop_count: u32,
+};
 
 // Params BindGroup
-@group(1) @binding(0) var<uniform> z: f32;
-@group(1) @binding(1) var<uniform> fill_color: vec4<f32>;
+@group(1) @binding(0) var<uniform> params: Params;
+
+// Sprite Atlas BindGroup
+@group(2) @binding(0) var texture: texture_2d<f32>;
+@group(2) @binding(1) var texture_sampler: sampler;
 
 @vertex
 fn vs_main(
     @location(0) vertex_coord: vec2<f32>
     ) -> VertexOutput {
-    var p = (vertex_coord - map_center) / map_res;
-    let position = view_proj * vec4<f32>(p.xy, z / map_res, 1.0);
+    let coord = vertex_coord - map_center(params.origin);
+    var p = coord / map_view.res;
+    let position = map_view.view_proj * vec4<f32>(p.xy, params.z / map_view.res, 1.0);
+
+    let pattern_coord = coord * params.pattern_transform.zw + params.pattern_transform.xy;
+    let gradient_coord = coord * params.gradient_transform.zw + params.gradient_transform.xy;
 
-    return VertexOutput(position, fill_color);
+    return VertexOutput(position, params.fill_color, pattern_coord, gradient_coord);
 }
 
 @fragment
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    return vertex.color;
+    let pattern_tex_rect = params.pattern_tex_rect;
+    let gradient_geometry = params.gradient_geometry;
+
+    switch params.fill_type {
+        case 1u: {
+            // Derivatives of the continuous coords avoid seams where the repeats wrap
+            let texture_coord = mix(pattern_tex_rect.xy, pattern_tex_rect.zw, fract(vertex.pattern_coord));
+            let pattern_color = textureSampleGrad(
+                texture,
+                texture_sampler,
+                texture_coord,
+                dpdx(vertex.pattern_coord) * (pattern_tex_rect.zw - pattern_tex_rect.xy),
+                dpdy(vertex.pattern_coord) * (pattern_tex_rect.zw - pattern_tex_rect.xy)
+            );
+            return vec4<f32>(pattern_color.rgb, pattern_color.a * vertex.color.a);
+        }
+        case 2u: {
+            let d = gradient_geometry.zw - gradient_geometry.xy;
+            let t = dot(vertex.gradient_coord - gradient_geometry.xy, d) / max(dot(d, d), 0.000001);
+            return gradient_color(t);
+        }
+        case 3u: {
+            let t = length(vertex.gradient_coord - gradient_geometry.xy) / max(gradient_geometry.z, 0.000001);
+            return gradient_color(t);
+        }
+        default: {
+            return vertex.color;
+        }
+    }
+}
+
+// Color at an offset along the gradient, clamped to the first and last stops.
+fn gradient_color(t: f32) -> vec4<f32> {
+    var color = params.gradient_colors[0];
+
+    for (var i = 1u; i < params.gradient_stop_count; i++) {
+        let start = params.gradient_offsets[(i - 1u) / 4u][(i - 1u) % 4u];
+        let end = params.gradient_offsets[i / 4u][i % 4u];
+
+        if t >= end {
+            color = params.gradient_colors[i];
+        } else if t > start {
+            color = mix(params.gradient_colors[i - 1u], params.gradient_colors[i], (t - start) / (end - start));
+            break;
+        } else {
+            break;
+        }
+    }
+
+    return color;
 }
@@ -0,0 +1,48 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+struct FeatureStyle {
+    // High and low parts of the vertex coords origin of the feature
+    origin: vec4<f32>,
+    fill_color: vec4<f32>,
+    stroke_color: vec4<f32>,
+    // Stroke width in pixels, stroke align (0: center 1:left 2:right) and 1 if visible
+    params: vec4<f32>,
+};
+
+// Shared with shape_stroke_batch.wgsl, only z is used by fills
+struct Params {
+    z: f32,
+    line_cap: u32,
+    line_join: u32,
+    miter_limit: f32,
+};
+
+// Params BindGroup
+@group(1) @binding(0) var<uniform> params: Params;
+@group(1) @binding(1) var<storage, read> feature_styles: array<FeatureStyle>;
+
+@vertex
+fn vs_main(
+    @location(0) vertex_coord: vec2<f32>,
+    @location(1) feature_index: u32,
+    ) -> VertexOutput {
+    let style = feature_styles[feature_index];
+
+    // Triangles of hidden features collapse to a point
+    if style.params.z < 0.5 {
+        return VertexOutput(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(0.0));
+    }
+
+    let p = (vertex_coord - map_center(style.origin)) / map_view.res;
+    let position = map_view.view_proj * vec4<f32>(p.xy, params.z / map_view.res, 1.0);
+
+    return VertexOutput(position, style.fill_color);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return vertex.color;
+}
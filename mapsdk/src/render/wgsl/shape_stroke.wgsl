@@ -1,19 +1,36 @@
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
-    @location(1) edge: f32,
+    // Offset from the line along and across it, in units of the stroke extent
+    @location(1) offset: vec2<f32>,
+    // Distance along the line in pixels
+    @location(2) distance: f32,
 };
 
-// Map View BindGroup
-@group(0) @binding(0) var<uniform> view_proj: mat4x4<f32>;
-@group(0) @binding(1) var<uniform> map_center: vec2<f32>;
-@group(0) @binding(2) var<uniform> map_res: f32;
+struct Params {
+    // High and low parts of the vertex coords origin
+    origin: vec4<f32>,
+    stroke_color: vec4<f32>,
+    // Alternating dash and gap lengths in pixels
+    dash_array: array<vec4<f32>, 4>,
+    pattern_tex_rect: vec4<f32>,
+    // Size of the pattern icon, zero without pattern
+    pattern_size: vec2<f32>,
+    z: f32,
+    stroke_width: f32,
+    align: u32, // 0: center 1:left 2:right
+    line_cap: u32, // 0: butt 1: round 2: square
+    line_join: u32, // 0: miter 1: bevel 2: round
+    miter_limit: f32,
+    dash_count: u32,
+};
 
 // Params BindGroup
-@group(1) @binding(0) var<uniform> z: f32;
-@group(1) @binding(1) var<uniform> align: u32; // 0: center 1:left 2:right
-@group(1) @binding(2) var<uniform> stroke_width: f32;
-@group(1) @binding(3) var<uniform> stroke_color: vec4<f32>;
+@group(1) @binding(0) var<uniform> params: Params;
+
+// Sprite Atlas BindGroup
+@group(2) @binding(0) var texture: texture_2d<f32>;
+@group(2) @binding(1) var texture_sampler: sampler;
 
 @vertex
 fn vs_main(
@@ -21,87 +38,75 @@ fn vs_main(
     @location(1) prev_vertex_coord: vec2<f32>,
     @location(2) next_vertex_coord: vec2<f32>,
     @location(3) vertex_type: f32,
+    @location(4) vertex_distance: f32,
     ) -> VertexOutput {
-    var p = (vertex_coord - map_center) / map_res;
-
-    let max_edge_scale = 2.0;
-    
-    let vertex_type_trunc = trunc(vertex_type);
-    let vertex_type_frac = fract(vertex_type);
+    let stroke = expand_stroke_vertex(
+        map_center(params.origin),
+        vertex_coord,
+        prev_vertex_coord,
+        next_vertex_coord,
+        vertex_type,
+        vertex_distance,
+        stroke_edges(params.align, params.stroke_width)
+    );
+
+    let position = map_view.view_proj * vec4<f32>(stroke.position, params.z / map_view.res, 1.0);
+
+    return VertexOutput(position, params.stroke_color, stroke.offset, stroke.distance);
+}
 
-    let start_or_end = sign(vertex_type_trunc - 0.5); // start: -1.0, end: 1.0
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    var color = vertex.color;
+    var alpha = smoothstep(0.0, 1.0, 2.0 * (1.0 - length(vertex.offset)));
 
-    let seg_prev = vertex_coord - prev_vertex_coord;
-    let seg_next = next_vertex_coord - vertex_coord;
-    let seg = select(seg_prev, seg_next, start_or_end < 0.0);
+    if params.dash_count > 0u {
+        alpha *= dash_coverage(vertex.distance);
+    }
 
-    let seg_prev_dir = normalize(seg_prev);
-    let seg_next_dir = normalize(seg_next);
-    let seg_dir = normalize(seg);
+    if alpha <= 0.0 {
+        discard;
+    }
 
-    let seg_norm = vec2<f32>(-seg_dir.y, seg_dir.x);
-    let seg_length = length(seg) / map_res;
+    // Repeat the pattern along the line, scaled to the stroke width
+    if params.pattern_size.x > 0.0 && params.pattern_size.y > 0.0 {
+        let edges = stroke_edges(params.align, params.stroke_width);
+        let extent = max(max(-edges.x, edges.y), 0.0001);
 
-    let join_dir = normalize(seg_prev_dir + seg_next_dir);
-    let join_norm = vec2<f32>(-join_dir.y, join_dir.x);
+        let pattern_length = params.pattern_size.x / params.pattern_size.y * (edges.y - edges.x);
+        let u = fract(vertex.distance / pattern_length);
+        let v = clamp((edges.y - vertex.offset.y * extent) / (edges.y - edges.x), 0.0, 1.0);
 
-    let angle = select(0.0, acos(clamp(dot(seg_prev_dir, seg_next_dir), -1.0, 1.0)), length(seg_prev) * length(seg_next) > 0.0); // 0 ~ PI
-    let angle_sign = sign(seg_prev_dir.x * seg_next_dir.y - seg_prev_dir.y * seg_next_dir.x);
+        let texture_coord = mix(params.pattern_tex_rect.xy, params.pattern_tex_rect.zw, vec2<f32>(u, v));
+        let pattern_color = textureSampleLevel(texture, texture_sampler, texture_coord, 0.0);
+        color = vec4<f32>(pattern_color.rgb, pattern_color.a * color.a);
+    }
 
-    let edge_width = select(stroke_width, stroke_width / 2.0, align == 0);
-    let align_sign = select(-1.0, 1.0, align == 2);
+    return vec4(color.xyz, alpha * color.w);
+}
 
-    if vertex_type_frac < 0.25 { // straight line center
-        if align == 0 || (align_sign * angle_sign > 0.0) {
-            p -= seg_dir * min(seg_length, edge_width * tan(angle / 2.0)) * start_or_end;
-        }
+// Coverage of the dash pattern at a distance along the line, antialiased at dash ends.
+fn dash_coverage(distance: f32) -> f32 {
+    var total = 0.0;
+    for (var i = 0u; i < params.dash_count; i++) {
+        total += params.dash_array[i / 4u][i % 4u];
     }
-
-    if vertex_type_frac < 0.25 { // straight line
-        let edge_sign = sign(vertex_type_frac - 0.15);
-        if align == 0 {
-            p += seg_norm * edge_width * edge_sign;
-        } else {
-            if vertex_type_frac > 0.15 {
-                p += seg_norm * edge_width * edge_sign * align_sign;
-            }
-        }
-    } else if vertex_type_frac > 0.25 && vertex_type_frac < 0.35 { // join edge
-        if align == 0 || (align > 0 && align_sign * angle_sign < 0.0) {
-            let edge_scale = 1.0 / cos(angle / 2.0);
-            if edge_scale < max_edge_scale {
-                p -= join_norm * edge_width * edge_scale * angle_sign;
-            } else {
-                let w = min(seg_length, edge_width * max_edge_scale);
-                p -= join_norm * w * angle_sign;
-                p -= join_dir * sin(angle / 2.0) * (edge_width * edge_scale - w) / edge_scale * start_or_end;
-            }
-        }
+    if total <= 0.0 {
+        return 1.0;
     }
 
-    let position = view_proj * vec4<f32>(p.xy, z / map_res, 1.0);
+    let t = distance - floor(distance / total) * total;
 
-    var edge = 0.0;
-    if align == 0 {
-        if vertex_type_frac < 0.25 {
-            edge = 1.0 * sign(vertex_type_frac - 0.15);
-        } else if vertex_type_frac > 0.25 {
-            edge = -1.0 * angle_sign;
-        }
-    } else {
-        if vertex_type_frac > 0.15 && vertex_type_frac < 0.25 {
-            edge = 1.0;
-        } else if vertex_type_frac > 0.25 {
-            edge = select(1.0, 0.0, align_sign * angle_sign > 0.0);
+    var start = 0.0;
+    for (var i = 0u; i < params.dash_count; i++) {
+        let end = start + params.dash_array[i / 4u][i % 4u];
+        if t < end {
+            // Distance to the nearest dash end, even entries are dashes and odd ones gaps
+            let d = min(t - start, end - t);
+            return clamp(select(0.5 - d, 0.5 + d, i % 2u == 0u), 0.0, 1.0);
         }
+        start = end;
     }
 
-    return VertexOutput(position, stroke_color, edge);
-}
-
-@fragment
-fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let alpha = smoothstep(0.0, 1.0, 2.0 * (1.0 - abs(vertex.edge)));
-    
-    return vec4(vertex.color.xyz, alpha * vertex.color.w);
+    return 1.0;
 }
@@ -0,0 +1,68 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Offset from the line along and across it, in units of the stroke extent
+    @location(1) offset: vec2<f32>,
+};
+
+struct FeatureStyle {
+    // High and low parts of the vertex coords origin of the feature
+    origin: vec4<f32>,
+    fill_color: vec4<f32>,
+    stroke_color: vec4<f32>,
+    // Stroke width in pixels, stroke align (0: center 1:left 2:right) and 1 if visible
+    params: vec4<f32>,
+};
+
+struct Params {
+    z: f32,
+    line_cap: u32, // 0: butt 1: round 2: square
+    line_join: u32, // 0: miter 1: bevel 2: round
+    miter_limit: f32,
+};
+
+// Params BindGroup
+@group(1) @binding(0) var<uniform> params: Params;
+@group(1) @binding(1) var<storage, read> feature_styles: array<FeatureStyle>;
+
+@vertex
+fn vs_main(
+    @location(0) vertex_coord: vec2<f32>,
+    @location(1) prev_vertex_coord: vec2<f32>,
+    @location(2) next_vertex_coord: vec2<f32>,
+    @location(3) vertex_type: f32,
+    @location(4) vertex_distance: f32,
+    @location(5) feature_index: u32,
+    ) -> VertexOutput {
+    let style = feature_styles[feature_index];
+
+    // Triangles of hidden features collapse to a point
+    if style.params.z < 0.5 {
+        return VertexOutput(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(0.0), vec2<f32>(0.0));
+    }
+
+    let stroke = expand_stroke_vertex(
+        map_center(style.origin),
+        vertex_coord,
+        prev_vertex_coord,
+        next_vertex_coord,
+        vertex_type,
+        vertex_distance,
+        stroke_edges(u32(style.params.y + 0.5), style.params.x)
+    );
+
+    let position = map_view.view_proj * vec4<f32>(stroke.position, params.z / map_view.res, 1.0);
+
+    return VertexOutput(position, style.stroke_color, stroke.offset);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = smoothstep(0.0, 1.0, 2.0 * (1.0 - length(vertex.offset)));
+
+    if alpha <= 0.0 {
+        discard;
+    }
+
+    return vec4(vertex.color.xyz, alpha * vertex.color.w);
+}
@@ -0,0 +1,131 @@
+// Stroke geometry shared by shape_stroke.wgsl and shape_stroke_batch.wgsl, appended to them when
+// the shaders are created. Uses map_view and the line_cap, line_join and miter_limit params of the
+// shader it is appended to.
+
+// Largest turn angle of joins, reversing lines turn by it
+const MAX_ANGLE: f32 = 3.14;
+
+struct StrokeVertex {
+    // Position relative to the map center in pixels
+    position: vec2<f32>,
+    // Offset from the line along and across it, in units of the stroke extent
+    offset: vec2<f32>,
+    // Distance along the line in pixels
+    distance: f32,
+};
+
+// Expand a vertex of the line to the stroke edges, see stroke_edges. The center is the map center
+// relative to the vertex coords origin.
+fn expand_stroke_vertex(
+    center: vec2<f32>,
+    vertex_coord: vec2<f32>,
+    prev_vertex_coord: vec2<f32>,
+    next_vertex_coord: vec2<f32>,
+    vertex_type: f32,
+    vertex_distance: f32,
+    edges: vec2<f32>,
+    ) -> StrokeVertex {
+    let v = (vertex_coord - center) / map_view.res;
+    var p = v;
+    var offset = vec2<f32>(0.0, 0.0);
+    var distance = vertex_distance / map_view.res;
+
+    // Vertex types are listed in tessellation/geometry/line_string.rs
+    let vertex_type_idx = u32(vertex_type + 0.5);
+
+    let seg_prev = (vertex_coord - prev_vertex_coord) / map_view.res;
+    let seg_next = (next_vertex_coord - vertex_coord) / map_view.res;
+
+    let seg_prev_dir = direction(seg_prev);
+    let seg_next_dir = direction(seg_next);
+
+    // Kept below PI, acos(-1.0) rounds above it and the tangent of the half angle turns negative
+    let angle = select(0.0, min(acos(clamp(dot(seg_prev_dir, seg_next_dir), -1.0, 1.0)), MAX_ANGLE), length(seg_prev) * length(seg_next) > 0.0); // 0 ~ PI
+    let angle_sign = select(-1.0, 1.0, seg_prev_dir.x * seg_next_dir.y - seg_prev_dir.y * seg_next_dir.x > 0.0);
+
+    let edge_low = edges.x;
+    let edge_high = edges.y;
+    let extent = max(max(-edge_low, edge_high), 0.0001);
+
+    switch vertex_type_idx {
+        // Segment start and end
+        case 0u, 1u, 2u, 3u: {
+            let is_end = vertex_type_idx >= 2u;
+            let edge = select(edge_low, edge_high, vertex_type_idx % 2u == 1u);
+
+            let seg = select(seg_next, seg_prev, is_end);
+            let seg_dir = direction(seg);
+
+            // Segments meet on the inner side of turns
+            var pullback = 0.0;
+            if edge * angle_sign > 0.0 {
+                pullback = min(length(seg), abs(edge) * tan(angle / 2.0));
+            }
+
+            p += normal(seg_dir) * edge - seg_dir * pullback * select(-1.0, 1.0, is_end);
+            offset = vec2<f32>(0.0, edge / extent);
+            distance -= pullback * select(-1.0, 1.0, is_end);
+        }
+        // Outer side of joins, between the perpendicular segment ends
+        case 5u, 6u, 7u, 8u: {
+            let is_next = vertex_type_idx >= 7u;
+            let edge = select(max(edge_high, 0.0), min(edge_low, 0.0), angle_sign > 0.0);
+
+            let seg_dir = select(seg_prev_dir, seg_next_dir, is_next);
+            p += normal(seg_dir) * edge;
+
+            // Distance of the corners along the segment edges in units of the edge offset
+            var corner = 0.0;
+            if params.line_join == 2u {
+                corner = tan(angle / 4.0);
+            } else if params.line_join == 0u && 1.0 / cos(angle / 2.0) <= params.miter_limit {
+                corner = tan(angle / 2.0);
+            }
+
+            if vertex_type_idx == 6u || vertex_type_idx == 7u {
+                p += seg_dir * abs(edge) * corner * select(1.0, -1.0, is_next);
+            }
+
+            if params.line_join == 2u {
+                let d = p - v;
+                offset = vec2<f32>(dot(d, seg_prev_dir), dot(d, normal(seg_prev_dir))) / extent;
+            } else {
+                offset = vec2<f32>(0.0, edge / extent);
+            }
+        }
+        // Caps at the start and end of open lines
+        case 9u, 10u, 11u, 12u: {
+            let is_end = vertex_type_idx >= 11u;
+            let edge = select(edge_low, edge_high, vertex_type_idx % 2u == 0u);
+
+            let seg_dir = select(seg_next_dir, seg_prev_dir, is_end);
+            let cap = select(extent, 0.0, params.line_cap == 0u);
+
+            p += normal(seg_dir) * edge + seg_dir * cap * select(-1.0, 1.0, is_end);
+            offset = vec2<f32>(select(0.0, cap / extent, params.line_cap == 1u), edge / extent);
+            distance += cap * select(-1.0, 1.0, is_end);
+        }
+        // Join center
+        default: {}
+    }
+
+    return StrokeVertex(p, offset, distance);
+}
+
+// Low and high offsets of the stroke edges along the segment normal.
+fn stroke_edges(align: u32, stroke_width: f32) -> vec2<f32> {
+    if align == 0 {
+        return vec2<f32>(-stroke_width / 2.0, stroke_width / 2.0);
+    }
+
+    let side = stroke_width * select(-1.0, 1.0, align == 2);
+    return vec2<f32>(min(side, 0.0), max(side, 0.0));
+}
+
+fn direction(seg: vec2<f32>) -> vec2<f32> {
+    return select(vec2<f32>(0.0, 0.0), normalize(seg), length(seg) > 0.0);
+}
+
+fn normal(dir: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(-dir.y, dir.x);
+}
@@ -0,0 +1,48 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coord: vec2<f32>,
+};
+
+// Sprite Atlas BindGroup
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+// Params BindGroup
+@group(2) @binding(0) var<uniform> z: f32;
+// Left, top, right and bottom of the icon around its point, y is up
+@group(2) @binding(1) var<uniform> rect: vec4<f32>;
+// Counterclockwise in the map plane
+@group(2) @binding(2) var<uniform> rotation: f32;
+@group(2) @binding(3) var<uniform> tint: vec4<f32>;
+@group(2) @binding(4) var<uniform> tex_rect: vec4<f32>;
+// High and low parts of the vertex coords origin
+@group(2) @binding(5) var<uniform> origin: vec4<f32>;
+
+@vertex
+fn vs_main(
+    @location(0) vertex_coord: vec2<f32>,
+    @builtin(vertex_index) vertex_idx: u32
+    ) -> VertexOutput {
+    let center = map_center(origin);
+    var x = (vertex_coord[0] - center[0]) / map_view.res;
+    var y = (vertex_coord[1] - center[1]) / map_view.res;
+
+    let vertex_idx_norm = vertex_idx % 4;
+    let left = vertex_idx_norm % 2 == 0;
+    let top = vertex_idx_norm < 2;
+
+    let dx = select(rect.z, rect.x, left);
+    let dy = select(rect.w, rect.y, top);
+    let c = cos(rotation);
+    let s = sin(rotation);
+
+    let position = map_view.view_proj * vec4<f32>(x + dx * c - dy * s, y + dx * s + dy * c, z / map_view.res, 1.0);
+    let texture_coord = vec2<f32>(select(tex_rect.z, tex_rect.x, left), select(tex_rect.w, tex_rect.y, top));
+
+    return VertexOutput(position, texture_coord);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(texture, texture_sampler, vertex.texture_coord) * tint;
+}
@@ -0,0 +1,141 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) coord: vec2<f32>,
+};
+
+// Params BindGroup
+@group(1) @binding(0) var<uniform> z: f32;
+@group(1) @binding(1) var<uniform> radius: f32;
+@group(1) @binding(2) var<uniform> fill_color: vec4<f32>;
+// 0 circle, 1 square, 2 triangle, 3 diamond, 4 star, 5 cross, 6 arrow
+@group(1) @binding(3) var<uniform> shape: u32;
+@group(1) @binding(4) var<uniform> stroke_color: vec4<f32>;
+@group(1) @binding(5) var<uniform> stroke_width: f32;
+// High and low parts of the vertex coords origin
+@group(1) @binding(6) var<uniform> origin: vec4<f32>;
+
+@vertex
+fn vs_main(
+    @location(0) vertex_coord: vec2<f32>,
+    @builtin(vertex_index) vertex_idx: u32
+    ) -> VertexOutput {
+    let center = map_center(origin);
+    var x = (vertex_coord[0] - center[0]) / map_view.res;
+    var y = (vertex_coord[1] - center[1]) / map_view.res;
+
+    // Leave room for the stroke and antialiasing
+    let extent = radius + stroke_width / 2.0 + 1.0;
+
+    let vertex_idx_norm = vertex_idx % 4;
+    let dx = select(extent * 2.0, 0.0, vertex_idx_norm % 2 == 0) - extent;
+    let dy = extent - select(extent * 2.0, 0.0, vertex_idx_norm < 2);
+
+    let position = map_view.view_proj * vec4<f32>(x + dx, y + dy, z / map_view.res, 1.0);
+    let coord = vec2<f32>(dx, dy);
+
+    return VertexOutput(position, coord);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let d = shape_distance(vertex.coord, radius);
+
+    var fill = fill_color;
+    fill.a *= clamp(0.5 - d, 0.0, 1.0);
+
+    var stroke = stroke_color;
+    stroke.a *= select(0.0, clamp(0.5 + stroke_width / 2.0 - abs(d), 0.0, 1.0), stroke_width > 0.0);
+
+    // Stroke over fill
+    let alpha = stroke.a + fill.a * (1.0 - stroke.a);
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    let rgb = (stroke.rgb * stroke.a + fill.rgb * fill.a * (1.0 - stroke.a)) / alpha;
+
+    return vec4<f32>(rgb, alpha);
+}
+
+// Signed distance to the edge of the shape, negative inside, y is up.
+fn shape_distance(p: vec2<f32>, r: f32) -> f32 {
+    switch shape {
+        case 1u: {
+            return box_distance(p, vec2<f32>(r, r));
+        }
+        case 2u: {
+            let h = r * 0.866;
+            return triangle_distance(p, vec2<f32>(0.0, h), vec2<f32>(-r, -h), vec2<f32>(r, -h));
+        }
+        case 3u: {
+            return (abs(p.x) + abs(p.y) - r) * 0.7071;
+        }
+        case 4u: {
+            return star_distance(p, r, 0.45);
+        }
+        case 5u: {
+            let w = r * 0.3;
+            return min(box_distance(p, vec2<f32>(r, w)), box_distance(p, vec2<f32>(w, r)));
+        }
+        case 6u: {
+            let head = triangle_distance(
+                p,
+                vec2<f32>(0.0, r),
+                vec2<f32>(-r * 0.8, 0.0),
+                vec2<f32>(r * 0.8, 0.0)
+            );
+            let shaft = box_distance(p - vec2<f32>(0.0, -r * 0.5), vec2<f32>(r * 0.3, r * 0.5));
+            return min(head, shaft);
+        }
+        default: {
+            return length(p) - r;
+        }
+    }
+}
+
+// Reference: https://iquilezles.org/articles/distfunctions2d/
+fn box_distance(p: vec2<f32>, b: vec2<f32>) -> f32 {
+    let d = abs(p) - b;
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0);
+}
+
+fn triangle_distance(p: vec2<f32>, p0: vec2<f32>, p1: vec2<f32>, p2: vec2<f32>) -> f32 {
+    let e0 = p1 - p0;
+    let e1 = p2 - p1;
+    let e2 = p0 - p2;
+    let v0 = p - p0;
+    let v1 = p - p1;
+    let v2 = p - p2;
+
+    let pq0 = v0 - e0 * clamp(dot(v0, e0) / dot(e0, e0), 0.0, 1.0);
+    let pq1 = v1 - e1 * clamp(dot(v1, e1) / dot(e1, e1), 0.0, 1.0);
+    let pq2 = v2 - e2 * clamp(dot(v2, e2) / dot(e2, e2), 0.0, 1.0);
+
+    let s = sign(e0.x * e2.y - e0.y * e2.x);
+    let d = min(
+        min(
+            vec2<f32>(dot(pq0, pq0), s * (v0.x * e0.y - v0.y * e0.x)),
+            vec2<f32>(dot(pq1, pq1), s * (v1.x * e1.y - v1.y * e1.x))
+        ),
+        vec2<f32>(dot(pq2, pq2), s * (v2.x * e2.y - v2.y * e2.x))
+    );
+
+    return -sqrt(d.x) * sign(d.y);
+}
+
+// Five pointed star with the inner radius as a fraction of the outer one.
+fn star_distance(p_in: vec2<f32>, r: f32, rf: f32) -> f32 {
+    let k1 = vec2<f32>(0.809016994375, -0.587785252292);
+    let k2 = vec2<f32>(-k1.x, k1.y);
+
+    var p = vec2<f32>(abs(p_in.x), p_in.y);
+    p -= 2.0 * max(dot(k1, p), 0.0) * k1;
+    p -= 2.0 * max(dot(k2, p), 0.0) * k2;
+    p.x = abs(p.x);
+    p.y -= r;
+
+    let ba = rf * vec2<f32>(-k1.y, k1.x) - vec2<f32>(0.0, 1.0);
+    let h = clamp(dot(p, ba) / dot(ba, ba), 0.0, r);
+
+    return length(p - ba * h) * sign(p.y * ba.x - p.x * ba.y);
+}
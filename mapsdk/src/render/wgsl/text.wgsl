@@ -0,0 +1,52 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) texture_coord: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) halo_color: vec4<f32>,
+    // Edge gamma and halo edge of the distance field
+    @location(4) params: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coord: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) halo_color: vec4<f32>,
+    @location(3) params: vec2<f32>,
+};
+
+// Glyph Atlas BindGroup
+@group(0) @binding(0) var texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+const SDF_EDGE: f32 = 0.75;
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    return VertexOutput(
+        vec4<f32>(vertex.position, 0.0, 1.0),
+        vertex.texture_coord,
+        vertex.color,
+        vertex.halo_color,
+        vertex.params
+    );
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let dist = textureSample(texture, texture_sampler, vertex.texture_coord).r;
+    let gamma = vertex.params.x;
+
+    let fill_alpha = vertex.color.a * smoothstep(SDF_EDGE - gamma, SDF_EDGE + gamma, dist);
+    let halo_alpha = vertex.halo_color.a
+        * smoothstep(vertex.params.y - gamma, vertex.params.y + gamma, dist)
+        * (1.0 - fill_alpha);
+
+    let alpha = fill_alpha + halo_alpha;
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    let rgb = (vertex.color.rgb * fill_alpha + vertex.halo_color.rgb * halo_alpha) / alpha;
+    return vec4<f32>(rgb, alpha);
+}
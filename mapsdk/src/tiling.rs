@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use geo::{Coord, Rect};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -52,6 +54,29 @@ impl Tiling {
         }
     }
 
+    /// Tiling with explicit zoom resolutions, e.g. for scales that are not powers of two.
+    pub fn from_resolutions(
+        tile_size: u32,
+        map_size: f64,
+        origin_x: f64,
+        origin_y: f64,
+        zoom_resolutions: Vec<f64>,
+    ) -> Self {
+        assert!(tile_size > 0 && map_size > 0.0 && !zoom_resolutions.is_empty());
+        assert!(zoom_resolutions
+            .windows(2)
+            .all(|pair| pair[0] > pair[1] && pair[1] > 0.0));
+
+        Self {
+            tile_size,
+            map_size,
+            origin_x,
+            origin_y,
+
+            zoom_resolutions,
+        }
+    }
+
     pub fn drill_down_tile_ids(&self, tile_id: &TileId, level: u32) -> Vec<TileId> {
         let mut tile_ids: Vec<TileId> = Vec::new();
 
@@ -201,6 +226,11 @@ impl Tiling {
         self.map_size
     }
 
+    /// X of the copy of a coordinate in the world copy nearest to the reference x.
+    pub fn nearest_world_x(&self, x: f64, reference_x: f64) -> f64 {
+        x + ((reference_x - x) / self.map_size).round() * self.map_size
+    }
+
     pub fn origin_x(&self) -> f64 {
         self.origin_x
     }
@@ -209,6 +239,21 @@ impl Tiling {
         self.origin_y
     }
 
+    /// Resolution of a fractional zoom level, interpolated geometrically between the zoom levels.
+    ///
+    /// Zoom levels beyond the tiling continue the scale of the first or last pair of levels.
+    pub fn resolution_for_zoom(&self, zoom: f64) -> f64 {
+        let resolutions = &self.zoom_resolutions;
+        if resolutions.len() < 2 {
+            return self.get_resolution(0) / 2.0_f64.powf(zoom);
+        }
+
+        let i = (zoom.floor().max(0.0) as usize).min(resolutions.len() - 2);
+        let scale = resolutions[i + 1] / resolutions[i];
+
+        resolutions[i] * scale.powf(zoom - i as f64)
+    }
+
     pub fn roll_up_tile_id(&self, tile_id: &TileId, level: u32) -> Option<TileId> {
         if tile_id.z >= level as usize {
             let child_z = tile_id.z - level as usize;
@@ -227,6 +272,43 @@ impl Tiling {
     pub fn tile_size(&self) -> u32 {
         self.tile_size
     }
+
+    /// Indices of the world copies overlapped by the rect, 0 is the world of the tiling itself.
+    pub fn world_copies(&self, rect: &Rect) -> RangeInclusive<i32> {
+        let first = ((rect.min().x - self.origin_x) / self.map_size).floor() as i32;
+        let last = ((rect.max().x - self.origin_x) / self.map_size).floor() as i32;
+
+        first..=last
+    }
+
+    /// Wrap the x index of a tile into the tiles of its zoom level.
+    pub fn wrap_tile_id(&self, tile_id: &TileId) -> TileId {
+        TileId {
+            z: tile_id.z,
+            x: tile_id.x.rem_euclid(self.get_max_x_y(tile_id.z) + 1),
+            y: tile_id.y,
+        }
+    }
+
+    /// Wrap x into the world of the tiling.
+    pub fn wrap_x(&self, x: f64) -> f64 {
+        self.origin_x + (x - self.origin_x).rem_euclid(self.map_size)
+    }
+
+    /// Fractional zoom level of a resolution, the inverse of [`Tiling::resolution_for_zoom`].
+    pub fn zoom_for_resolution(&self, resolution: f64) -> f64 {
+        let resolutions = &self.zoom_resolutions;
+        if resolutions.len() < 2 {
+            return (self.get_resolution(0) / resolution).log2();
+        }
+
+        let i = resolutions
+            .windows(2)
+            .position(|pair| resolution >= pair[1])
+            .unwrap_or(resolutions.len() - 2);
+
+        i as f64 + (resolutions[i] / resolution).ln() / (resolutions[i] / resolutions[i + 1]).ln()
+    }
 }
 
 #[cfg(test)]
@@ -259,4 +341,69 @@ mod tests {
         assert_eq!(tiling.get_closest_zoom(0.6), 18);
         assert_eq!(tiling.get_closest_lower_zoom(0.0001), 23);
     }
+
+    #[test]
+    fn test_world_wrap() {
+        let tiling = Tiling::default();
+        let half = tiling.map_size() / 2.0;
+
+        assert_eq!(tiling.wrap_x(0.0), 0.0);
+        assert_eq!(tiling.wrap_x(-half), -half);
+        assert!((tiling.wrap_x(half + 1000.0) - (-half + 1000.0)).abs() < 1e-6);
+        assert!((tiling.wrap_x(-half - 1000.0) - (half - 1000.0)).abs() < 1e-6);
+
+        assert!((tiling.nearest_world_x(-half + 1000.0, half) - (half + 1000.0)).abs() < 1e-6);
+        assert_eq!(tiling.nearest_world_x(1000.0, 0.0), 1000.0);
+
+        let rect = Rect::new(
+            Coord {
+                x: half - 1000.0,
+                y: 0.0,
+            },
+            Coord {
+                x: half + 1000.0,
+                y: 1.0,
+            },
+        );
+        assert_eq!(tiling.world_copies(&rect), 0..=1);
+
+        assert_eq!(
+            tiling.wrap_tile_id(&TileId { z: 2, x: -1, y: 1 }),
+            TileId { z: 2, x: 3, y: 1 }
+        );
+        assert_eq!(
+            tiling.wrap_tile_id(&TileId { z: 2, x: 4, y: 1 }),
+            TileId { z: 2, x: 0, y: 1 }
+        );
+    }
+
+    #[test]
+    fn test_fractional_zoom() {
+        let tiling = Tiling::default();
+
+        assert_eq!(tiling.zoom_for_resolution(tiling.get_resolution(3)), 3.0);
+        assert_eq!(tiling.resolution_for_zoom(3.0), tiling.get_resolution(3));
+        assert!(
+            (tiling.resolution_for_zoom(2.5) - tiling.get_resolution(2) / 2.0_f64.sqrt()).abs()
+                < 1e-6
+        );
+        assert!((tiling.zoom_for_resolution(tiling.resolution_for_zoom(12.5)) - 12.5).abs() < 1e-9);
+        assert!((tiling.zoom_for_resolution(tiling.get_resolution(0) * 2.0) + 1.0).abs() < 1e-9);
+
+        // Scales that are not powers of two
+        let tiling = Tiling::from_resolutions(
+            256,
+            256000.0,
+            0.0,
+            256000.0,
+            vec![1000.0, 500.0, 200.0, 100.0],
+        );
+
+        assert_eq!(tiling.zoom_for_resolution(500.0), 1.0);
+        assert!((tiling.zoom_for_resolution(200.0 * 2.5_f64.sqrt()) - 1.5).abs() < 1e-9);
+        assert!((tiling.resolution_for_zoom(1.5) - 200.0 * 2.5_f64.sqrt()).abs() < 1e-9);
+        assert!((tiling.zoom_for_resolution(50.0) - 4.0).abs() < 1e-9);
+        assert!((tiling.resolution_for_zoom(-1.0) - 2000.0).abs() < 1e-9);
+        assert!((tiling.resolution_for_zoom(4.0) - 50.0).abs() < 1e-9);
+    }
 }
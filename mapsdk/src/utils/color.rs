@@ -1,4 +1,8 @@
-#[derive(Clone, Debug)]
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -14,6 +18,376 @@ impl Color {
     pub fn from_rgba(r: u8, g: u8, b: u8, a: f64) -> Color {
         Color { r, g, b, a }
     }
+
+    /// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.strip_prefix('#')?;
+        if !hex.is_ascii() {
+            return None;
+        }
+
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        let short_channel = |s: &str| channel(s).map(|v| v * 17);
+
+        match hex.len() {
+            3 | 4 => Some(Color {
+                r: short_channel(&hex[0..1])?,
+                g: short_channel(&hex[1..2])?,
+                b: short_channel(&hex[2..3])?,
+                a: match hex.get(3..4) {
+                    Some(a) => short_channel(a)? as f64 / 255.0,
+                    None => 1.0,
+                },
+            }),
+            6 | 8 => Some(Color {
+                r: channel(&hex[0..2])?,
+                g: channel(&hex[2..4])?,
+                b: channel(&hex[4..6])?,
+                a: match hex.get(6..8) {
+                    Some(a) => channel(a)? as f64 / 255.0,
+                    None => 1.0,
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Color from hue in degrees, saturation and lightness in [0, 1].
+    pub fn from_hsl(h: f64, s: f64, l: f64) -> Color {
+        Self::from_hsla(h, s, l, 1.0)
+    }
+
+    pub fn from_hsla(h: f64, s: f64, l: f64, a: f64) -> Color {
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+
+        Self::from_hue_chroma(h, chroma, l - chroma / 2.0, a)
+    }
+
+    /// Color from hue in degrees, saturation and value in [0, 1].
+    pub fn from_hsv(h: f64, s: f64, v: f64) -> Color {
+        Self::from_hsva(h, s, v, 1.0)
+    }
+
+    pub fn from_hsva(h: f64, s: f64, v: f64, a: f64) -> Color {
+        let s = s.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+        let chroma = v * s;
+
+        Self::from_hue_chroma(h, chroma, v - chroma, a)
+    }
+
+    fn from_hue_chroma(h: f64, chroma: f64, min: f64, a: f64) -> Color {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let channel = |v: f64| ((v + min) * 255.0).round().clamp(0.0, 255.0) as u8;
+
+        Color {
+            r: channel(r),
+            g: channel(g),
+            b: channel(b),
+            a,
+        }
+    }
+
+    /// Hue in degrees, saturation and lightness in [0, 1].
+    pub fn to_hsl(&self) -> (f64, f64, f64) {
+        let (h, max, min) = self.hue_max_min();
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+
+        (h, s, l)
+    }
+
+    /// Hue in degrees, saturation and value in [0, 1].
+    pub fn to_hsv(&self) -> (f64, f64, f64) {
+        let (h, max, min) = self.hue_max_min();
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+
+        (h, s, max)
+    }
+
+    fn hue_max_min(&self) -> (f64, f64, f64) {
+        let r = self.r as f64 / 255.0;
+        let g = self.g as f64 / 255.0;
+        let b = self.b as f64 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        let h = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+
+        (h, max, min)
+    }
+
+    /// Interpolate between two colors, `t` in [0, 1].
+    pub fn lerp(&self, other: &Color, t: f64) -> Color {
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+
+    /// Interpolate between two colors in linear space, `t` in [0, 1]. Mixes of saturated colors
+    /// don't get as dark as with `lerp`.
+    pub fn lerp_linear(&self, other: &Color, t: f64) -> Color {
+        let channel = |a: u8, b: u8| {
+            let a = srgb_to_linear(a as f64 / 255.0);
+            let b = srgb_to_linear(b as f64 / 255.0);
+            (linear_to_srgb(a + (b - a) * t) * 255.0).round() as u8
+        };
+
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+}
+
+fn srgb_to_linear(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Parse CSS colors, i.e. hex colors, `rgb()`, `rgba()`, `hsl()`, `hsla()` and named colors.
+impl FromStr for Color {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+
+        let color = if s.starts_with('#') {
+            Color::from_hex(&s)
+        } else if let Some((name, args)) = s.strip_suffix(')').and_then(|s| s.split_once('(')) {
+            parse_color_function(name.trim(), args)
+        } else if s == "transparent" {
+            Some(Color::from_rgba(0, 0, 0, 0.0))
+        } else {
+            NAMED_COLORS
+                .binary_search_by_key(&s.as_str(), |(name, _)| name)
+                .ok()
+                .map(|i| {
+                    let [_, r, g, b] = NAMED_COLORS[i].1.to_be_bytes();
+                    Color::from_rgb(r, g, b)
+                })
+        };
+
+        color.ok_or_else(|| format!("Unknown color {}", s).into())
+    }
+}
+
+/// Arguments separated by commas or spaces, the alpha also by a slash.
+fn parse_color_function(name: &str, args: &str) -> Option<Color> {
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    if args.len() != 3 && args.len() != 4 {
+        return None;
+    }
+
+    // Numbers or percentages of the given range
+    let number = |arg: &str, range: f64| match arg.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f64>().ok().map(|v| v / 100.0 * range),
+        None => arg.parse::<f64>().ok(),
+    };
+    let alpha = match args.get(3) {
+        Some(arg) => number(arg, 1.0)?.clamp(0.0, 1.0),
+        None => 1.0,
+    };
+
+    match name {
+        "rgb" | "rgba" => {
+            let channel = |arg: &str| number(arg, 255.0).map(|v| v.round().clamp(0.0, 255.0) as u8);
+            Some(Color::from_rgba(
+                channel(args[0])?,
+                channel(args[1])?,
+                channel(args[2])?,
+                alpha,
+            ))
+        }
+        "hsl" | "hsla" => {
+            let hue = args[0]
+                .strip_suffix("deg")
+                .unwrap_or(args[0])
+                .parse()
+                .ok()?;
+            // Saturation and lightness are percentages, with or without the percent sign
+            let percentage = |arg: &str| {
+                arg.strip_suffix('%')
+                    .unwrap_or(arg)
+                    .parse::<f64>()
+                    .ok()
+                    .map(|v| v / 100.0)
+            };
+            Some(Color::from_hsla(
+                hue,
+                percentage(args[1])?,
+                percentage(args[2])?,
+                alpha,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Hex string if opaque, `rgba()` otherwise to keep the alpha exact.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.a == 1.0 {
+            write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, self.a)
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Colors at positions from 0 to 1, sampled with interpolation in linear space, e.g. to color
+/// features by an attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorRamp {
+    stops: Vec<(f64, Color)>,
+}
+
+impl ColorRamp {
+    /// Stops are sorted by their positions.
+    pub fn new(mut stops: Vec<(f64, Color)>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    /// Colors spaced evenly from 0 to 1.
+    pub fn from_colors(colors: Vec<Color>) -> Self {
+        let last = colors.len().saturating_sub(1).max(1) as f64;
+        Self::new(
+            colors
+                .into_iter()
+                .enumerate()
+                .map(|(i, color)| (i as f64 / last, color))
+                .collect(),
+        )
+    }
+
+    pub fn stops(&self) -> &[(f64, Color)] {
+        &self.stops
+    }
+
+    /// Color at a position, clamped to the first and last stops.
+    pub fn sample(&self, t: f64) -> Color {
+        let upper = self.stops.iter().position(|(stop, _)| *stop > t);
+
+        match upper {
+            None => self
+                .stops
+                .last()
+                .map_or(Color::from_rgba(0, 0, 0, 0.0), |(_, color)| color.clone()),
+            Some(0) => self.stops[0].1.clone(),
+            Some(i) => {
+                let (lower_stop, lower) = &self.stops[i - 1];
+                let (upper_stop, upper) = &self.stops[i];
+                lower.lerp_linear(upper, (t - lower_stop) / (upper_stop - lower_stop))
+            }
+        }
+    }
+
+    pub fn viridis() -> Self {
+        Self::from_hex_colors(&[
+            "#440154", "#482878", "#3e4989", "#31688e", "#26828e", "#1f9e89", "#35b779", "#6ece58",
+            "#b5de2b", "#fde725",
+        ])
+    }
+
+    pub fn magma() -> Self {
+        Self::from_hex_colors(&[
+            "#000004", "#180f3d", "#440f76", "#721f81", "#9e2f7f", "#cd4071", "#f1605d", "#fd9668",
+            "#feca8d", "#fcfdbf",
+        ])
+    }
+
+    pub fn inferno() -> Self {
+        Self::from_hex_colors(&[
+            "#000004", "#1b0c41", "#4a0c6b", "#781c6d", "#a52c60", "#cf4446", "#ed6925", "#fb9b06",
+            "#f7d13d", "#fcffa4",
+        ])
+    }
+
+    pub fn plasma() -> Self {
+        Self::from_hex_colors(&[
+            "#0d0887", "#46039f", "#7201a8", "#9c179e", "#bd3786", "#d8576b", "#ed7953", "#fb9f3a",
+            "#fdca26", "#f0f921",
+        ])
+    }
+
+    fn from_hex_colors(colors: &[&str]) -> Self {
+        Self::from_colors(colors.iter().filter_map(|c| Color::from_hex(c)).collect())
+    }
+}
+
+impl FromStr for ColorRamp {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viridis" => Ok(ColorRamp::viridis()),
+            "magma" => Ok(ColorRamp::magma()),
+            "inferno" => Ok(ColorRamp::inferno()),
+            "plasma" => Ok(ColorRamp::plasma()),
+            _ => Err(format!("Unknown color ramp {}", s).into()),
+        }
+    }
 }
 
 impl Into<wgpu::Color> for Color {
@@ -37,3 +411,274 @@ impl Into<[f32; 4]> for Color {
         ]
     }
 }
+
+/// CSS named colors, sorted by name.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(
+            Color::from_hex("#ff8000"),
+            Some(Color::from_rgb(255, 128, 0))
+        );
+        assert_eq!(Color::from_hex("#f80"), Some(Color::from_rgb(255, 136, 0)));
+        assert_eq!(
+            Color::from_hex("#ff000080"),
+            Some(Color::from_rgba(255, 0, 0, 128.0 / 255.0))
+        );
+        assert_eq!(Color::from_hex("ff0000"), None);
+        assert_eq!(
+            Color::from_hex("#ff00"),
+            Some(Color::from_rgba(255, 255, 0, 0.0))
+        );
+        assert_eq!(Color::from_hex("#ggg"), None);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let parse = |s: &str| s.parse::<Color>().ok();
+
+        assert_eq!(parse("#f80c"), Some(Color::from_rgba(255, 136, 0, 0.8)));
+        assert_eq!(
+            parse("rgb(255, 128, 0)"),
+            Some(Color::from_rgb(255, 128, 0))
+        );
+        assert_eq!(
+            parse("rgba(255,0,0,0.5)"),
+            Some(Color::from_rgba(255, 0, 0, 0.5))
+        );
+        assert_eq!(
+            parse("rgb(100% 0% 0% / 25%)"),
+            Some(Color::from_rgba(255, 0, 0, 0.25))
+        );
+        assert_eq!(
+            parse("hsl(120, 50%, 50%)"),
+            Some(Color::from_rgb(64, 191, 64))
+        );
+        assert_eq!(
+            parse("hsla(240deg, 100%, 50%, 0.5)"),
+            Some(Color::from_rgba(0, 0, 255, 0.5))
+        );
+        assert_eq!(parse(" SteelBlue "), Some(Color::from_rgb(70, 130, 180)));
+        assert_eq!(parse("transparent"), Some(Color::from_rgba(0, 0, 0, 0.0)));
+        assert_eq!(parse("rgb(1, 2)"), None);
+        assert_eq!(parse("notacolor"), None);
+    }
+
+    #[test]
+    fn test_hsl_hsv() {
+        let color = Color::from_rgb(255, 128, 0);
+
+        let (h, s, l) = color.to_hsl();
+        assert!((h - 30.1).abs() < 0.1 && s == 1.0 && (l - 0.5).abs() < 0.01);
+        assert_eq!(Color::from_hsl(h, s, l), color);
+
+        let (h, s, v) = color.to_hsv();
+        assert!((h - 30.1).abs() < 0.1 && s == 1.0 && v == 1.0);
+        assert_eq!(Color::from_hsv(h, s, v), color);
+
+        assert_eq!(
+            Color::from_hsv(-120.0, 1.0, 0.5),
+            Color::from_rgb(0, 0, 128)
+        );
+        assert_eq!(
+            Color::from_rgb(128, 128, 128).to_hsl(),
+            (0.0, 0.0, 128.0 / 255.0)
+        );
+    }
+
+    #[test]
+    fn test_lerp_linear() {
+        let red = Color::from_rgb(255, 0, 0);
+        let green = Color::from_rgb(0, 255, 0);
+
+        assert_eq!(red.lerp(&green, 0.5), Color::from_rgb(128, 128, 0));
+        assert_eq!(red.lerp_linear(&green, 0.5), Color::from_rgb(188, 188, 0));
+        assert_eq!(red.lerp_linear(&green, 1.0), green);
+    }
+
+    #[test]
+    fn test_color_ramp() {
+        let ramp = ColorRamp::from_colors(vec![
+            Color::from_rgb(0, 0, 0),
+            Color::from_rgb(255, 255, 255),
+        ]);
+        assert_eq!(ramp.sample(-1.0), Color::from_rgb(0, 0, 0));
+        assert_eq!(ramp.sample(0.5), Color::from_rgb(188, 188, 188));
+        assert_eq!(ramp.sample(2.0), Color::from_rgb(255, 255, 255));
+
+        let viridis: ColorRamp = "viridis".parse().unwrap();
+        assert_eq!(viridis.stops().len(), 10);
+        assert_eq!(viridis.sample(0.0), Color::from_rgb(68, 1, 84));
+        assert_eq!(viridis.sample(1.0), Color::from_rgb(253, 231, 37));
+        assert!("rainbow".parse::<ColorRamp>().is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let colors: Vec<Color> =
+            serde_json::from_str(r#"["red", "rgba(0, 0, 255, 0.5)"]"#).unwrap();
+        assert_eq!(
+            colors,
+            vec![Color::from_rgb(255, 0, 0), Color::from_rgba(0, 0, 255, 0.5)]
+        );
+        assert_eq!(
+            serde_json::to_string(&colors).unwrap(),
+            r##"["#ff0000","rgba(0, 0, 255, 0.5)"]"##
+        );
+        assert!(serde_json::from_str::<Color>(r#""nope""#).is_err());
+    }
+}
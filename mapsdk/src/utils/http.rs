@@ -76,12 +76,12 @@ impl<T: Clone + Debug + Eq + Hash + PartialEq> HttpRequest<T> {
 pub struct HttpResponse<T> {
     pub id: T,
 
-    response: Response,
+    response: Result<Response, reqwest::Error>,
 }
 
 impl<T> HttpResponse<T> {
     pub async fn bytes(self) -> Result<Bytes, reqwest::Error> {
-        self.response.bytes().await
+        self.response?.bytes().await
     }
 }
 
@@ -129,15 +129,12 @@ impl HttpRequestWorker {
                             }
                         };
 
-                        match resp {
-                            Ok(response) => {
-                                let http_response = HttpResponse { id, response };
-                                let _ = response_sender.send(http_response);
-                            }
-                            Err(err) => {
-                                log::error!("{}", err);
-                            }
+                        let response = resp.and_then(|resp| resp.error_for_status());
+                        if let Err(err) = &response {
+                            log::error!("{}", err);
                         }
+
+                        let _ = response_sender.send(HttpResponse { id, response });
                     }
                 }
             }
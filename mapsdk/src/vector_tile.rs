@@ -5,6 +5,8 @@ use nanoid::nanoid;
 
 use crate::feature::{Feature, Shape};
 
+const EXTENT: f64 = 4096.0;
+
 #[derive(Debug, Clone)]
 pub struct VectorTile {
     bbox: Rect,
@@ -23,7 +25,8 @@ impl VectorTile {
                     .get_features(i)?
                     .into_iter()
                     .map(|f| {
-                        let id = nanoid!();
+                        // Ids identify features across tiles, generated when tiles have none
+                        let id = f.id.map_or_else(|| nanoid!(), |id| id.to_string());
 
                         let shape = Shape::Geometry(f.geometry);
 
@@ -59,6 +62,47 @@ impl VectorTile {
     pub fn layers(&self) -> &BTreeMap<String, VectorTileLayer> {
         &self.layers
     }
+
+    /// Convert a rect from map coordinates to tile coordinates.
+    pub fn to_tile_rect(&self, rect: &Rect) -> Rect<f32> {
+        let min = self.bbox.min();
+        let max = self.bbox.max();
+        let scale_x = EXTENT / self.bbox.width();
+        let scale_y = EXTENT / self.bbox.height();
+
+        Rect::new(
+            Coord {
+                x: ((rect.min().x - min.x) * scale_x) as f32,
+                y: ((max.y - rect.min().y) * scale_y) as f32,
+            },
+            Coord {
+                x: ((rect.max().x - min.x) * scale_x) as f32,
+                y: ((max.y - rect.max().y) * scale_y) as f32,
+            },
+        )
+    }
+
+    /// Convert a feature of this tile from tile coordinates to map coordinates.
+    pub fn to_map_feature(&self, feature: &Feature<f32>) -> Feature {
+        let min = self.bbox.min();
+        let scale_x = self.bbox.width() / EXTENT;
+        let scale_y = self.bbox.height() / EXTENT;
+
+        let to_map = |coord: Coord<f32>| Coord {
+            x: min.x + coord.x as f64 * scale_x,
+            y: self.bbox.max().y - coord.y as f64 * scale_y,
+        };
+
+        let shape = match feature.shape() {
+            Shape::Geometry(geom) => Shape::Geometry(geom.map_coords(to_map)),
+            Shape::Circle { center, radius } => Shape::Circle {
+                center: to_map(*center),
+                radius: *radius as f64 * scale_x,
+            },
+        };
+
+        Feature::new(feature.id(), shape, feature.attrs().clone())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -98,4 +142,38 @@ mod tests {
             ["centroids", "countries", "geolines"]
         );
     }
+
+    #[test]
+    fn test_to_map_feature() {
+        let vt = VectorTile {
+            bbox: Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 100.0, y: 100.0 }),
+            layers: BTreeMap::new(),
+        };
+
+        let feature = Feature::<f32>::new(
+            "0",
+            Shape::Geometry(Point::new(1024.0, 1024.0).into()),
+            None,
+        );
+        if let Shape::Geometry(Geometry::Point(point)) = vt.to_map_feature(&feature).shape() {
+            assert_eq!(point, &Point::new(25.0, 75.0));
+        } else {
+            panic!("Expected a point shape");
+        }
+
+        let tile_rect = vt.to_tile_rect(&Rect::new(
+            Coord { x: 25.0, y: 75.0 },
+            Coord { x: 50.0, y: 100.0 },
+        ));
+        assert_eq!(
+            tile_rect,
+            Rect::new(
+                Coord { x: 1024.0, y: 0.0 },
+                Coord {
+                    x: 2048.0,
+                    y: 1024.0
+                }
+            )
+        );
+    }
 }